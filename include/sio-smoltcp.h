@@ -4,8 +4,133 @@
 #include <stdbool.h>
 #include <stdint.h>
 #include <stdlib.h>
-typedef void SocketHandle;
 
+/**
+ * Bumped on every change of the exported functions or of the layouts of the `repr(C)` types that breaks the bindings built for the previous version.
+ */
+#define ABI_VERSION 1
+
+/**
+ * Bits of the mask returned by `getSupportedProtocols`.
+ */
+#define PROTOCOL_IPV4 (1 << 0)
+
+#define PROTOCOL_IPV6 (1 << 1)
+
+#define PROTOCOL_IGMP (1 << 2)
+
+#define PROTOCOL_DHCPV4 (1 << 3)
+
+#define PROTOCOL_DNS (1 << 4)
+
+#define PROTOCOL_IPV4_FRAGMENTATION (1 << 5)
+
+#define PROTOCOL_SIXLOWPAN (1 << 6)
+
+#define LINKTYPE_ETHERNET 1
+
+#define LINKTYPE_RAW 101
+
+#define LINKTYPE_IEEE802_15_4_NOFCS 230
+
+/**
+ * Max count of frames an impairment stage holds when the queue of its direction is unlimited.
+ */
+#define MAX_HELD_FRAMES 1024
+
+/**
+ * Max count of rounds of polling and passing frames on in one tick of a link, a switch or a router, so devices bouncing frames off each other cannot hang it.
+ */
+#define MAX_ROUNDS_PER_TICK 64
+
+/**
+ * Returned by `ifaceGetPollAt` and `ifaceGetPollDelay` when the stack has nothing scheduled, or when they have failed.
+ */
+#define POLL_NO_DEADLINE -1
+
+/**
+ * Returned by `routerAddPort` when the port cannot be added.
+ */
+#define ROUTER_NO_PORT ~0
+
+/**
+ * Frames in a ring are stored as records: a native-endian `u32` length followed by the frame bytes, padded to a multiple of `RING_RECORD_ALIGN`.
+ */
+#define RING_RECORD_ALIGN 4
+
+/**
+ * Written by the producer instead of a record length when the rest of the data area is too small for the next record; the record then starts at offset 0.
+ */
+#define RING_WRAP_MARKER 4294967295
+
+/**
+ * Required alignment of a ring region.
+ */
+#define RING_REGION_ALIGN 64
+
+/**
+ * Which way the frames an impairment stage applies to go.
+ */
+enum CImpairmentDirection
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+	/**
+	 * The frames put into the device by the host, before the stack gets them.
+	 */
+	Rx = 0,
+	/**
+	 * The frames transmitted by the stack, before the host gets them.
+	 */
+	Tx = 1,
+};
+#ifndef __cplusplus
+typedef uint8_t CImpairmentDirection;
+#endif // __cplusplus
+
+/**
+ * How the frames of a device attached to a link get to the others.
+ */
+enum CLinkMode
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+	/**
+	 * A cable between exactly two devices.
+	 */
+	PointToPoint = 0,
+	/**
+	 * A hub or a bus: every frame goes to every other device.
+	 */
+	Shared = 1,
+};
+#ifndef __cplusplus
+typedef uint8_t CLinkMode;
+#endif // __cplusplus
+
+/**
+ * Level of a log record, or of the filter of them.
+ */
+enum CLogLevel
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+	/**
+	 * Only used as a filter, to log nothing.
+	 */
+	Off = 0,
+	Error = 1,
+	Warn = 2,
+	Info = 3,
+	Debug = 4,
+	Trace = 5,
+};
+#ifndef __cplusplus
+typedef uint8_t CLogLevel;
+#endif // __cplusplus
 
 /**
  * Type of medium of a device.
@@ -36,7 +161,202 @@ enum ICMPEchoPacketType
 typedef uint8_t ICMPEchoPacketType;
 #endif // __cplusplus
 
-typedef void *CBuilderPtr;
+/**
+ * What a full `SansIOQueue` does with one more frame.
+ */
+enum QueueOverflowPolicy
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+	/**
+	 * Drop the oldest queued frame to make room for the new one.
+	 */
+	DropOldest = 0,
+	/**
+	 * Drop the new frame.
+	 */
+	DropNewest = 1,
+	/**
+	 * Refuse the new frame with `Exhausted`.
+	 */
+	Reject = 2,
+};
+#ifndef __cplusplus
+typedef uint8_t QueueOverflowPolicy;
+#endif // __cplusplus
+
+/**
+ * The error type for the networking stack.
+ */
+enum ResultCode
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+	/**
+	 * Everything is OK, operation has succeed. Used in FFI bindings.
+	 */
+	OK = 0,
+	/**
+	 * An operation cannot proceed because a buffer is empty or full.
+	 */
+	Exhausted = 1,
+	/**
+	 * An operation is not permitted in the current state.
+	 */
+	Illegal = 2,
+	/**
+	 * An endpoint or address of a remote host could not be translated to a lower level address.
+	 * E.g. there was no an Ethernet address corresponding to an IPv4 address in the ARP cache,
+	 * or a TCP connection attempt was made to an unspecified endpoint.
+	 */
+	Unaddressable = 3,
+	/**
+	 * The operation is finished.
+	 * E.g. when reading from a TCP socket, there's no more data to read because the remote
+	 * has closed the connection.
+	 */
+	Finished = 4,
+	/**
+	 * An incoming packet could not be parsed because some of its fields were out of bounds
+	 * of the received data.
+	 */
+	Truncated = 5,
+	/**
+	 * An incoming packet had an incorrect checksum and was dropped.
+	 */
+	Checksum = 6,
+	/**
+	 * An incoming packet could not be recognized and was dropped.
+	 * E.g. an Ethernet packet with an unknown EtherType.
+	 */
+	Unrecognized = 7,
+	/**
+	 * An incoming IP packet has been split into several IP fragments and was dropped,
+	 * since IP reassembly is not supported.
+	 */
+	Fragmented = 8,
+	/**
+	 * An incoming packet was recognized but was self-contradictory.
+	 * E.g. a TCP packet with both SYN and FIN flags set.
+	 */
+	Malformed = 9,
+	/**
+	 * An incoming packet was recognized but contradicted internal state.
+	 * E.g. a TCP packet addressed to a socket that doesn't exist.
+	 */
+	Dropped = 10,
+	/**
+	 * An incoming fragment arrived too late.
+	 */
+	ReassemblyTimeout = 11,
+	/**
+	 * The packet assembler is not initialized, thus it cannot know what the final size of the
+	 * packet would be.
+	 */
+	PacketAssemblerNotInit = 12,
+	/**
+	 * The buffer of the assembler is to small and thus the final packet wont fit into it.
+	 */
+	PacketAssemblerBufferTooSmall = 13,
+	/**
+	 * The packet assembler did not receive all the fragments for assembling the final packet.
+	 */
+	PacketAssemblerIncomplete = 14,
+	/**
+	 * There are too many holes in the packet assembler (should be fixed in the future?).
+	 */
+	PacketAssemblerTooManyHoles = 15,
+	/**
+	 * There was an overlap when adding data to the packet assembler.
+	 */
+	PacketAssemblerOverlap = 16,
+	/**
+	 * The packet assembler set has no place for assembling a new stream of fragments.
+	 */
+	PacketAssemblerSetFull = 17,
+	/**
+	 * The key was not found in the packet assembler set.
+	 */
+	PacketAssemblerSetKeyNotFound = 18,
+	/**
+	 * An incoming packet was recognized but some parts are not supported by smoltcp.
+	 * E.g. some bit configuration in a packet header is not supported, but is defined in an RFC.
+	 */
+	NotSupported = 19,
+	InvalidState = 20,
+	BufferFull = 21,
+	NoFreeSlot = 22,
+	InvalidName = 23,
+	NameTooLong = 24,
+	/**
+	 * The operation is not done yet.
+	 */
+	Pending = 25,
+	/**
+	 * The operation has failed
+	 */
+	Failed = 26,
+	BufferInsufficient = 255,
+};
+#ifndef __cplusplus
+typedef uint8_t ResultCode;
+#endif // __cplusplus
+
+/**
+ * What `CBuilderPtr` points to, opaque to C. Behind it is a boxed `InterfaceBuilder`.
+ */
+typedef struct CBuilder CBuilder;
+
+typedef struct CDevice CDevice;
+
+/**
+ * What the pointers returned by the `new*Socket` functions point to, opaque to C. They are never dereferenced: a handle is the index of a slot of the socket table together with the generation of the slot, so a handle of a socket of another kind, of another device or of a deleted socket is refused with `Illegal`, and the slot of a deleted socket is reused by the next one.
+ */
+typedef struct CSocketHandle CSocketHandle;
+
+/**
+ * What the pointers returned by `dnsSocketQuery` point to, opaque to C. Behind it is a boxed `dns::QueryHandle`.
+ */
+typedef struct DNSQueryHandle DNSQueryHandle;
+
+/**
+ * What the pointers to DNS sockets point to, opaque to C. It is a `CSocketHandle` of a DNS socket.
+ */
+typedef struct DNSSocketHandle DNSSocketHandle;
+
+/**
+ * What the pointers to ICMP sockets point to, opaque to C. It is a `CSocketHandle` of an ICMP socket.
+ */
+typedef struct ICMPSocketHandle ICMPSocketHandle;
+
+/**
+ * Joins devices of the same medium within the process, passing the frames transmitted by each to the RX queues of the others.
+ */
+typedef struct Link Link;
+
+/**
+ * Forwards IPv4 and IPv6 packets between its ports, the way a gateway does: resolving the next hops with ARP and neighbour discovery, decrementing TTLs and hop limits and sending ICMP errors about the packets it cannot forward. It also answers echo requests to its own addresses.
+ */
+typedef struct Router Router;
+
+/**
+ * A learning Ethernet switch between devices of `Medium::Ethernet` within the process, with a port for the host.
+ */
+typedef struct Switch Switch;
+
+/**
+ * What the pointers to TCP sockets point to, opaque to C. It is a `CSocketHandle` of a TCP socket.
+ */
+typedef struct TCPSocketHandle TCPSocketHandle;
+
+/**
+ * What the pointers to UDP sockets point to, opaque to C. It is a `CSocketHandle` of a UDP socket.
+ */
+typedef struct UDPSocketHandle UDPSocketHandle;
+
+typedef struct CBuilder *CBuilderPtr;
 
 typedef struct CMacAddress {
 	unsigned char mac[6];
@@ -51,26 +371,288 @@ typedef struct CInterface {
 	struct CAddress addr;
 } CInterface;
 
-typedef void *CDevicePtr;
+/**
+ * Points to a `CDevice`, which C only sees as an opaque struct, so it cannot be mixed up with the other pointers.
+ */
+typedef struct CDevice *CDevicePtr;
+
+/**
+ * What an impairment stage does to the frames. All the times are on the clock of the stack, the probabilities are in `[0, 1]`.
+ */
+typedef struct CImpairmentConfig {
+	/**
+	 * Seeds the generator deciding what happens to each frame. The same seed and the same frames at the same moments give the same outcome.
+	 */
+	uint64_t seed;
+	/**
+	 * Probability of a frame being lost.
+	 */
+	double drop_probability;
+	/**
+	 * Delay added to every frame, in milliseconds.
+	 */
+	uint32_t delay_ms;
+	/**
+	 * Max random delay added to the fixed one, in milliseconds. Frames can be reordered by it.
+	 */
+	uint32_t jitter_ms;
+	/**
+	 * Probability of a frame being delivered twice.
+	 */
+	double duplicate_probability;
+	/**
+	 * Probability of a frame being held back by a random extra delay of up to `reorder_window_ms`, so the frames after it overtake it.
+	 */
+	double reorder_probability;
+	uint32_t reorder_window_ms;
+	/**
+	 * Probability of a single random bit of a frame being flipped.
+	 */
+	double bit_flip_probability;
+	/**
+	 * Rate of the link in bits per second, frames wait for the ones before them to be "sent". `0` means unlimited.
+	 */
+	uint64_t bandwidth_bps;
+} CImpairmentConfig;
+
+/**
+ * What an impairment stage has done so far.
+ */
+typedef struct CImpairmentStats {
+	/**
+	 * Frames that entered the stage.
+	 */
+	uint64_t frames;
+	uint64_t dropped;
+	uint64_t duplicated;
+	uint64_t reordered;
+	uint64_t corrupted;
+} CImpairmentStats;
+
+typedef struct Link *CLinkPtr;
+
+/**
+ * Called for each log record. `target` and `message` are NUL-terminated and only valid during the call. Can be called from any thread calling into the library.
+ */
+typedef void (*LogCallback)(void *user_data, CLogLevel level, const char *target, const char *message);
+
+typedef struct Router *CRouterPtr;
+
+/**
+ * How a NAT treats the connections. A field left 0 gets its default.
+ */
+typedef struct CNatConfig {
+	/**
+	 * How long an established TCP connection is kept without packets, in milliseconds. 7440 seconds by default.
+	 */
+	uint32_t tcp_established_ms;
+	/**
+	 * How long a TCP connection being opened or closed is kept without packets, in milliseconds. 240 seconds by default.
+	 */
+	uint32_t tcp_transitory_ms;
+	/**
+	 * 120 seconds by default.
+	 */
+	uint32_t udp_ms;
+	/**
+	 * For the echo requests, 60 seconds by default.
+	 */
+	uint32_t icmp_ms;
+	/**
+	 * The range of the ports and ICMP identifiers on the outside, 1024 to 65535 by default. A port of the inside is kept if it is in the range and free.
+	 */
+	uint16_t port_min;
+	uint16_t port_max;
+} CNatConfig;
+
+/**
+ * What a NAT has done so far.
+ */
+typedef struct CNatStats {
+	/**
+	 * Connections in the table now.
+	 */
+	uint64_t connections;
+	uint64_t created;
+	uint64_t expired;
+	/**
+	 * Packets translated on their way out.
+	 */
+	uint64_t outbound;
+	/**
+	 * Packets translated on their way in.
+	 */
+	uint64_t inbound;
+	/**
+	 * Packets that could not be translated on their way out: of other protocols, fragments but the first ones, truncated, or finding no free port.
+	 */
+	uint64_t dropped;
+	/**
+	 * Packets coming in through the outside port to an address other than the ones of the router, answering none of the connections, dropped.
+	 */
+	uint64_t unsolicited;
+} CNatStats;
+
+/**
+ * Describes one frame for `putRxPackets` and `getTxPackets`.
+ */
+typedef struct CPacketIoVec {
+	/**
+	 * The frame (RX) or the buffer to copy a frame into (TX).
+	 */
+	uint8_t *data;
+	/**
+	 * The size of the frame (RX) or of the buffer (TX). For TX it is set to the size of the frame, even if it didn't fit.
+	 */
+	uint32_t size;
+	/**
+	 * Set to the outcome for this frame.
+	 */
+	ResultCode status;
+} CPacketIoVec;
+
+/**
+ * Called when the stack is done with a buffer lent by the host, so the host can reuse or free it.
+ */
+typedef void (*HostBufferReleaseCallback)(void *user_data, uint8_t *buffer);
+
+/**
+ * Called to get a host buffer of `size` bytes the stack writes a frame to be transmitted into. Returning NULL drops the frame.
+ */
+typedef uint8_t *(*TxSlotAcquireCallback)(void *user_data, uintptr_t size);
+
+/**
+ * Called when the frame in a buffer obtained from `TxSlotAcquireCallback` is ready to be transmitted. `size` is 0 if the stack has failed to produce the frame and the buffer can be just reused.
+ */
+typedef void (*TxSlotCommitCallback)(void *user_data, uint8_t *buffer, uintptr_t size);
+
+/**
+ * Called synchronously for each frame transmitted by the stack. The frame is only valid during the call.
+ */
+typedef void (*TxCallback)(void *user_data, const uint8_t *frame, uintptr_t size);
 
-typedef SocketHandle DNSSocketHandle;
+/**
+ * What `replay` has done with the frames of a capture.
+ */
+typedef struct CReplayStats {
+	/**
+	 * Frames in the capture.
+	 */
+	uintptr_t frames;
+	/**
+	 * Frames put into the RX queue.
+	 */
+	uintptr_t injected;
+	/**
+	 * Frames skipped, because their link type doesn't match the medium of the device or they are not addressed to the interface.
+	 */
+	uintptr_t skipped;
+	/**
+	 * Frames refused by the RX queue.
+	 */
+	uintptr_t refused;
+} CReplayStats;
 
-typedef SocketHandle ICMPSocketHandle;
+/**
+ * What a router has done so far.
+ */
+typedef struct CRouterStats {
+	/**
+	 * IP packets that entered the router.
+	 */
+	uint64_t packets;
+	uint64_t forwarded;
+	/**
+	 * Packets addressed to the router itself, including neighbour discovery and echo requests.
+	 */
+	uint64_t local;
+	/**
+	 * Packets dropped because their TTL or hop limit ran out, with an ICMP Time Exceeded sent back.
+	 */
+	uint64_t time_exceeded;
+	/**
+	 * Packets dropped because there was no route, their next hop could not be resolved or they were too big, with an ICMP Destination Unreachable or Packet Too Big sent back.
+	 */
+	uint64_t unreachable;
+	/**
+	 * Packets dropped silently: malformed ones, ones that must not be forwarded and ones that did not fit into the queues.
+	 */
+	uint64_t dropped;
+} CRouterStats;
 
 typedef struct CIPEndpoint {
 	uint16_t port;
 	struct CAddress addr;
 } CIPEndpoint;
 
-typedef SocketHandle TCPSocketHandle;
+typedef struct Switch *CSwitchPtr;
 
-typedef SocketHandle UDPSocketHandle;
+/**
+ * What a switch has done so far.
+ */
+typedef struct CSwitchStats {
+	/**
+	 * Frames that entered the switch.
+	 */
+	uint64_t frames;
+	/**
+	 * Frames sent out of the only port their destination was learned on.
+	 */
+	uint64_t forwarded;
+	/**
+	 * Frames sent out of all the ports but the one they came from, because their destination is broadcast, multicast or unknown.
+	 */
+	uint64_t flooded;
+	/**
+	 * Frames not sent anywhere, because their destination was learned on the port they came from.
+	 */
+	uint64_t filtered;
+	/**
+	 * Frames too short to be Ethernet frames.
+	 */
+	uint64_t malformed;
+	/**
+	 * Copies of frames refused by the RX queues of the devices.
+	 */
+	uint64_t dropped;
+} CSwitchStats;
+
+/**
+ * The header of a single-producer/single-consumer ring living in shared memory, followed by `capacity` bytes of data area.
+ *
+ * `head` (the offset the producer writes the next record at) is only written by the producer, `tail` (the offset of the oldest unconsumed record) only by the consumer. Both must be accessed atomically, published with release and read with acquire ordering. The ring is empty when `head == tail`, and a producer never lets `head` catch up with `tail`.
+ */
+typedef struct CRingHeader {
+	uint32_t head;
+	uint8_t _head_padding[60];
+	uint32_t tail;
+	uint32_t capacity;
+	uint8_t _tail_padding[56];
+} CRingHeader;
 
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
 
-void initLogging(void);
+/**
+ * Returns the version of the ABI of the library, see `ABI_VERSION`. Bindings should refuse to use a library with a version they were not written for.
+ */
+uint32_t getAbiVersion(void);
+
+/**
+ * Returns the mask of the `PROTOCOL_*` bits of the protocols the library is built with.
+ */
+uint32_t getSupportedProtocols(void);
+
+/**
+ * Returns the mask of the kinds of sockets the library can create, the bit `1 << kind` being set for each `CSocketKind`.
+ */
+uint32_t getSupportedSocketKinds(void);
+
+/**
+ * Returns the mask of the mediums the library can create devices with, the bit `1 << medium` being set for each `CMedium`.
+ */
+uint32_t getSupportedMediums(void);
 
 CBuilderPtr newBuilder(void);
 
@@ -82,76 +664,526 @@ CBuilderPtr builderInitNeighbourCache(CBuilderPtr c_builder);
 
 CBuilderPtr builderInitIPv4ReassemblyBuffer(CBuilderPtr c_builder, uint32_t size);
 
+/**
+ * Returns NULL, leaving the builder as it was, if the prefix length doesn't fit the address.
+ */
 CBuilderPtr builderSetIPAddr(CBuilderPtr c_builder, struct CInterface my_ip);
 
+/**
+ * Returns NULL, leaving the builder as it was, if the gateway is not an IPv4 address.
+ */
 CBuilderPtr builderSetRoutes(CBuilderPtr c_builder, struct CAddress gateway_ip);
 
-CDevicePtr builderFinalize(CBuilderPtr c_builder, CMedium medium, uintptr_t mtu);
+/**
+ * Makes a device out of the builder, which is freed if this succeeds. If this fails and returns NULL, the builder stays the caller's, to be freed with `deleteBuilder`. `medium` is a `CMedium`, the ones the library doesn't support are refused.
+ */
+CDevicePtr builderFinalize(CBuilderPtr c_builder, uint8_t medium, uintptr_t mtu);
+
+/**
+ * Starts writing all the frames put into the device and transmitted by it into a pcapng file at `path` (a NUL-terminated UTF-8 string), replacing the capture going on, if any. Returns `Illegal` if the file cannot be created.
+ */
+ResultCode captureStartFile(CDevicePtr c_device, const char *path);
+
+/**
+ * Starts writing all the frames put into the device and transmitted by it into a pcapng capture in memory, replacing the capture going on, if any. The capture can be read with `captureGetBuffer`.
+ */
+void captureStartMemory(CDevicePtr c_device);
+
+/**
+ * Stops the capture going on, flushing its file or dropping its memory buffer. Returns `Illegal` if writing the file has failed at some point.
+ */
+ResultCode captureStop(CDevicePtr c_device);
+
+/**
+ * Returns the count of frames captured since the start of the capture going on, 0 if there is none.
+ */
+uint64_t captureGetFrameCount(CDevicePtr c_device);
+
+/**
+ * Copies the in-memory capture going on into `dst`. Its size is written to `capture_size` (if not NULL). If `size` is too small, nothing is copied and `BufferInsufficient` is returned; if there is no in-memory capture going on, `Illegal` is returned. The capture is a valid pcapng file at any moment.
+ */
+ResultCode captureGetBuffer(CDevicePtr c_device, uint8_t *dst, uintptr_t size, uintptr_t *capture_size);
 
-void freeDevice(CDevicePtr c_device);
+/**
+ * Returns the code of the last failure of an exported function on the calling thread, `OK` if there was none since `clearLastError`.
+ */
+ResultCode getLastError(void);
+
+/**
+ * Forgets the last error of the calling thread.
+ */
+void clearLastError(void);
+
+/**
+ * Returns the details of the last failure of an exported function on the calling thread, such as the smoltcp error or the pointer that was NULL, or NULL if there was none. The string is owned by the library and stays valid until the next failure on the thread or `clearLastError`.
+ */
+const char *getLastErrorMessage(void);
+
+/**
+ * Returns a static NUL-terminated description of a `ResultCode` (or of a socket error code, which share the values).
+ */
+const char *resultCodeToString(uint8_t code);
+
+/**
+ * Puts an impairment stage with `config` on the way of the frames going in `direction`, a `CImpairmentDirection`, replacing the one there, if any. Passing NULL removes the stage. The frames held by a replaced or removed stage are dropped. Returns `Illegal` if the direction or the config is invalid.
+ */
+ResultCode setImpairment(CDevicePtr c_device, uint8_t direction, const struct CImpairmentConfig *config);
+
+/**
+ * Writes what the impairment stage of `direction`, a `CImpairmentDirection`, has done so far into `stats`. Returns `Illegal` if the direction is invalid or there is no stage there.
+ */
+ResultCode getImpairmentStats(CDevicePtr c_device, uint8_t direction, struct CImpairmentStats *stats);
+
+/**
+ * Creates a link joining no devices yet. `mode` is a `CLinkMode`, NULL is returned for other values.
+ */
+CLinkPtr newLink(uint8_t mode);
+
+/**
+ * Frees the link, detaching the devices from it.
+ */
+void deleteLink(CLinkPtr c_link);
+
+/**
+ * Attaches the device to the link. The device must transmit into its TX queue (no TX callback, slot provider or ring) and have the medium of the devices attached before. It cannot be freed or given another TX sink until detached. Returns `Illegal` otherwise, or if both ends of a point-to-point link are taken.
+ */
+ResultCode linkAttach(CLinkPtr c_link, CDevicePtr c_device);
+
+/**
+ * Detaches the device from the link. Returns `Illegal` if it is not attached.
+ */
+ResultCode linkDetach(CLinkPtr c_link, CDevicePtr c_device);
+
+/**
+ * Polls the devices of the link at the moment `millis` of the caller's clock and passes the frames they transmit on to each other, see `Link::tick`. Returns the count of frames delivered.
+ */
+uint64_t linkTick(CLinkPtr c_link, int64_t millis);
+
+/**
+ * Returns the count of frames delivered to the devices of the link since it was created.
+ */
+uint64_t linkGetDeliveredCount(CLinkPtr c_link);
+
+/**
+ * Returns the count of frames the RX queues of the devices have refused, because they were full.
+ */
+uint64_t linkGetDropCount(CLinkPtr c_link);
+
+/**
+ * Sets up logging to stderr, or to the callback set with `setLogCallback`, at the `Trace` level unless another one was set with `setLogLevel`. Calling it again does nothing.
+ */
+void initLogging(void);
+
+/**
+ * Routes the log records to `callback` instead of stderr, setting up logging if it is not yet. Passing NULL routes them back to stderr.
+ */
+ResultCode setLogCallback(LogCallback callback, void *user_data);
+
+/**
+ * Sets the level of the least severe records logged. Records below it are not even formatted. `level` is a `CLogLevel`, `Illegal` is returned for other values.
+ */
+ResultCode setLogLevel(uint8_t level);
+
+/**
+ * Masquerades the IPv4 connections going out of the port `outside_port` of the router as coming from `public_addr`, which must be an IPv4 address of that port, replacing the NAT set before, if any. `config` can be NULL for the defaults. Returns `Illegal` if the port, the address or the config is invalid.
+ */
+ResultCode routerSetNat(CRouterPtr c_router, uintptr_t outside_port, struct CAddress public_addr, const struct CNatConfig *config);
+
+/**
+ * Stops translating, forgetting all the connections.
+ */
+void routerRemoveNat(CRouterPtr c_router);
+
+/**
+ * Writes what the NAT of the router has done so far into `stats`. Returns `Illegal` if the router has no NAT.
+ */
+ResultCode routerGetNatStats(CRouterPtr c_router, struct CNatStats *stats);
+
+/**
+ * Frees the device. Refused with `Illegal` while the host holds handles of its sockets or the device is attached to a link, a switch or a router, since they would point into the freed device: the sockets must be deleted and the device detached first. Sockets being closed gracefully don't count.
+ */
+ResultCode freeDevice(CDevicePtr c_device);
+
+/**
+ * Returns the count of sockets of the device the host holds handles of, which must be deleted before `freeDevice`.
+ */
+uintptr_t getCountOfSocketHandles(CDevicePtr c_device);
 
 uintptr_t getCountOfPacketsInTxQueue(CDevicePtr c_device);
 
 uintptr_t getLastTxPacketSize(CDevicePtr c_device);
 
-uintptr_t getLastTxPacket(CDevicePtr c_device, uint8_t *dst, uint32_t size);
+/**
+ * Copies the oldest frame of the TX queue into `dst` and removes it from the queue. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is left in the queue and `BufferInsufficient` is returned; if the queue is empty, `Exhausted` is returned.
+ */
+ResultCode getLastTxPacket(CDevicePtr c_device, uint8_t *dst, uint32_t size, uintptr_t *frame_size);
+
+/**
+ * Puts a received frame into the RX queue. Returns `Dropped` if the queue is full and its overflow policy is `DropNewest`, so the frame was dropped, and `Exhausted` if the policy is `Reject`.
+ */
+ResultCode putRxPacket(CDevicePtr c_device, const uint8_t *src, uint32_t size);
+
+/**
+ * Puts up to `count` received frames into the RX queue in one call. Stops at the first frame the queue refuses, marking it `Exhausted`, or whose data is NULL, marking it `Illegal`. The frames the queue drops because of the `DropNewest` policy are marked `Dropped` and taken, and `Dropped` is returned if nothing was refused. The count of frames taken is written to `consumed` (if not NULL).
+ */
+ResultCode putRxPackets(CDevicePtr c_device, struct CPacketIoVec *vecs, uintptr_t count, uintptr_t *consumed);
+
+/**
+ * Copies up to `count` frames out of the TX queue in one call. Stops at the first frame that doesn't fit its buffer: it is left in the queue, its slot gets `BufferInsufficient` and the required size, and `BufferInsufficient` is returned. It also stops at a slot whose buffer is NULL, marking it `Illegal`. The count of frames copied is written to `produced` (if not NULL).
+ */
+ResultCode getTxPackets(CDevicePtr c_device, struct CPacketIoVec *vecs, uintptr_t count, uintptr_t *produced);
+
+/**
+ * Sets the callback giving the buffers put with `putRxPacketBorrowed` back to the host. Frames already in the RX queue keep the callback they were put with.
+ */
+void setRxReleaseCallback(CDevicePtr c_device, HostBufferReleaseCallback callback, void *user_data);
+
+/**
+ * Puts a received frame into the RX queue without copying it. The stack processes the frame right in the host buffer (and may modify it), so the buffer must stay valid until it is passed to the callback set with `setRxReleaseCallback`. If the frame is not accepted, the buffer is released before this function returns. Returns `Illegal` if `src` is NULL or no release callback is set, `Dropped` if the queue is full and its overflow policy is `DropNewest`, and `Exhausted` if the policy is `Reject`.
+ */
+ResultCode putRxPacketBorrowed(CDevicePtr c_device, uint8_t *src, uint32_t size);
+
+/**
+ * Makes the stack write the transmitted frames directly into buffers provided by the host instead of queuing them. Pass NULL for both callbacks to go back to the TX queue. Returns `Illegal` if only one of the callbacks is NULL, or if the device is attached to a link, a switch or a router.
+ */
+ResultCode setTxSlotProvider(CDevicePtr c_device, TxSlotAcquireCallback acquire, TxSlotCommitCallback commit, void *user_data);
 
-void putRxPacket(CDevicePtr c_device, const uint8_t *src, uint32_t size);
+/**
+ * Makes the stack push each transmitted frame to `callback` synchronously, from within `ifacePoll`, instead of queuing it. Pass NULL to go back to the TX queue. Returns `Illegal` if the device is attached to a link, a switch or a router.
+ */
+ResultCode setTxCallback(CDevicePtr c_device, TxCallback callback, void *user_data);
+
+/**
+ * Makes the device exchange frames with the host through a pair of single-producer/single-consumer rings in the shared memory region of `size` bytes at `region`, without an FFI call per frame. The region (aligned to `RING_REGION_ALIGN`) is split into two equal halves, each starting with a `CRingHeader`: the host produces received frames into the first one and consumes transmitted frames from the second one. The rings are initialized by this function, and the region must stay valid until `detachRingTransport` or `freeDevice`. Returns `Illegal` if the region is misaligned or too small, or if the device is attached to a link, a switch or a router.
+ */
+ResultCode attachRingTransport(CDevicePtr c_device, uint8_t *region, uintptr_t size);
+
+/**
+ * Stops using the rings set up by `attachRingTransport` and goes back to the TX queue. Frames left in the rings are not touched.
+ */
+void detachRingTransport(CDevicePtr c_device);
+
+/**
+ * Limits the count of frames in the RX queue, `0` means unlimited. `policy` is a `QueueOverflowPolicy`, `Illegal` is returned for other values.
+ */
+ResultCode setRxQueueLimit(CDevicePtr c_device, uintptr_t capacity, uint8_t policy);
+
+/**
+ * Limits the count of frames in the TX queue, `0` means unlimited. `policy` is a `QueueOverflowPolicy`, `Illegal` is returned for other values. With the `Reject` policy the stack keeps the data in the socket buffers until the queue is drained.
+ */
+ResultCode setTxQueueLimit(CDevicePtr c_device, uintptr_t capacity, uint8_t policy);
+
+/**
+ * Returns the count of frames dropped or rejected because the RX queue was full.
+ */
+uint64_t getRxQueueDropCount(CDevicePtr c_device);
+
+/**
+ * Returns the count of frames dropped or rejected because the TX queue was full.
+ */
+uint64_t getTxQueueDropCount(CDevicePtr c_device);
 
 void ifacePoll(CDevicePtr c_device);
 
-DNSSocketHandle *newDnsSocket(CDevicePtr c_device, struct CAddress address);
+/**
+ * Polls the interface at the moment `millis` (in milliseconds) of the caller's clock instead of the wall clock. The device clock is only moved by the caller, so a simulated clock gives reproducible timing.
+ */
+void ifacePollAt(CDevicePtr c_device, int64_t millis);
+
+/**
+ * Returns the moment (in milliseconds of the device clock) at which `ifacePoll`/`ifacePollAt` should be called next, e.g. for a TCP retransmit, a DNS retry or a DHCP renewal. Returns `POLL_NO_DEADLINE` if nothing is scheduled.
+ */
+int64_t ifaceGetPollAt(CDevicePtr c_device);
+
+/**
+ * Returns how many milliseconds after the last poll the interface should be polled again. Returns `POLL_NO_DEADLINE` if nothing is scheduled.
+ */
+int64_t ifaceGetPollDelay(CDevicePtr c_device);
+
+/**
+ * Replays the frames of the pcap or pcapng file at `path` (a NUL-terminated UTF-8 string) into the device, see `replay`. What has been done with them is written to `stats` (if not NULL). Returns `Illegal` if the file cannot be read, `Malformed` if it is not a valid capture.
+ */
+ResultCode replayCaptureFile(CDevicePtr c_device, const char *path, bool only_addressed, struct CReplayStats *stats);
+
+/**
+ * Same as `replayCaptureFile`, with the capture of `size` bytes in memory at `data`. Returns `Illegal` if `data` is NULL while `size` is not 0.
+ */
+ResultCode replayCaptureBuffer(CDevicePtr c_device, const uint8_t *data, uintptr_t size, bool only_addressed, struct CReplayStats *stats);
+
+/**
+ * Creates a router with no ports yet.
+ */
+CRouterPtr newRouter(void);
+
+/**
+ * Frees the router, disconnecting the devices from it.
+ */
+void deleteRouter(CRouterPtr c_router);
+
+/**
+ * Adds a port of `medium`, a `CMedium`, sending IP packets of up to `mtu` bytes, with the hardware address `mac` if the medium is `Ethernet`. Returns the index of the port, or `ROUTER_NO_PORT` if the medium is not `Ethernet` or `Ip`.
+ */
+uintptr_t routerAddPort(CRouterPtr c_router, uint8_t medium, struct CMacAddress mac, uintptr_t mtu);
+
+/**
+ * Gives the port an address, the router answers ARP, neighbour solicitations and echo requests for it. The subnet of the address is routed to the port. Returns `Illegal` if there is no such port.
+ */
+ResultCode routerPortAddAddress(CRouterPtr c_router, uintptr_t port, struct CInterface address);
+
+/**
+ * Routes the `destination` subnet to the port, through `gateway` unless it is unspecified. Returns `Illegal` if there is no such port or the gateway is of another IP version than the destination.
+ */
+ResultCode routerAddRoute(CRouterPtr c_router, struct CInterface destination, struct CAddress gateway, uintptr_t port);
+
+/**
+ * Connects the device to the port, so the frames transmitted by each go to the other at each tick. The device must have the medium of the port and transmit into its TX queue, as for `linkAttach`. It cannot be freed or given another TX sink until disconnected. Returns `Illegal` otherwise, or if the port is connected already.
+ */
+ResultCode routerConnect(CRouterPtr c_router, uintptr_t port, CDevicePtr c_device);
+
+/**
+ * Disconnects the device connected to the port. Returns `Illegal` if none is.
+ */
+ResultCode routerDisconnect(CRouterPtr c_router, uintptr_t port);
+
+/**
+ * Puts a frame into the RX queue of the port, to be routed at the next tick. Returns `Illegal` if there is no such port.
+ */
+ResultCode routerPutRxPacket(CRouterPtr c_router, uintptr_t port, const uint8_t *src, uint32_t size);
+
+/**
+ * Copies the oldest frame sent out of the port into `dst` and removes it, for ports not connected to a device. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is kept and `BufferInsufficient` is returned; if there is no frame, `Exhausted` is returned; if there is no such port, `Illegal` is returned.
+ */
+ResultCode routerGetTxPacket(CRouterPtr c_router, uintptr_t port, uint8_t *dst, uint32_t size, uintptr_t *frame_size);
+
+/**
+ * Polls the devices connected to the router at the moment `millis` of the caller's clock and routes the frames they and the host put into the ports, see `Router::tick`. Returns the count of frames routed.
+ */
+uint64_t routerTick(CRouterPtr c_router, int64_t millis);
+
+/**
+ * Writes what the router has done so far into `stats`.
+ */
+ResultCode routerGetStats(CRouterPtr c_router, struct CRouterStats *stats);
+
+struct DNSSocketHandle *newDnsSocket(CDevicePtr c_device, struct CAddress address);
+
+/**
+ * Removes the socket from its device and frees the handle.
+ */
+void dnsSocketDelete(struct DNSSocketHandle *c_handle);
+
+/**
+ * Same as `dnsSocketDelete`, with the device passed along. The device must be the one of the socket.
+ */
+void deleteDnsSocket(CDevicePtr c_device, struct DNSSocketHandle *c_handle);
 
-void deleteDnsSocket(DNSSocketHandle *c_handle);
+const struct DNSQueryHandle *dnsSocketQuery(struct DNSSocketHandle *c_handle, const uint8_t *name, uint32_t name_size);
 
-const QueryHandle *newDnsQuery(CDevicePtr c_device, DNSSocketHandle *c_handle, const uint8_t *name, uint32_t name_size);
+/**
+ * Same as `dnsSocketQuery`, with the device passed along. The device must be the one of the socket.
+ */
+const struct DNSQueryHandle *newDnsQuery(CDevicePtr c_device, struct DNSSocketHandle *c_handle, const uint8_t *name, uint32_t name_size);
 
-ICMPSocketHandle *newIcmpSocket(CDevicePtr c_device);
+struct ICMPSocketHandle *newIcmpSocket(CDevicePtr c_device);
 
-void deleteIcmpSocket(ICMPSocketHandle *c_handle);
+/**
+ * Removes the socket from its device and frees the handle.
+ */
+void icmpSocketDelete(struct ICMPSocketHandle *c_handle);
+
+/**
+ * Same as `icmpSocketDelete`, with the device passed along. The device must be the one of the socket.
+ */
+void deleteIcmpSocket(CDevicePtr c_device, struct ICMPSocketHandle *c_handle);
 
+/**
+ * Writes an echo packet into `dst`. Returns 0 if it fits, or the size it needs if `dst_size` is too small. Returns `u32::MAX` if a buffer is NULL while its size is not 0.
+ */
 uint32_t buildIcmpV4EchoPacket(ICMPEchoPacketType tp, uint16_t ident, uint16_t seq_no, const uint8_t *payload, uint32_t payload_size, uint8_t *dst, uint32_t dst_size);
 
-ICMPBindError icmpBindAny(CDevicePtr c_device, ICMPSocketHandle *c_handle);
+ICMPBindError icmpSocketBindAny(struct ICMPSocketHandle *c_handle);
+
+/**
+ * Same as `icmpSocketBindAny`, with the device passed along. The device must be the one of the socket.
+ */
+ICMPBindError icmpBindAny(CDevicePtr c_device, struct ICMPSocketHandle *c_handle);
+
+ICMPBindError icmpSocketBindIdent(struct ICMPSocketHandle *c_handle, uint16_t ident);
 
-ICMPBindError icmpBindIdent(CDevicePtr c_device, ICMPSocketHandle *c_handle, uint16_t ident);
+/**
+ * Same as `icmpSocketBindIdent`, with the device passed along. The device must be the one of the socket.
+ */
+ICMPBindError icmpBindIdent(CDevicePtr c_device, struct ICMPSocketHandle *c_handle, uint16_t ident);
+
+ICMPBindError icmpSocketBindUDP(struct ICMPSocketHandle *c_handle, struct CIPEndpoint endpoint);
+
+/**
+ * Same as `icmpSocketBindUDP`, with the device passed along. The device must be the one of the socket.
+ */
+ICMPBindError icmpBindUDP(CDevicePtr c_device, struct ICMPSocketHandle *c_handle, struct CIPEndpoint endpoint);
+
+ICMPSendError icmpSocketSend(struct ICMPSocketHandle *c_handle, struct CAddress address, const uint8_t *data, uint32_t size);
 
-ICMPBindError icmpBindUDP(CDevicePtr c_device, ICMPSocketHandle *c_handle, struct CIPEndpoint endpoint);
+/**
+ * Same as `icmpSocketSend`, with the device passed along. The device must be the one of the socket.
+ */
+ICMPSendError icmpSend(CDevicePtr c_device, struct ICMPSocketHandle *c_handle, struct CAddress address, const uint8_t *data, uint32_t size);
 
-ICMPSendError icmpSend(CDevicePtr c_device, ICMPSocketHandle *c_handle, struct CAddress address, const uint8_t *data, uint32_t size);
+/**
+ * Copies the oldest datagram into `dst` and its source into `address`. The size of the datagram is written to `datagram_size` (if not NULL). If `size` is too small, `BufferInsufficient` is returned and the datagram is kept for the next call. Returns `Illegal` if `address` is NULL.
+ */
+ICMPRecvError icmpSocketReceive(struct ICMPSocketHandle *c_handle, struct CAddress *address, uint8_t *dst, uint32_t size, uint32_t *datagram_size);
 
-ICMPRecvError icmpReceive(CDevicePtr c_device, ICMPSocketHandle *c_handle, struct CAddress *address, uint8_t *dst, uint32_t size);
+/**
+ * Same as `icmpSocketReceive`, with the device passed along. The device must be the one of the socket.
+ */
+ICMPRecvError icmpReceive(CDevicePtr c_device, struct ICMPSocketHandle *c_handle, struct CAddress *address, uint8_t *dst, uint32_t size, uint32_t *datagram_size);
 
 /**
  * Deletes a socket using its handle. While you currently can use ths function to delete sockets, using specialized functions is more future-proof since I can imagine that we can have to store some auxillary info besides the handle.
  */
-void delete_socket(SocketHandle *c_handle);
+void delete_socket(CDevicePtr c_device, struct CSocketHandle *c_handle);
 
-TCPSocketHandle *newTcpSocket(CDevicePtr c_device);
+struct TCPSocketHandle *newTcpSocket(CDevicePtr c_device);
 
-void deleteTcpSocket(TCPSocketHandle *c_handle);
+/**
+ * Frees the handle and removes the socket from its device. If `graceful` is set, the connection is closed first (sending FIN once the data enqueued so far is sent), and the socket is removed after the connection is over, during one of the later polls. Otherwise the socket is removed right away, without notifying the remote.
+ */
+void tcpSocketDelete(struct TCPSocketHandle *c_handle, bool graceful);
 
-TCPConnectError tcpConnect(CDevicePtr c_device, TCPSocketHandle *c_handle, struct CIPEndpoint c_endpoint, uint16_t local_port);
+/**
+ * Same as `tcpSocketDelete`, with the device passed along. The device must be the one of the socket.
+ */
+void deleteTcpSocket(CDevicePtr c_device, struct TCPSocketHandle *c_handle, bool graceful);
 
-void tcpSend(CDevicePtr c_device, TCPSocketHandle *c_handle, const uint8_t *data, uint32_t size);
+TCPConnectError tcpSocketConnect(struct TCPSocketHandle *c_handle, struct CIPEndpoint c_endpoint, uint16_t local_port);
 
-void tcpReceive(CDevicePtr c_device, TCPSocketHandle *c_handle, uint8_t *data, uint32_t size);
+/**
+ * Same as `tcpSocketConnect`, with the device passed along. The device must be the one of the socket.
+ */
+TCPConnectError tcpConnect(CDevicePtr c_device, struct TCPSocketHandle *c_handle, struct CIPEndpoint c_endpoint, uint16_t local_port);
 
-void tcpListen(CDevicePtr c_device, TCPSocketHandle *c_handle, uint16_t port);
+/**
+ * Enqueues up to `size` bytes of `data` for sending. The count of bytes actually enqueued, which is less than `size` if the send buffer is full, is written to `sent` (if not NULL).
+ */
+TCPSendError tcpSocketSend(struct TCPSocketHandle *c_handle, const uint8_t *data, uint32_t size, uint32_t *sent);
 
-bool tcpIsActive(CDevicePtr c_device, TCPSocketHandle *c_handle);
+/**
+ * Same as `tcpSocketSend`, with the device passed along. The device must be the one of the socket.
+ */
+TCPSendError tcpSend(CDevicePtr c_device, struct TCPSocketHandle *c_handle, const uint8_t *data, uint32_t size, uint32_t *sent);
 
-UDPSocketHandle *newUdpSocket(CDevicePtr c_device);
+/**
+ * Copies up to `size` bytes of received data into `data`. Since TCP is a stream, the data not fitting the buffer just stays in the socket for the next call. The count of bytes copied is written to `received` (if not NULL).
+ */
+TCPRecvError tcpSocketReceive(struct TCPSocketHandle *c_handle, uint8_t *data, uint32_t size, uint32_t *received);
 
-void deleteUdpSocket(UDPSocketHandle *c_handle);
+/**
+ * Same as `tcpSocketReceive`, with the device passed along. The device must be the one of the socket.
+ */
+TCPRecvError tcpReceive(CDevicePtr c_device, struct TCPSocketHandle *c_handle, uint8_t *data, uint32_t size, uint32_t *received);
 
-UDPBindError udpBind(CDevicePtr c_device, UDPSocketHandle *c_handle, uint16_t port);
+TCPListenError tcpSocketListen(struct TCPSocketHandle *c_handle, uint16_t port);
 
-uint32_t udpGetLastReceivedPacketSize(CDevicePtr c_device, UDPSocketHandle *c_handle);
+/**
+ * Same as `tcpSocketListen`, with the device passed along. The device must be the one of the socket.
+ */
+TCPListenError tcpListen(CDevicePtr c_device, struct TCPSocketHandle *c_handle, uint16_t port);
+
+bool tcpSocketIsActive(struct TCPSocketHandle *c_handle);
+
+/**
+ * Same as `tcpSocketIsActive`, with the device passed along. The device must be the one of the socket.
+ */
+bool tcpIsActive(CDevicePtr c_device, struct TCPSocketHandle *c_handle);
+
+struct UDPSocketHandle *newUdpSocket(CDevicePtr c_device);
+
+/**
+ * Removes the socket from its device and frees the handle.
+ */
+void udpSocketDelete(struct UDPSocketHandle *c_handle);
+
+/**
+ * Same as `udpSocketDelete`, with the device passed along. The device must be the one of the socket.
+ */
+void deleteUdpSocket(CDevicePtr c_device, struct UDPSocketHandle *c_handle);
+
+UDPBindError udpSocketBind(struct UDPSocketHandle *c_handle, uint16_t port);
+
+/**
+ * Same as `udpSocketBind`, with the device passed along. The device must be the one of the socket.
+ */
+UDPBindError udpBind(CDevicePtr c_device, struct UDPSocketHandle *c_handle, uint16_t port);
+
+uint32_t udpSocketGetLastReceivedPacketSize(struct UDPSocketHandle *c_handle);
+
+/**
+ * Same as `udpSocketGetLastReceivedPacketSize`, with the device passed along. The device must be the one of the socket.
+ */
+uint32_t udpGetLastReceivedPacketSize(CDevicePtr c_device, struct UDPSocketHandle *c_handle);
+
+/**
+ * Copies the oldest datagram into `dst` and its source into `endpoint`. The size of the datagram is written to `datagram_size` (if not NULL). If `size` is too small, the datagram is left in the socket and `BufferInsufficient` is returned. Returns `Illegal` if `endpoint` is NULL.
+ */
+UDPRecvError udpSocketReceive(struct UDPSocketHandle *c_handle, struct CIPEndpoint *endpoint, uint8_t *dst, uint32_t size, uint32_t *datagram_size);
 
-UDPRecvError udpReceive(CDevicePtr c_device, UDPSocketHandle *c_handle, struct CIPEndpoint *endpoint, uint8_t *dst, uint32_t size);
+/**
+ * Same as `udpSocketReceive`, with the device passed along. The device must be the one of the socket.
+ */
+UDPRecvError udpReceive(CDevicePtr c_device, struct UDPSocketHandle *c_handle, struct CIPEndpoint *endpoint, uint8_t *dst, uint32_t size, uint32_t *datagram_size);
 
-UDPSendError udpSend(CDevicePtr c_device, UDPSocketHandle *c_handle, struct CIPEndpoint endpoint, const uint8_t *data, uint32_t size);
+UDPSendError udpSocketSend(struct UDPSocketHandle *c_handle, struct CIPEndpoint endpoint, const uint8_t *data, uint32_t size);
+
+/**
+ * Same as `udpSocketSend`, with the device passed along. The device must be the one of the socket.
+ */
+UDPSendError udpSend(CDevicePtr c_device, struct UDPSocketHandle *c_handle, struct CIPEndpoint endpoint, const uint8_t *data, uint32_t size);
+
+/**
+ * Creates a switch with no devices attached yet. Learned addresses are forgotten after not being seen for `ageing_ms` milliseconds, 0 means the default of 300 seconds.
+ */
+CSwitchPtr newSwitch(uint32_t ageing_ms);
+
+/**
+ * Frees the switch, detaching the devices from it.
+ */
+void deleteSwitch(CSwitchPtr c_switch);
+
+/**
+ * Attaches the device to a port of the switch. The device must be of `Medium::Ethernet` and transmit into its TX queue, as for `linkAttach`. It cannot be freed or given another TX sink until detached. Returns `Illegal` otherwise.
+ */
+ResultCode switchAttach(CSwitchPtr c_switch, CDevicePtr c_device);
+
+/**
+ * Detaches the device from the switch. Returns `Illegal` if it is not attached.
+ */
+ResultCode switchDetach(CSwitchPtr c_switch, CDevicePtr c_device);
+
+/**
+ * Polls the devices of the switch at the moment `millis` of the caller's clock and switches the frames they transmit, see `Switch::tick`. Returns the count of frames switched.
+ */
+uint64_t switchTick(CSwitchPtr c_switch, int64_t millis);
+
+/**
+ * Puts a frame into the switch through the host port. It reaches the devices at their next poll. Returns `Illegal` if `src` is NULL.
+ */
+ResultCode switchInjectFrame(CSwitchPtr c_switch, const uint8_t *src, uint32_t size);
+
+/**
+ * Copies the oldest frame sent out of the host port into `dst` and removes it. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is kept and `BufferInsufficient` is returned; if there is no frame, `Exhausted` is returned.
+ */
+ResultCode switchPopHostFrame(CSwitchPtr c_switch, uint8_t *dst, uint32_t size, uintptr_t *frame_size);
+
+/**
+ * Writes what the switch has done so far into `stats`.
+ */
+ResultCode switchGetStats(CSwitchPtr c_switch, struct CSwitchStats *stats);
+
+/**
+ * Returns the count of addresses in the table of the switch, aged out at each tick.
+ */
+uintptr_t switchGetMacTableSize(CSwitchPtr c_switch);
 
 #ifdef __cplusplus
 } // extern "C"
@@ -1,17 +1,27 @@
+import typing
+from ctypes import byref, cast, string_at
+
 from netaddr import EUI
 
 from .address import CAddress, CInterface, CMacAddress
-from .ctypes.enums import Medium, MediumIntT
+from .ctypes.callbacks import TxCallback
+from .ctypes.enums import ErrorCode, ImpairmentDirection, ImpairmentDirectionIntT, Medium, MediumIntT, QueueOverflowPolicy, QueueOverflowPolicyIntT
 from .ctypes.functions import c_uint32, freeDevice
+from .ctypes.functions import c_bool, getImpairmentStats, replayCaptureBuffer, replayCaptureFile, setImpairment
+from .ctypes.functions import captureGetBuffer, captureGetFrameCount, captureStartFile, captureStartMemory, captureStop
 from .ctypes.functions import getCountOfPacketsInTxQueue as getCountOfPacketsInTxQueue_ct
 from .ctypes.functions import getLastTxPacket as getLastTxPacket_ct
 from .ctypes.functions import getLastTxPacketSize as getLastTxPacketSize_ct
-from .ctypes.functions import ifacePoll
+from .ctypes.functions import POLL_NO_DEADLINE, c_int64, ifaceGetPollAt, ifaceGetPollDelay, ifacePoll, ifacePollAt
+from .ctypes.functions import getRxQueueDropCount, getTxQueueDropCount, setRxQueueLimit, setTxCallback, setTxQueueLimit
+from .ctypes.functions import c_uint8_p, getTxPackets, putRxPackets
 from .ctypes.functions import putRxPacket as putRxPacket_ct
 from .ctypes.functions import size_t, uintptr_t
 from .ctypes.opaque import CDevicePtr
+from .ctypes.structs import CImpairmentConfig, CImpairmentStats, CPacketIoVec, CReplayStats
 from .ctypes.utils import byteStringToPointer
 from .dns import DNSSocket
+from .errors import SmolTCPError
 from .icmp import ICMPSocket
 from .tcp import TCPSocket
 from .udp import UDPSocket
@@ -29,28 +39,87 @@ def getLastTxPacketSize(c_device: CDevicePtr) -> size_t:
 
 def getLastTxPacket(c_device: CDevicePtr, dst: bytearray) -> int:
 	buf, size = byteStringToPointer(dst)
-	return int(getLastTxPacket_ct(c_device, buf, c_uint32(size)))
+	frameSize = size_t(0)
+	res = ErrorCode(getLastTxPacket_ct(c_device, buf, c_uint32(size), byref(frameSize)))
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res, frameSize.value)
+	return frameSize.value
 
 
 def putRxPacket(c_device: CDevicePtr, data: bytes) -> None:
 	buf, size = byteStringToPointer(data)
-	return putRxPacket_ct(c_device, buf, c_uint32(size))
+	res = ErrorCode(putRxPacket_ct(c_device, buf, c_uint32(size)))
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res)
+
+
+def putRxPacketsBatch(c_device: CDevicePtr, frames: typing.Sequence[bytes]) -> int:
+	vecs = (CPacketIoVec * len(frames))()
+	for vec, frame in zip(vecs, frames):
+		buf, size = byteStringToPointer(frame)
+		vec.data = cast(buf, c_uint8_p)
+		vec.size = size
+	consumed = size_t(0)
+	putRxPackets(c_device, vecs, size_t(len(frames)), byref(consumed))
+	return consumed.value
+
+
+def getTxPacketsBatch(c_device: CDevicePtr, count: int, slotSize: int) -> typing.List[bytearray]:
+	bufs = [bytearray(slotSize) for _ in range(count)]
+	vecs = (CPacketIoVec * count)()
+	for vec, buf in zip(vecs, bufs):
+		ptr, size = byteStringToPointer(buf)
+		vec.data = cast(ptr, c_uint8_p)
+		vec.size = size
+	produced = size_t(0)
+	res = ErrorCode(getTxPackets(c_device, vecs, size_t(count), byref(produced)))
+	if res == ErrorCode.BufferInsufficient and not produced.value:
+		raise SmolTCPError(res, vecs[0].size)
+	return [buf[: vec.size] for buf, vec in zip(bufs[: produced.value], vecs)]
 
 
 class Device(Resource):
-	__slots__ = ()
+	__slots__ = ("_txCallback",)
 	DTOR = freeDevice
 
+	def __init__(self, ptr: int) -> None:
+		super().__init__(ptr)
+		self._txCallback = None
+
+	def free(self):
+		if self._ptr:
+			res = ErrorCode(freeDevice(self._ptr))
+			if res != ErrorCode.OK:
+				raise SmolTCPError(res)
+			self._ptr = None
+
 	def getCountOfPacketsInTxQueue(self):
 		if self._ptr:
 			return getCountOfPacketsInTxQueue(self.ptr)
 		return 0
 
-	def poll(self) -> None:
-		ifacePoll(self.ptr)
+	def poll(self, millis: typing.Optional[int] = None) -> None:
+		if millis is None:
+			ifacePoll(self.ptr)
+		else:
+			ifacePollAt(self.ptr, c_int64(millis))
+
+	def pollAt(self) -> typing.Optional[int]:
+		res = int(ifaceGetPollAt(self.ptr))
+		if res == POLL_NO_DEADLINE:
+			return None
+		return res
+
+	def pollDelay(self) -> typing.Optional[int]:
+		res = int(ifaceGetPollDelay(self.ptr))
+		if res == POLL_NO_DEADLINE:
+			return None
+		return res
 
 	def pop(self) -> bytearray:
 		sz = getLastTxPacketSize(self.ptr)
+		if not sz:
+			return bytearray()
 		res = bytearray(sz)
 		getLastTxPacket(self.ptr, res)
 		return res
@@ -58,6 +127,92 @@ class Device(Resource):
 	def put(self, data: bytearray) -> None:
 		putRxPacket(self.ptr, data)
 
+	def putMany(self, frames: typing.Sequence[bytes]) -> int:
+		return putRxPacketsBatch(self.ptr, frames)
+
+	def popMany(self, count: int, slotSize: int = 65535) -> typing.List[bytearray]:
+		return getTxPacketsBatch(self.ptr, count, slotSize)
+
+	def setRxQueueLimit(self, capacity: int, policy: QueueOverflowPolicy = QueueOverflowPolicy.DropOldest) -> None:
+		res = ErrorCode(setRxQueueLimit(self.ptr, size_t(capacity), QueueOverflowPolicyIntT(policy)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def setTxQueueLimit(self, capacity: int, policy: QueueOverflowPolicy = QueueOverflowPolicy.DropOldest) -> None:
+		res = ErrorCode(setTxQueueLimit(self.ptr, size_t(capacity), QueueOverflowPolicyIntT(policy)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def setTxCallback(self, callback: typing.Optional[typing.Callable[[bytes], None]]) -> None:
+		if callback is None:
+			ctCallback = TxCallback()
+		else:
+			ctCallback = TxCallback(lambda _userData, frame, size: callback(string_at(frame, size)))
+		res = ErrorCode(setTxCallback(self.ptr, ctCallback, None))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		self._txCallback = ctCallback
+
+	def startCapture(self, path: typing.Optional[str] = None) -> None:
+		if path is None:
+			captureStartMemory(self.ptr)
+			return
+		res = ErrorCode(captureStartFile(self.ptr, str(path).encode("utf-8")))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def stopCapture(self) -> None:
+		res = ErrorCode(captureStop(self.ptr))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def getCapture(self) -> bytes:
+		captureSize = size_t(0)
+		res = ErrorCode(captureGetBuffer(self.ptr, None, size_t(0), byref(captureSize)))
+		if res not in (ErrorCode.OK, ErrorCode.BufferInsufficient):
+			raise SmolTCPError(res)
+		dst = bytearray(captureSize.value)
+		buf, size = byteStringToPointer(dst)
+		res = ErrorCode(captureGetBuffer(self.ptr, buf, size_t(size), byref(captureSize)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return bytes(dst[: captureSize.value])
+
+	def replay(self, capture: typing.Union[str, bytes], onlyAddressed: bool = False) -> CReplayStats:
+		stats = CReplayStats()
+		if isinstance(capture, str):
+			res = ErrorCode(replayCaptureFile(self.ptr, capture.encode("utf-8"), c_bool(onlyAddressed), byref(stats)))
+		else:
+			buf, size = byteStringToPointer(capture)
+			res = ErrorCode(replayCaptureBuffer(self.ptr, buf, size_t(size), c_bool(onlyAddressed), byref(stats)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return stats
+
+	@property
+	def capturedFrameCount(self) -> int:
+		return int(captureGetFrameCount(self.ptr))
+
+	def setImpairment(self, direction: ImpairmentDirection, config: typing.Optional[CImpairmentConfig]) -> None:
+		res = ErrorCode(setImpairment(self.ptr, ImpairmentDirectionIntT(direction), byref(config) if config is not None else None))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def impairmentStats(self, direction: ImpairmentDirection) -> CImpairmentStats:
+		stats = CImpairmentStats()
+		res = ErrorCode(getImpairmentStats(self.ptr, ImpairmentDirectionIntT(direction), byref(stats)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return stats
+
+	@property
+	def rxDropCount(self) -> int:
+		return int(getRxQueueDropCount(self.ptr))
+
+	@property
+	def txDropCount(self) -> int:
+		return int(getTxQueueDropCount(self.ptr))
+
 	def tcpSocket(self) -> TCPSocket:
 		return TCPSocket(self)
 
@@ -3,7 +3,7 @@ from ipaddress import IPv4Interface, _BaseAddress
 from netaddr import EUI
 
 from .address import CAddress, CInterface, CMacAddress
-from .ctypes.enums import Medium, MediumIntT
+from .ctypes.enums import ErrorCode, Medium, MediumIntT
 from .ctypes.functions import builderFinalize as builderFinalize_ct
 from .ctypes.functions import builderInitIPv4ReassemblyBuffer, builderInitNeighbourCache, builderInitSixlowpan
 from .ctypes.functions import builderSetHardwareAddr as builderSetHardwareAddr_ct
@@ -12,6 +12,7 @@ from .ctypes.functions import builderSetRoutes as builderSetRoutes_ct
 from .ctypes.functions import deleteBuilder, newBuilder, uintptr_t
 from .ctypes.opaque import CBuilderPtr, CDevicePtr
 from .Device import Device
+from .errors import SmolTCPError
 
 
 def builderSetHardwareAddr(c_builder: CBuilderPtr, mac: EUI) -> CBuilderPtr:
@@ -30,6 +31,12 @@ def builderFinalize(c_builder: CBuilderPtr, medium: Medium, mtu: int) -> CDevice
 	return builderFinalize_ct(c_builder, MediumIntT(medium), uintptr_t(mtu))
 
 
+# the setters return the builder, or NULL if they have failed and left it as it was
+def checkBuilder(c_builder: CBuilderPtr) -> None:
+	if not c_builder:
+		raise SmolTCPError(ErrorCode.Illegal)
+
+
 class DeviceBuilder:
 	__slots__ = ("ptr",)
 
@@ -37,25 +44,28 @@ class DeviceBuilder:
 		self.ptr = newBuilder()
 
 	def setHardwareAddr(self, mac: EUI):
-		self.ptr = builderSetHardwareAddr(self.ptr, mac)
+		checkBuilder(builderSetHardwareAddr(self.ptr, mac))
 
 	def initNeighbourCache(self):
-		self.ptr = builderInitNeighbourCache(self.ptr)
+		checkBuilder(builderInitNeighbourCache(self.ptr))
 
 	def initSixlowpan(self):
-		self.ptr = builderInitSixlowpan(self.ptr)
+		checkBuilder(builderInitSixlowpan(self.ptr))
 
 	def initIPv4FragmentsCache(self, size: int = 4096) -> None:
-		self.ptr = builderInitIPv4ReassemblyBuffer(self.ptr, size)
+		checkBuilder(builderInitIPv4ReassemblyBuffer(self.ptr, size))
 
 	def setIPAddr(self, my_ip: IPv4Interface) -> None:
-		self.ptr = builderSetIPAddr(self.ptr, my_ip)
+		checkBuilder(builderSetIPAddr(self.ptr, my_ip))
 
 	def setRoutes(self, gateway: _BaseAddress) -> None:
-		self.ptr = builderSetRoutes(self.ptr, gateway)
+		checkBuilder(builderSetRoutes(self.ptr, gateway))
 
+	# the builder is freed if a device is made of it, and stays ours to free otherwise
 	def finalize(self, medium: Medium, mtu: int) -> Device:
 		devicePtr = builderFinalize(self.ptr, medium, mtu)
+		if not devicePtr:
+			raise SmolTCPError(ErrorCode.Illegal)
 		self.ptr = None
 		return Device(devicePtr)
 
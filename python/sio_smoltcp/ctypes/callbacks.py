@@ -0,0 +1,9 @@
+from ctypes import CFUNCTYPE, POINTER, c_char_p, c_size_t, c_uint8, c_void_p
+
+c_uint8_p = POINTER(c_uint8)
+
+HostBufferReleaseCallback = CFUNCTYPE(None, c_void_p, c_uint8_p)
+TxSlotAcquireCallback = CFUNCTYPE(c_uint8_p, c_void_p, c_size_t)
+TxSlotCommitCallback = CFUNCTYPE(None, c_void_p, c_uint8_p, c_size_t)
+TxCallback = CFUNCTYPE(None, c_void_p, c_uint8_p, c_size_t)
+LogCallback = CFUNCTYPE(None, c_void_p, c_uint8, c_char_p, c_char_p)
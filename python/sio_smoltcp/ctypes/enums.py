@@ -1,5 +1,5 @@
 from ctypes import c_uint8
-from enum import IntEnum
+from enum import IntEnum, IntFlag
 
 class Medium(IntEnum):
 	Invalid = 0
@@ -8,6 +8,12 @@ class Medium(IntEnum):
 	Ieee802154 = 4
 
 
+class QueueOverflowPolicy(IntEnum):
+	DropOldest = 0
+	DropNewest = 1
+	Reject = 2
+
+
 class ErrorCode(IntEnum):
 	OK = 0
 	Exhausted = 1
@@ -40,6 +46,25 @@ class ErrorCode(IntEnum):
 
 	BufferInsufficient = 0xFF
 
+class ImpairmentDirection(IntEnum):
+	Rx = 0
+	Tx = 1
+
+
+class LinkMode(IntEnum):
+	PointToPoint = 0
+	Shared = 1
+
+
+class LogLevel(IntEnum):
+	Off = 0
+	Error = 1
+	Warn = 2
+	Info = 3
+	Debug = 4
+	Trace = 5
+
+
 class DNSType(IntEnum):
 	A     = 0x0001
 	Ns    = 0x0002
@@ -48,9 +73,30 @@ class DNSType(IntEnum):
 	Aaaa  = 0x001c
 
 
+class Protocol(IntFlag):
+	IPv4 = 1 << 0
+	IPv6 = 1 << 1
+	IGMP = 1 << 2
+	DHCPv4 = 1 << 3
+	DNS = 1 << 4
+	IPv4Fragmentation = 1 << 5
+	Sixlowpan = 1 << 6
+
+
+class SocketKind(IntEnum):
+	TCP = 1
+	UDP = 2
+	ICMP = 3
+	DNS = 4
+
+
 UDPBindError = TCPConnectError = UDPSendError = ErrorCode
 
 
 MediumIntT = c_uint8
+QueueOverflowPolicyIntT = c_uint8
 IPVersionIntT = c_uint8
 ErrorCodeIntT = c_uint8
+LogLevelIntT = c_uint8
+ImpairmentDirectionIntT = c_uint8
+LinkModeIntT = c_uint8
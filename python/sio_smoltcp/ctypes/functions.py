@@ -1,10 +1,11 @@
-from ctypes import POINTER, c_int, c_ubyte, c_uint8, c_uint16, c_uint32, c_ulong
+from ctypes import POINTER, c_bool, c_char_p, c_int, c_void_p, c_int64, c_ubyte, c_uint8, c_uint16, c_uint32, c_uint64, c_ulong
 
 from ._funcToCtypesSignatureConvertor import assignTypesFromFunctionSignature as atffs
-from .enums import ErrorCodeIntT, MediumIntT
+from .callbacks import HostBufferReleaseCallback, LogCallback, TxCallback, TxSlotAcquireCallback, TxSlotCommitCallback
+from .enums import ErrorCodeIntT, ImpairmentDirectionIntT, LinkModeIntT, LogLevelIntT, MediumIntT, QueueOverflowPolicyIntT
 from .library import lib
-from .opaque import CBuilderPtr, CDevicePtr, DNSQueryHandlePtr, DNSSocketPtr, ICMPSocketPtr, TCPSocketPtr, UDPSocketPtr
-from .structs import CAddress, CInterface, CMacAddress, IPEndpoint, IPEndpointPtr
+from .opaque import CBuilderPtr, CDevicePtr, CLinkPtr, CRouterPtr, CSwitchPtr, DNSQueryHandlePtr, DNSSocketPtr, ICMPSocketPtr, TCPSocketPtr, UDPSocketPtr
+from .structs import CAddress, CImpairmentConfigPtr, CImpairmentStatsPtr, CInterface, CMacAddress, CPacketIoVecPtr, CNatConfigPtr, CNatStatsPtr, CReplayStatsPtr, CRouterStatsPtr, CSwitchStatsPtr, IPEndpoint, IPEndpointPtr
 
 # pylint:disable=too-many-arguments
 
@@ -79,13 +80,20 @@ def builderFinalize(c_builder: CBuilderPtr, medium: MediumIntT, mtu: uintptr_t)
 _builderFinalize = atffs(builderFinalize, lib)
 
 
-def freeDevice(c_device: CDevicePtr) -> None:
+def freeDevice(c_device: CDevicePtr) -> ErrorCodeIntT:
 	return _freeDevice(c_device)
 
 
 _freeDevice = atffs(freeDevice, lib)
 
 
+def getCountOfSocketHandles(c_device: CDevicePtr) -> size_t:
+	return _getCountOfSocketHandles(c_device)
+
+
+_getCountOfSocketHandles = atffs(getCountOfSocketHandles, lib)
+
+
 def getCountOfPacketsInTxQueue(c_device: CDevicePtr) -> size_t:
 	return _getCountOfPacketsInTxQueue(c_device)
 
@@ -100,20 +108,380 @@ def getLastTxPacketSize(c_device: CDevicePtr) -> size_t:
 _getLastTxPacketSize = atffs(getLastTxPacketSize, lib)
 
 
-def getLastTxPacket(c_device: CDevicePtr, dst: c_uint8_p, size: c_uint32) -> size_t:
-	return _getLastTxPacket(c_device, dst, size)
+def getLastTxPacket(c_device: CDevicePtr, dst: c_uint8_p, size: c_uint32, frame_size: POINTER(size_t)) -> ErrorCodeIntT:
+	return _getLastTxPacket(c_device, dst, size, frame_size)
 
 
 _getLastTxPacket = atffs(getLastTxPacket, lib)
 
 
-def putRxPacket(c_device: CDevicePtr, src: c_uint8_p, size: c_uint32) -> None:
+def putRxPacket(c_device: CDevicePtr, src: c_uint8_p, size: c_uint32) -> ErrorCodeIntT:
 	return _putRxPacket(c_device, src, size)
 
 
 _putRxPacket = atffs(putRxPacket, lib)
 
 
+def putRxPackets(c_device: CDevicePtr, vecs: CPacketIoVecPtr, count: size_t, consumed: POINTER(size_t)) -> ErrorCodeIntT:
+	return _putRxPackets(c_device, vecs, count, consumed)
+
+
+_putRxPackets = atffs(putRxPackets, lib)
+
+
+def getTxPackets(c_device: CDevicePtr, vecs: CPacketIoVecPtr, count: size_t, produced: POINTER(size_t)) -> ErrorCodeIntT:
+	return _getTxPackets(c_device, vecs, count, produced)
+
+
+_getTxPackets = atffs(getTxPackets, lib)
+
+
+def setRxReleaseCallback(c_device: CDevicePtr, callback: HostBufferReleaseCallback, user_data: c_void_p) -> None:
+	return _setRxReleaseCallback(c_device, callback, user_data)
+
+
+_setRxReleaseCallback = atffs(setRxReleaseCallback, lib)
+
+
+def putRxPacketBorrowed(c_device: CDevicePtr, src: c_uint8_p, size: c_uint32) -> ErrorCodeIntT:
+	return _putRxPacketBorrowed(c_device, src, size)
+
+
+_putRxPacketBorrowed = atffs(putRxPacketBorrowed, lib)
+
+
+def setTxSlotProvider(c_device: CDevicePtr, acquire: TxSlotAcquireCallback, commit: TxSlotCommitCallback, user_data: c_void_p) -> ErrorCodeIntT:
+	return _setTxSlotProvider(c_device, acquire, commit, user_data)
+
+
+_setTxSlotProvider = atffs(setTxSlotProvider, lib)
+
+
+def setTxCallback(c_device: CDevicePtr, callback: TxCallback, user_data: c_void_p) -> ErrorCodeIntT:
+	return _setTxCallback(c_device, callback, user_data)
+
+
+_setTxCallback = atffs(setTxCallback, lib)
+
+
+def attachRingTransport(c_device: CDevicePtr, region: c_void_p, size: size_t) -> ErrorCodeIntT:
+	return _attachRingTransport(c_device, region, size)
+
+
+_attachRingTransport = atffs(attachRingTransport, lib)
+
+
+def detachRingTransport(c_device: CDevicePtr) -> None:
+	return _detachRingTransport(c_device)
+
+
+_detachRingTransport = atffs(detachRingTransport, lib)
+
+
+def setRxQueueLimit(c_device: CDevicePtr, capacity: size_t, policy: QueueOverflowPolicyIntT) -> ErrorCodeIntT:
+	return _setRxQueueLimit(c_device, capacity, policy)
+
+
+_setRxQueueLimit = atffs(setRxQueueLimit, lib)
+
+
+def setTxQueueLimit(c_device: CDevicePtr, capacity: size_t, policy: QueueOverflowPolicyIntT) -> ErrorCodeIntT:
+	return _setTxQueueLimit(c_device, capacity, policy)
+
+
+_setTxQueueLimit = atffs(setTxQueueLimit, lib)
+
+
+def getRxQueueDropCount(c_device: CDevicePtr) -> c_uint64:
+	return _getRxQueueDropCount(c_device)
+
+
+_getRxQueueDropCount = atffs(getRxQueueDropCount, lib)
+
+
+def getTxQueueDropCount(c_device: CDevicePtr) -> c_uint64:
+	return _getTxQueueDropCount(c_device)
+
+
+_getTxQueueDropCount = atffs(getTxQueueDropCount, lib)
+
+
+def captureStartFile(c_device: CDevicePtr, path: c_char_p) -> ErrorCodeIntT:
+	return _captureStartFile(c_device, path)
+
+
+_captureStartFile = atffs(captureStartFile, lib)
+
+
+def captureStartMemory(c_device: CDevicePtr) -> None:
+	return _captureStartMemory(c_device)
+
+
+_captureStartMemory = atffs(captureStartMemory, lib)
+
+
+def captureStop(c_device: CDevicePtr) -> ErrorCodeIntT:
+	return _captureStop(c_device)
+
+
+_captureStop = atffs(captureStop, lib)
+
+
+def captureGetFrameCount(c_device: CDevicePtr) -> c_uint64:
+	return _captureGetFrameCount(c_device)
+
+
+_captureGetFrameCount = atffs(captureGetFrameCount, lib)
+
+
+def captureGetBuffer(c_device: CDevicePtr, dst: c_uint8_p, size: size_t, capture_size: POINTER(size_t)) -> ErrorCodeIntT:
+	return _captureGetBuffer(c_device, dst, size, capture_size)
+
+
+_captureGetBuffer = atffs(captureGetBuffer, lib)
+
+
+def replayCaptureFile(c_device: CDevicePtr, path: c_char_p, only_addressed: c_bool, stats: CReplayStatsPtr) -> ErrorCodeIntT:
+	return _replayCaptureFile(c_device, path, only_addressed, stats)
+
+
+_replayCaptureFile = atffs(replayCaptureFile, lib)
+
+
+def replayCaptureBuffer(c_device: CDevicePtr, data: c_uint8_p, size: size_t, only_addressed: c_bool, stats: CReplayStatsPtr) -> ErrorCodeIntT:
+	return _replayCaptureBuffer(c_device, data, size, only_addressed, stats)
+
+
+_replayCaptureBuffer = atffs(replayCaptureBuffer, lib)
+
+
+def setImpairment(c_device: CDevicePtr, direction: ImpairmentDirectionIntT, config: CImpairmentConfigPtr) -> ErrorCodeIntT:
+	return _setImpairment(c_device, direction, config)
+
+
+_setImpairment = atffs(setImpairment, lib)
+
+
+def getImpairmentStats(c_device: CDevicePtr, direction: ImpairmentDirectionIntT, stats: CImpairmentStatsPtr) -> ErrorCodeIntT:
+	return _getImpairmentStats(c_device, direction, stats)
+
+
+_getImpairmentStats = atffs(getImpairmentStats, lib)
+
+
+def newLink(mode: LinkModeIntT) -> CLinkPtr:
+	return _newLink(mode)
+
+
+_newLink = atffs(newLink, lib)
+
+
+def deleteLink(c_link: CLinkPtr) -> None:
+	return _deleteLink(c_link)
+
+
+_deleteLink = atffs(deleteLink, lib)
+
+
+def linkAttach(c_link: CLinkPtr, c_device: CDevicePtr) -> ErrorCodeIntT:
+	return _linkAttach(c_link, c_device)
+
+
+_linkAttach = atffs(linkAttach, lib)
+
+
+def linkDetach(c_link: CLinkPtr, c_device: CDevicePtr) -> ErrorCodeIntT:
+	return _linkDetach(c_link, c_device)
+
+
+_linkDetach = atffs(linkDetach, lib)
+
+
+def linkTick(c_link: CLinkPtr, millis: c_int64) -> c_uint64:
+	return _linkTick(c_link, millis)
+
+
+_linkTick = atffs(linkTick, lib)
+
+
+def linkGetDeliveredCount(c_link: CLinkPtr) -> c_uint64:
+	return _linkGetDeliveredCount(c_link)
+
+
+_linkGetDeliveredCount = atffs(linkGetDeliveredCount, lib)
+
+
+def linkGetDropCount(c_link: CLinkPtr) -> c_uint64:
+	return _linkGetDropCount(c_link)
+
+
+_linkGetDropCount = atffs(linkGetDropCount, lib)
+
+
+def newSwitch(ageing_ms: c_uint32) -> CSwitchPtr:
+	return _newSwitch(ageing_ms)
+
+
+_newSwitch = atffs(newSwitch, lib)
+
+
+def deleteSwitch(c_switch: CSwitchPtr) -> None:
+	return _deleteSwitch(c_switch)
+
+
+_deleteSwitch = atffs(deleteSwitch, lib)
+
+
+def switchAttach(c_switch: CSwitchPtr, c_device: CDevicePtr) -> ErrorCodeIntT:
+	return _switchAttach(c_switch, c_device)
+
+
+_switchAttach = atffs(switchAttach, lib)
+
+
+def switchDetach(c_switch: CSwitchPtr, c_device: CDevicePtr) -> ErrorCodeIntT:
+	return _switchDetach(c_switch, c_device)
+
+
+_switchDetach = atffs(switchDetach, lib)
+
+
+def switchTick(c_switch: CSwitchPtr, millis: c_int64) -> c_uint64:
+	return _switchTick(c_switch, millis)
+
+
+_switchTick = atffs(switchTick, lib)
+
+
+def switchInjectFrame(c_switch: CSwitchPtr, src: c_uint8_p, size: c_uint32) -> ErrorCodeIntT:
+	return _switchInjectFrame(c_switch, src, size)
+
+
+_switchInjectFrame = atffs(switchInjectFrame, lib)
+
+
+def switchPopHostFrame(c_switch: CSwitchPtr, dst: c_uint8_p, size: c_uint32, frame_size: POINTER(size_t)) -> ErrorCodeIntT:
+	return _switchPopHostFrame(c_switch, dst, size, frame_size)
+
+
+_switchPopHostFrame = atffs(switchPopHostFrame, lib)
+
+
+def switchGetStats(c_switch: CSwitchPtr, stats: CSwitchStatsPtr) -> ErrorCodeIntT:
+	return _switchGetStats(c_switch, stats)
+
+
+_switchGetStats = atffs(switchGetStats, lib)
+
+
+def switchGetMacTableSize(c_switch: CSwitchPtr) -> size_t:
+	return _switchGetMacTableSize(c_switch)
+
+
+_switchGetMacTableSize = atffs(switchGetMacTableSize, lib)
+
+
+def newRouter() -> CRouterPtr:
+	return _newRouter()
+
+
+_newRouter = atffs(newRouter, lib)
+
+
+def deleteRouter(c_router: CRouterPtr) -> None:
+	return _deleteRouter(c_router)
+
+
+_deleteRouter = atffs(deleteRouter, lib)
+
+
+ROUTER_NO_PORT = uintptr_t(-1).value
+
+
+def routerAddPort(c_router: CRouterPtr, medium: MediumIntT, mac: CMacAddress, mtu: uintptr_t) -> uintptr_t:
+	return _routerAddPort(c_router, medium, mac, mtu)
+
+
+_routerAddPort = atffs(routerAddPort, lib)
+
+
+def routerPortAddAddress(c_router: CRouterPtr, port: uintptr_t, address: CInterface) -> ErrorCodeIntT:
+	return _routerPortAddAddress(c_router, port, address)
+
+
+_routerPortAddAddress = atffs(routerPortAddAddress, lib)
+
+
+def routerAddRoute(c_router: CRouterPtr, destination: CInterface, gateway: CAddress, port: uintptr_t) -> ErrorCodeIntT:
+	return _routerAddRoute(c_router, destination, gateway, port)
+
+
+_routerAddRoute = atffs(routerAddRoute, lib)
+
+
+def routerConnect(c_router: CRouterPtr, port: uintptr_t, c_device: CDevicePtr) -> ErrorCodeIntT:
+	return _routerConnect(c_router, port, c_device)
+
+
+_routerConnect = atffs(routerConnect, lib)
+
+
+def routerDisconnect(c_router: CRouterPtr, port: uintptr_t) -> ErrorCodeIntT:
+	return _routerDisconnect(c_router, port)
+
+
+_routerDisconnect = atffs(routerDisconnect, lib)
+
+
+def routerPutRxPacket(c_router: CRouterPtr, port: uintptr_t, src: c_uint8_p, size: c_uint32) -> ErrorCodeIntT:
+	return _routerPutRxPacket(c_router, port, src, size)
+
+
+_routerPutRxPacket = atffs(routerPutRxPacket, lib)
+
+
+def routerGetTxPacket(c_router: CRouterPtr, port: uintptr_t, dst: c_uint8_p, size: c_uint32, frame_size: POINTER(size_t)) -> ErrorCodeIntT:
+	return _routerGetTxPacket(c_router, port, dst, size, frame_size)
+
+
+_routerGetTxPacket = atffs(routerGetTxPacket, lib)
+
+
+def routerTick(c_router: CRouterPtr, millis: c_int64) -> c_uint64:
+	return _routerTick(c_router, millis)
+
+
+_routerTick = atffs(routerTick, lib)
+
+
+def routerGetStats(c_router: CRouterPtr, stats: CRouterStatsPtr) -> ErrorCodeIntT:
+	return _routerGetStats(c_router, stats)
+
+
+_routerGetStats = atffs(routerGetStats, lib)
+
+
+def routerSetNat(c_router: CRouterPtr, outside_port: uintptr_t, public_addr: CAddress, config: CNatConfigPtr) -> ErrorCodeIntT:
+	return _routerSetNat(c_router, outside_port, public_addr, config)
+
+
+_routerSetNat = atffs(routerSetNat, lib)
+
+
+def routerRemoveNat(c_router: CRouterPtr) -> None:
+	return _routerRemoveNat(c_router)
+
+
+_routerRemoveNat = atffs(routerRemoveNat, lib)
+
+
+def routerGetNatStats(c_router: CRouterPtr, stats: CNatStatsPtr) -> ErrorCodeIntT:
+	return _routerGetNatStats(c_router, stats)
+
+
+_routerGetNatStats = atffs(routerGetNatStats, lib)
+
+
 def newTcpSocket(c_device: CDevicePtr) -> TCPSocketPtr:
 	return _newTcpSocket(c_device)
 
@@ -121,8 +489,8 @@ def newTcpSocket(c_device: CDevicePtr) -> TCPSocketPtr:
 _newTcpSocket = atffs(newTcpSocket, lib)
 
 
-def deleteTcpSocket(sock: TCPSocketPtr) -> None:
-	return _deleteTcpSocket(sock)
+def deleteTcpSocket(c_device: CDevicePtr, sock: TCPSocketPtr, graceful: c_bool) -> None:
+	return _deleteTcpSocket(c_device, sock, graceful)
 
 
 _deleteTcpSocket = atffs(deleteTcpSocket, lib)
@@ -135,21 +503,21 @@ def tcpConnect(c_device: CDevicePtr, sock: TCPSocketPtr, endpoint: IPEndpoint, l
 _tcpConnect = atffs(tcpConnect, lib)
 
 
-def tcpSend(c_device: CDevicePtr, sock: TCPSocketPtr, data: c_uint8_p, size: c_uint32) -> None:
-	return _tcpSend(c_device, sock, data, size)
+def tcpSend(c_device: CDevicePtr, sock: TCPSocketPtr, data: c_uint8_p, size: c_uint32, sent: POINTER(c_uint32)) -> ErrorCodeIntT:
+	return _tcpSend(c_device, sock, data, size, sent)
 
 
 _tcpSend = atffs(tcpSend, lib)
 
 
-def tcpReceive(c_device: CDevicePtr, sock: TCPSocketPtr, data: c_uint8_p, size: c_uint32) -> None:
-	return _tcpReceive(c_device, sock, data, size)
+def tcpReceive(c_device: CDevicePtr, sock: TCPSocketPtr, data: c_uint8_p, size: c_uint32, received: POINTER(c_uint32)) -> ErrorCodeIntT:
+	return _tcpReceive(c_device, sock, data, size, received)
 
 
 _tcpReceive = atffs(tcpReceive, lib)
 
 
-def tcpListen(c_device: CDevicePtr, sock: TCPSocketPtr, port: c_uint16) -> None:
+def tcpListen(c_device: CDevicePtr, sock: TCPSocketPtr, port: c_uint16) -> ErrorCodeIntT:
 	return _tcpListen(c_device, sock, port)
 
 
@@ -163,6 +531,48 @@ def tcpIsActive(c_device: CDevicePtr, sock: TCPSocketPtr) -> bool:
 _isTcpActive = atffs(tcpIsActive, lib)
 
 
+def tcpSocketDelete(sock: TCPSocketPtr, graceful: c_bool) -> None:
+	return _tcpSocketDelete(sock, graceful)
+
+
+_tcpSocketDelete = atffs(tcpSocketDelete, lib)
+
+
+def tcpSocketConnect(sock: TCPSocketPtr, endpoint: IPEndpoint, local_port: c_uint16) -> ErrorCodeIntT:
+	return _tcpSocketConnect(sock, endpoint, local_port)
+
+
+_tcpSocketConnect = atffs(tcpSocketConnect, lib)
+
+
+def tcpSocketSend(sock: TCPSocketPtr, data: c_uint8_p, size: c_uint32, sent: POINTER(c_uint32)) -> ErrorCodeIntT:
+	return _tcpSocketSend(sock, data, size, sent)
+
+
+_tcpSocketSend = atffs(tcpSocketSend, lib)
+
+
+def tcpSocketReceive(sock: TCPSocketPtr, data: c_uint8_p, size: c_uint32, received: POINTER(c_uint32)) -> ErrorCodeIntT:
+	return _tcpSocketReceive(sock, data, size, received)
+
+
+_tcpSocketReceive = atffs(tcpSocketReceive, lib)
+
+
+def tcpSocketListen(sock: TCPSocketPtr, port: c_uint16) -> ErrorCodeIntT:
+	return _tcpSocketListen(sock, port)
+
+
+_tcpSocketListen = atffs(tcpSocketListen, lib)
+
+
+def tcpSocketIsActive(sock: TCPSocketPtr) -> bool:
+	return _tcpSocketIsActive(sock)
+
+
+_tcpSocketIsActive = atffs(tcpSocketIsActive, lib)
+
+
 def ifacePoll(c_device: CDevicePtr) -> None:
 	return _ifacePoll(c_device)
 
@@ -170,6 +580,29 @@ def ifacePoll(c_device: CDevicePtr) -> None:
 _ifacePoll = atffs(ifacePoll, lib)
 
 
+def ifacePollAt(c_device: CDevicePtr, millis: c_int64) -> None:
+	return _ifacePollAt(c_device, millis)
+
+
+_ifacePollAt = atffs(ifacePollAt, lib)
+
+POLL_NO_DEADLINE = -1
+
+
+def ifaceGetPollAt(c_device: CDevicePtr) -> c_int64:
+	return _ifaceGetPollAt(c_device)
+
+
+_ifaceGetPollAt = atffs(ifaceGetPollAt, lib)
+
+
+def ifaceGetPollDelay(c_device: CDevicePtr) -> c_int64:
+	return _ifaceGetPollDelay(c_device)
+
+
+_ifaceGetPollDelay = atffs(ifaceGetPollDelay, lib)
+
+
 def newDnsSocket(c_device: CDevicePtr, address: CAddress) -> DNSSocketPtr:
 	return _newDnsSocket(c_device, address)
 
@@ -177,8 +610,8 @@ def newDnsSocket(c_device: CDevicePtr, address: CAddress) -> DNSSocketPtr:
 _newDnsSocket = atffs(newDnsSocket, lib)
 
 
-def deleteDnsSocket(sock: ICMPSocketPtr) -> None:
-	return _deleteDnsSocket(sock)
+def deleteDnsSocket(c_device: CDevicePtr, sock: DNSSocketPtr) -> None:
+	return _deleteDnsSocket(c_device, sock)
 
 
 _deleteDnsSocket = atffs(deleteDnsSocket, lib)
@@ -191,6 +624,20 @@ def newDnsQuery(c_device: CDevicePtr, sock: DNSSocketPtr, name: c_uint8_p, name_
 _newDnsQuery = atffs(newDnsSocket, lib)
 
 
+def dnsSocketDelete(sock: DNSSocketPtr) -> None:
+	return _dnsSocketDelete(sock)
+
+
+_dnsSocketDelete = atffs(dnsSocketDelete, lib)
+
+
+def dnsSocketQuery(sock: DNSSocketPtr, name: c_uint8_p, name_size: c_uint32) -> DNSQueryHandlePtr:
+	return _dnsSocketQuery(sock, name, name_size)
+
+
+_dnsSocketQuery = atffs(dnsSocketQuery, lib)
+
+
 def newIcmpSocket(c_device: CDevicePtr) -> ICMPSocketPtr:
 	return _newIcmpSocket(c_device)
 
@@ -198,12 +645,19 @@ def newIcmpSocket(c_device: CDevicePtr) -> ICMPSocketPtr:
 _newIcmpSocket = atffs(newIcmpSocket, lib)
 
 
-def deleteIcmpSocket(sock: ICMPSocketPtr) -> None:
-	return _deleteIcmpSocket(sock)
+def deleteIcmpSocket(c_device: CDevicePtr, sock: ICMPSocketPtr) -> None:
+	return _deleteIcmpSocket(c_device, sock)
 
 
 _deleteIcmpSocket = atffs(deleteIcmpSocket, lib)
 
+
+def icmpSocketDelete(sock: ICMPSocketPtr) -> None:
+	return _icmpSocketDelete(sock)
+
+
+_icmpSocketDelete = atffs(icmpSocketDelete, lib)
+
 # ICMPEchoPacketType
 def buildIcmpV4EchoPacket(tp: c_int, ident: c_uint32, seq_no: c_uint32, payload: c_uint8_p, payload_size: c_uint32, dst: c_uint8_p, dst_size: c_uint32) -> c_uint32:
 	return _buildIcmpV4EchoPacket(tp, ident, seq_no, payload, payload_size, dst, dst_size)
@@ -219,8 +673,8 @@ def newUdpSocket(c_device: CDevicePtr) -> UDPSocketPtr:
 _newUdpSocket = atffs(newUdpSocket, lib)
 
 
-def deleteUdpSocket(sock: UDPSocketPtr) -> None:
-	return _deleteUdpSocket(sock)
+def deleteUdpSocket(c_device: CDevicePtr, sock: UDPSocketPtr) -> None:
+	return _deleteUdpSocket(c_device, sock)
 
 
 _deleteUdpSocket = atffs(deleteUdpSocket, lib)
@@ -233,8 +687,8 @@ def udpBind(c_device: CDevicePtr, sock: UDPSocketPtr, port: c_uint16) -> ErrorCo
 _udpBind = atffs(udpBind, lib)
 
 
-def udpReceive(c_device: CDevicePtr, sock: UDPSocketPtr, endpoint: IPEndpointPtr, dst: c_uint8_p, size: c_uint32) -> ErrorCodeIntT:
-	return _udpReceive(c_device, sock, endpoint, dst, size)
+def udpReceive(c_device: CDevicePtr, sock: UDPSocketPtr, endpoint: IPEndpointPtr, dst: c_uint8_p, size: c_uint32, datagram_size: POINTER(c_uint32)) -> ErrorCodeIntT:
+	return _udpReceive(c_device, sock, endpoint, dst, size, datagram_size)
 
 
 _udpReceive = atffs(udpReceive, lib)
@@ -254,10 +708,120 @@ def udpSend(c_device: CDevicePtr, sock: UDPSocketPtr, endpoint: IPEndpoint, data
 _udpSend = atffs(udpSend, lib)
 
 
+def udpSocketDelete(sock: UDPSocketPtr) -> None:
+	return _udpSocketDelete(sock)
+
+
+_udpSocketDelete = atffs(udpSocketDelete, lib)
+
+
+def udpSocketBind(sock: UDPSocketPtr, port: c_uint16) -> ErrorCodeIntT:
+	return _udpSocketBind(sock, port)
+
+
+_udpSocketBind = atffs(udpSocketBind, lib)
+
+
+def udpSocketReceive(sock: UDPSocketPtr, endpoint: IPEndpointPtr, dst: c_uint8_p, size: c_uint32, datagram_size: POINTER(c_uint32)) -> ErrorCodeIntT:
+	return _udpSocketReceive(sock, endpoint, dst, size, datagram_size)
+
+
+_udpSocketReceive = atffs(udpSocketReceive, lib)
+
+
+def udpSocketGetLastReceivedPacketSize(sock: UDPSocketPtr) -> c_uint32:
+	return _udpSocketGetLastReceivedPacketSize(sock)
+
+
+_udpSocketGetLastReceivedPacketSize = atffs(udpSocketGetLastReceivedPacketSize, lib)
+
+
+def udpSocketSend(sock: UDPSocketPtr, endpoint: IPEndpoint, data: c_uint8_p, size: c_uint32) -> ErrorCodeIntT:
+	return _udpSocketSend(sock, endpoint, data, size)
+
+
+_udpSocketSend = atffs(udpSocketSend, lib)
+
+
+def getLastError() -> ErrorCodeIntT:
+	return _getLastError()
+
+
+_getLastError = atffs(getLastError, lib)
+
+
+def clearLastError() -> None:
+	return _clearLastError()
+
+
+_clearLastError = atffs(clearLastError, lib)
+
+
+def getLastErrorMessage() -> c_char_p:
+	return _getLastErrorMessage()
+
+
+_getLastErrorMessage = atffs(getLastErrorMessage, lib)
+
+
+def resultCodeToString(code: ErrorCodeIntT) -> c_char_p:
+	return _resultCodeToString(code)
+
+
+_resultCodeToString = atffs(resultCodeToString, lib)
+
+
+def getAbiVersion() -> c_uint32:
+	return _getAbiVersion()
+
+
+_getAbiVersion = atffs(getAbiVersion, lib)
+
+
+def getSupportedProtocols() -> c_uint32:
+	return _getSupportedProtocols()
+
+
+_getSupportedProtocols = atffs(getSupportedProtocols, lib)
+
+
+def getSupportedSocketKinds() -> c_uint32:
+	return _getSupportedSocketKinds()
+
+
+_getSupportedSocketKinds = atffs(getSupportedSocketKinds, lib)
+
+
+def getSupportedMediums() -> c_uint32:
+	return _getSupportedMediums()
+
+
+_getSupportedMediums = atffs(getSupportedMediums, lib)
+
+ABI_VERSION = 1
+
+if getAbiVersion() != ABI_VERSION:
+	raise ImportError("The loaded sio_smoltcp library has ABI version " + str(getAbiVersion()) + ", but these bindings are for version " + str(ABI_VERSION))
+
+
 def initLogging() -> None:
 	return _initLogging()
 
 
 _initLogging = atffs(initLogging, lib)
 
+
+def setLogCallback(callback: LogCallback, user_data: c_void_p) -> ErrorCodeIntT:
+	return _setLogCallback(callback, user_data)
+
+
+_setLogCallback = atffs(setLogCallback, lib)
+
+
+def setLogLevel(level: LogLevelIntT) -> ErrorCodeIntT:
+	return _setLogLevel(level)
+
+
+_setLogLevel = atffs(setLogLevel, lib)
+
 initLogging()
@@ -7,3 +7,6 @@ DNSQueryHandlePtr = c_void_p
 ICMPSocketPtr = c_void_p
 UDPSocketPtr = c_void_p
 CBuilderPtr = c_void_p
+CLinkPtr = c_void_p
+CSwitchPtr = c_void_p
+CRouterPtr = c_void_p
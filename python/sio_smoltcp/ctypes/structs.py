@@ -1,4 +1,4 @@
-from ctypes import POINTER, Structure, c_ubyte, c_uint8, c_uint16, c_uint64
+from ctypes import POINTER, Structure, c_double, c_size_t, c_ubyte, c_uint8, c_uint16, c_uint32, c_uint64
 
 # pylint:disable=too-few-public-methods
 
@@ -30,3 +30,121 @@ class CInterface(Structure):
 class CMacAddress(Structure):
 	__slots__ = ("mac",)
 	_fields_ = (("mac", c_ubyte * 6),)
+
+
+class CPacketIoVec(Structure):
+	__slots__ = ("data", "size", "status")
+	_fields_ = (
+		("data", POINTER(c_uint8)),
+		("size", c_uint32),
+		("status", c_uint8),
+	)
+
+
+CPacketIoVecPtr = POINTER(CPacketIoVec)
+
+
+class CReplayStats(Structure):
+	__slots__ = ("frames", "injected", "skipped", "refused")
+	_fields_ = (
+		("frames", c_size_t),
+		("injected", c_size_t),
+		("skipped", c_size_t),
+		("refused", c_size_t),
+	)
+
+
+CReplayStatsPtr = POINTER(CReplayStats)
+
+
+class CImpairmentConfig(Structure):
+	__slots__ = ("seed", "drop_probability", "delay_ms", "jitter_ms", "duplicate_probability", "reorder_probability", "reorder_window_ms", "bit_flip_probability", "bandwidth_bps")
+	_fields_ = (
+		("seed", c_uint64),
+		("drop_probability", c_double),
+		("delay_ms", c_uint32),
+		("jitter_ms", c_uint32),
+		("duplicate_probability", c_double),
+		("reorder_probability", c_double),
+		("reorder_window_ms", c_uint32),
+		("bit_flip_probability", c_double),
+		("bandwidth_bps", c_uint64),
+	)
+
+
+CImpairmentConfigPtr = POINTER(CImpairmentConfig)
+
+
+class CImpairmentStats(Structure):
+	__slots__ = ("frames", "dropped", "duplicated", "reordered", "corrupted")
+	_fields_ = (
+		("frames", c_uint64),
+		("dropped", c_uint64),
+		("duplicated", c_uint64),
+		("reordered", c_uint64),
+		("corrupted", c_uint64),
+	)
+
+
+CImpairmentStatsPtr = POINTER(CImpairmentStats)
+
+
+class CSwitchStats(Structure):
+	__slots__ = ("frames", "forwarded", "flooded", "filtered", "malformed", "dropped")
+	_fields_ = (
+		("frames", c_uint64),
+		("forwarded", c_uint64),
+		("flooded", c_uint64),
+		("filtered", c_uint64),
+		("malformed", c_uint64),
+		("dropped", c_uint64),
+	)
+
+
+CSwitchStatsPtr = POINTER(CSwitchStats)
+
+
+class CRouterStats(Structure):
+	__slots__ = ("packets", "forwarded", "local", "time_exceeded", "unreachable", "dropped")
+	_fields_ = (
+		("packets", c_uint64),
+		("forwarded", c_uint64),
+		("local", c_uint64),
+		("time_exceeded", c_uint64),
+		("unreachable", c_uint64),
+		("dropped", c_uint64),
+	)
+
+
+CRouterStatsPtr = POINTER(CRouterStats)
+
+
+class CNatConfig(Structure):
+	__slots__ = ("tcp_established_ms", "tcp_transitory_ms", "udp_ms", "icmp_ms", "port_min", "port_max")
+	_fields_ = (
+		("tcp_established_ms", c_uint32),
+		("tcp_transitory_ms", c_uint32),
+		("udp_ms", c_uint32),
+		("icmp_ms", c_uint32),
+		("port_min", c_uint16),
+		("port_max", c_uint16),
+	)
+
+
+CNatConfigPtr = POINTER(CNatConfig)
+
+
+class CNatStats(Structure):
+	__slots__ = ("connections", "created", "expired", "outbound", "inbound", "dropped", "unsolicited")
+	_fields_ = (
+		("connections", c_uint64),
+		("created", c_uint64),
+		("expired", c_uint64),
+		("outbound", c_uint64),
+		("inbound", c_uint64),
+		("dropped", c_uint64),
+		("unsolicited", c_uint64),
+	)
+
+
+CNatStatsPtr = POINTER(CNatStats)
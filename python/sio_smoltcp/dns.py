@@ -1,6 +1,6 @@
 from ctypes import c_uint32
 
-from .ctypes.functions import c_uint8_p
+from .ctypes.functions import c_uint8_p, dnsSocketDelete
 from .ctypes.functions import newDnsQuery as newDnsQuery_ct
 from .ctypes.functions import newDnsSocket as newDnsSocket_ct
 from .ctypes.opaque import CDevicePtr, DNSQueryHandlePtr, DNSSocketPtr
@@ -24,7 +24,7 @@ class DNSSocket:
 
 	def free(self):
 		if self.ptr:
-			deleteDnsSocket(self.ptr)
+			dnsSocketDelete(self.ptr)
 			self.ptr = None
 
 	def query(self, name: str) -> "DNSQuery":
@@ -0,0 +1,13 @@
+import typing
+
+from .ctypes.enums import ErrorCode
+from .ctypes.functions import getLastErrorMessage, resultCodeToString
+
+
+class SmolTCPError(RuntimeError):
+	def __init__(self, code: ErrorCode, *args) -> None:
+		self.code = code
+		self.description = resultCodeToString(int(code)).decode("utf-8")
+		details = getLastErrorMessage()
+		self.details: typing.Optional[str] = details.decode("utf-8", "replace") if details else None
+		super().__init__(code, self.description, self.details, *args)
@@ -0,0 +1,20 @@
+import typing
+
+from .ctypes.enums import Medium, Protocol, SocketKind
+from .ctypes.functions import getSupportedMediums, getSupportedProtocols, getSupportedSocketKinds
+
+
+def _fromMask(enumCls, mask: int) -> typing.FrozenSet:
+	return frozenset(el for el in enumCls if mask & (1 << int(el)))
+
+
+def supportedProtocols() -> Protocol:
+	return Protocol(int(getSupportedProtocols()))
+
+
+def supportedSocketKinds() -> typing.FrozenSet[SocketKind]:
+	return _fromMask(SocketKind, int(getSupportedSocketKinds()))
+
+
+def supportedMediums() -> typing.FrozenSet[Medium]:
+	return _fromMask(Medium, int(getSupportedMediums()))
@@ -1,7 +1,7 @@
 from ctypes import c_int, c_uint32
 
 from .ctypes.functions import buildIcmpV4EchoPacket as ct_buildIcmpV4EchoPacket
-from .ctypes.functions import c_uint8_p, deleteIcmpSocket, newIcmpSocket
+from .ctypes.functions import c_uint8_p, icmpSocketDelete, newIcmpSocket
 from .ctypes.opaque import CDevicePtr, ICMPSocketPtr
 from .utils.resource import ResourceWithParent
 
@@ -20,7 +20,7 @@ def buildIcmpV4EchoPacket(tp: c_int, ident: c_uint32, seq_no: c_uint32, payload:
 class ICMPSocket(ResourceWithParent):
 	__slots__ = ("parent", "ptr")
 
-	DTOR = deleteIcmpSocket
+	DTOR = icmpSocketDelete
 
 	def __init__(self, parent: "Device"):
 		self.parent = parent
@@ -0,0 +1,49 @@
+import typing
+
+from .ctypes.enums import ErrorCode, LinkMode, LinkModeIntT
+from .ctypes.functions import c_int64, deleteLink, linkAttach, linkDetach, linkGetDeliveredCount, linkGetDropCount, linkTick, newLink
+from .Device import Device
+from .errors import SmolTCPError
+from .utils.resource import Resource
+
+
+class Link(Resource):
+	__slots__ = ("devices",)
+	DTOR = deleteLink
+
+	def __init__(self, mode: LinkMode = LinkMode.PointToPoint, devices: typing.Iterable[Device] = ()) -> None:
+		ptr = newLink(LinkModeIntT(mode))
+		if not ptr:
+			raise SmolTCPError(ErrorCode.Illegal)
+		super().__init__(ptr)
+		# keeps the devices alive while they are attached
+		self.devices = []
+		for device in devices:
+			self.attach(device)
+
+	def attach(self, device: Device) -> None:
+		res = ErrorCode(linkAttach(self.ptr, device.ptr))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		self.devices.append(device)
+
+	def detach(self, device: Device) -> None:
+		res = ErrorCode(linkDetach(self.ptr, device.ptr))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		self.devices.remove(device)
+
+	def free(self):
+		super().free()
+		self.devices = []
+
+	def tick(self, millis: int) -> int:
+		return int(linkTick(self.ptr, c_int64(millis)))
+
+	@property
+	def delivered(self) -> int:
+		return int(linkGetDeliveredCount(self.ptr))
+
+	@property
+	def dropped(self) -> int:
+		return int(linkGetDropCount(self.ptr))
@@ -0,0 +1,45 @@
+import logging
+import typing
+
+from .ctypes.callbacks import LogCallback
+from .ctypes.enums import ErrorCode, LogLevel
+from .ctypes.functions import setLogCallback, setLogLevel
+from .errors import SmolTCPError
+
+_LEVELS = {
+	LogLevel.Error: logging.ERROR,
+	LogLevel.Warn: logging.WARNING,
+	LogLevel.Info: logging.INFO,
+	LogLevel.Debug: logging.DEBUG,
+	LogLevel.Trace: logging.DEBUG - 5,
+}
+
+# The callback must stay alive as long as the library can call it
+_callback = None
+
+
+# Routes the logs of the library into `logger`, or into the loggers named after the targets of the records (`smoltcp.iface` and so on) if it is None
+def routeToLogging(logger: typing.Optional[logging.Logger] = None) -> None:
+	global _callback  # pylint:disable=global-statement
+
+	def onRecord(_userData, level: int, target: bytes, message: bytes) -> None:
+		dst = logger if logger is not None else logging.getLogger(target.decode("utf-8", "replace").replace("::", "."))
+		dst.log(_LEVELS.get(LogLevel(level), logging.DEBUG), "%s", message.decode("utf-8", "replace"))
+
+	callback = LogCallback(onRecord)
+	res = ErrorCode(setLogCallback(callback, None))
+	_callback = callback
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res)
+
+
+def routeToStderr() -> None:
+	global _callback  # pylint:disable=global-statement
+	setLogCallback(LogCallback(), None)
+	_callback = None
+
+
+def setLevel(level: LogLevel) -> None:
+	res = ErrorCode(setLogLevel(int(level)))
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res)
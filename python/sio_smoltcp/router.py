@@ -0,0 +1,108 @@
+import typing
+from ctypes import byref
+from ipaddress import _BaseAddress, ip_address
+
+from netaddr import EUI
+
+from .address import CAddress, CInterface, CMacAddress
+from .ctypes.enums import ErrorCode, Medium, MediumIntT
+from .ctypes.functions import ROUTER_NO_PORT, c_int64, c_uint32, deleteRouter, newRouter, routerAddPort, routerAddRoute, routerConnect, routerDisconnect, routerGetNatStats, routerGetStats, routerGetTxPacket, routerPortAddAddress, routerPutRxPacket, routerRemoveNat, routerSetNat, routerTick, size_t, uintptr_t
+from .ctypes.structs import CNatConfig, CNatStats, CRouterStats
+from .ctypes.utils import byteStringToPointer
+from .Device import Device
+from .errors import SmolTCPError
+from .utils.address import InterfaceT
+from .utils.resource import Resource
+
+
+class Router(Resource):
+	__slots__ = ("peers",)
+	DTOR = deleteRouter
+
+	def __init__(self) -> None:
+		super().__init__(newRouter())
+		# keeps the devices alive while they are connected, by port
+		self.peers = {}
+
+	def addPort(self, medium: Medium = Medium.Ethernet, mac: typing.Optional[EUI] = None, mtu: int = 1500) -> int:
+		if mac is None:
+			mac = EUI(0)
+		port = int(routerAddPort(self.ptr, MediumIntT(medium), CMacAddress.fromPythonic(mac), uintptr_t(mtu)))
+		if port == ROUTER_NO_PORT:
+			raise SmolTCPError(ErrorCode.Illegal)
+		return port
+
+	def addAddress(self, port: int, address: InterfaceT) -> None:
+		res = ErrorCode(routerPortAddAddress(self.ptr, uintptr_t(port), CInterface.fromPythonic(address)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def addRoute(self, destination: InterfaceT, port: int, gateway: typing.Optional[_BaseAddress] = None) -> None:
+		if gateway is None:
+			gateway = ip_address("::")
+		res = ErrorCode(routerAddRoute(self.ptr, CInterface.fromPythonic(destination), CAddress.fromPythonic(gateway), uintptr_t(port)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def connect(self, port: int, device: Device) -> None:
+		res = ErrorCode(routerConnect(self.ptr, uintptr_t(port), device.ptr))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		self.peers[port] = device
+
+	def disconnect(self, port: int) -> None:
+		res = ErrorCode(routerDisconnect(self.ptr, uintptr_t(port)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		del self.peers[port]
+
+	def setNat(self, outsidePort: int, publicAddress: _BaseAddress, config: typing.Optional[CNatConfig] = None) -> None:
+		res = ErrorCode(routerSetNat(self.ptr, uintptr_t(outsidePort), CAddress.fromPythonic(publicAddress), byref(config) if config is not None else None))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def removeNat(self) -> None:
+		routerRemoveNat(self.ptr)
+
+	def free(self):
+		super().free()
+		self.peers = {}
+
+	def tick(self, millis: int) -> int:
+		return int(routerTick(self.ptr, c_int64(millis)))
+
+	def put(self, port: int, frame: bytes) -> None:
+		buf, size = byteStringToPointer(frame)
+		res = ErrorCode(routerPutRxPacket(self.ptr, uintptr_t(port), buf, c_uint32(size)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def pop(self, port: int) -> typing.Optional[bytearray]:
+		frameSize = size_t(0)
+		res = ErrorCode(routerGetTxPacket(self.ptr, uintptr_t(port), None, c_uint32(0), byref(frameSize)))
+		if res == ErrorCode.Exhausted:
+			return None
+		if res not in (ErrorCode.OK, ErrorCode.BufferInsufficient):
+			raise SmolTCPError(res)
+		frame = bytearray(frameSize.value)
+		buf, size = byteStringToPointer(frame)
+		res = ErrorCode(routerGetTxPacket(self.ptr, uintptr_t(port), buf, c_uint32(size), byref(frameSize)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return frame
+
+	@property
+	def stats(self) -> CRouterStats:
+		stats = CRouterStats()
+		res = ErrorCode(routerGetStats(self.ptr, byref(stats)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return stats
+
+	@property
+	def natStats(self) -> CNatStats:
+		stats = CNatStats()
+		res = ErrorCode(routerGetNatStats(self.ptr, byref(stats)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return stats
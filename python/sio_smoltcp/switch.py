@@ -0,0 +1,71 @@
+import typing
+from ctypes import byref
+
+from .ctypes.enums import ErrorCode
+from .ctypes.functions import c_int64, c_uint32, deleteSwitch, newSwitch, size_t, switchAttach, switchDetach, switchGetMacTableSize, switchGetStats, switchInjectFrame, switchPopHostFrame, switchTick
+from .ctypes.structs import CSwitchStats
+from .ctypes.utils import byteStringToPointer
+from .Device import Device
+from .errors import SmolTCPError
+from .utils.resource import Resource
+
+
+class Switch(Resource):
+	__slots__ = ("devices",)
+	DTOR = deleteSwitch
+
+	def __init__(self, devices: typing.Iterable[Device] = (), ageingMillis: int = 0) -> None:
+		super().__init__(newSwitch(c_uint32(ageingMillis)))
+		# keeps the devices alive while they are attached
+		self.devices = []
+		for device in devices:
+			self.attach(device)
+
+	def attach(self, device: Device) -> None:
+		res = ErrorCode(switchAttach(self.ptr, device.ptr))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		self.devices.append(device)
+
+	def detach(self, device: Device) -> None:
+		res = ErrorCode(switchDetach(self.ptr, device.ptr))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		self.devices.remove(device)
+
+	def free(self):
+		super().free()
+		self.devices = []
+
+	def tick(self, millis: int) -> int:
+		return int(switchTick(self.ptr, c_int64(millis)))
+
+	def inject(self, frame: bytes) -> None:
+		buf, size = byteStringToPointer(frame)
+		res = ErrorCode(switchInjectFrame(self.ptr, buf, c_uint32(size)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+
+	def pop(self) -> typing.Optional[bytearray]:
+		frameSize = size_t(0)
+		res = ErrorCode(switchPopHostFrame(self.ptr, None, c_uint32(0), byref(frameSize)))
+		if res == ErrorCode.Exhausted:
+			return None
+		frame = bytearray(frameSize.value)
+		buf, size = byteStringToPointer(frame)
+		res = ErrorCode(switchPopHostFrame(self.ptr, buf, c_uint32(size), byref(frameSize)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return frame
+
+	@property
+	def stats(self) -> CSwitchStats:
+		stats = CSwitchStats()
+		res = ErrorCode(switchGetStats(self.ptr, byref(stats)))
+		if res != ErrorCode.OK:
+			raise SmolTCPError(res)
+		return stats
+
+	@property
+	def macTableSize(self) -> int:
+		return int(switchGetMacTableSize(self.ptr))
@@ -1,38 +1,49 @@
 import typing
-from ctypes import c_uint16, c_uint32
+from ctypes import byref, c_uint16, c_uint32
 
 from .address import IPEndpoint, PythonicEndpointT
-from .ctypes.enums import TCPConnectError
-from .ctypes.functions import newTcpSocket
-from .ctypes.functions import tcpConnect as ct_tcpConnect
-from .ctypes.functions import tcpIsActive as ct_tcpIsActive
-from .ctypes.functions import tcpListen as ct_tcpListen
-from .ctypes.functions import tcpReceive as ct_tcpReceive
-from .ctypes.functions import tcpSend as ct_tcpSend
-from .ctypes.opaque import CDevicePtr, TCPSocketPtr
+from .ctypes.enums import ErrorCode, TCPConnectError
+from .ctypes.functions import newTcpSocket, tcpSocketDelete
+from .ctypes.functions import tcpSocketConnect as ct_tcpConnect
+from .ctypes.functions import tcpSocketIsActive as ct_tcpIsActive
+from .ctypes.functions import tcpSocketListen as ct_tcpListen
+from .ctypes.functions import tcpSocketReceive as ct_tcpReceive
+from .ctypes.functions import tcpSocketSend as ct_tcpSend
+from .ctypes.opaque import TCPSocketPtr
 from .ctypes.utils import byteStringToPointer
+from .errors import SmolTCPError
 
 # from .Device import Device
 
 
-def tcpConnect(c_device: CDevicePtr, sock: TCPSocketPtr, endpoint: PythonicEndpointT, local_port: int) -> None:
-	res = TCPConnectError(int(ct_tcpConnect(c_device, sock, IPEndpoint.fromPythonic(endpoint), c_uint16(local_port))))
+def tcpConnect(sock: TCPSocketPtr, endpoint: PythonicEndpointT, local_port: int) -> None:
+	res = TCPConnectError(int(ct_tcpConnect(sock, IPEndpoint.fromPythonic(endpoint), c_uint16(local_port))))
 	if res != TCPConnectError.OK:
-		raise RuntimeError("TCP connection failed", res)
+		raise SmolTCPError(res, "TCP connection failed")
 
 
-def tcpSend(c_device: CDevicePtr, sock: TCPSocketPtr, data: bytes) -> None:
+def tcpSend(sock: TCPSocketPtr, data: bytes) -> int:
 	buf, size = byteStringToPointer(data)
-	return ct_tcpSend(c_device, sock, buf, c_uint32(size))
+	sent = c_uint32(0)
+	res = ErrorCode(ct_tcpSend(sock, buf, c_uint32(size), byref(sent)))
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res)
+	return sent.value
 
 
-def tcpReceive(c_device: CDevicePtr, sock: TCPSocketPtr, data: bytes) -> None:
+def tcpReceive(sock: TCPSocketPtr, data: bytearray) -> int:
 	buf, size = byteStringToPointer(data)
-	return ct_tcpReceive(c_device, sock, buf, c_uint32(size))
+	received = c_uint32(0)
+	res = ErrorCode(ct_tcpReceive(sock, buf, c_uint32(size), byref(received)))
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res)
+	return received.value
 
 
-def tcpListen(c_device: CDevicePtr, sock: TCPSocketPtr, port: int) -> None:
-	return ct_tcpListen(c_device, sock, c_uint16(port))
+def tcpListen(sock: TCPSocketPtr, port: int) -> None:
+	res = ErrorCode(ct_tcpListen(sock, c_uint16(port)))
+	if res != ErrorCode.OK:
+		raise SmolTCPError(res)
 
 
 class TCPSocket:
@@ -44,20 +55,25 @@ class TCPSocket:
 
 	def free(self):
 		if self.ptr:
-			deleteTcpSocket(self.ptr)
+			tcpSocketDelete(self.ptr, False)
+			self.ptr = None
+
+	def close(self):
+		if self.ptr:
+			tcpSocketDelete(self.ptr, True)
 			self.ptr = None
 
 	def isActive(self) -> bool:
-		return ct_tcpIsActive(self.parent.ptr, self.ptr)
+		return ct_tcpIsActive(self.ptr)
 
 	def connect(self, endpoint: PythonicEndpointT, local_port: int):
-		tcpConnect(self.parent.ptr, self.ptr, endpoint, local_port)
+		tcpConnect(self.ptr, endpoint, local_port)
 
 	def listen(self, port: int):
-		tcpConnect(self.parent.ptr, self.ptr, port)
+		tcpConnect(self.ptr, port)
 
-	def send(self, data: bytes):
-		tcpSend(self.parent.ptr, self.ptr, data)
+	def send(self, data: bytes) -> int:
+		return tcpSend(self.ptr, data)
 
-	def receive(self, data: bytes):
-		tcpReceive(self.parent.ptr, self.ptr, data)
+	def receive(self, data: bytearray) -> int:
+		return tcpReceive(self.ptr, data)
@@ -25,3 +25,8 @@ class ResourceWithParent(Resource):
 	def __init__(self, parent: Resource) -> None:
 		self.parent = parent
 		self.ptr = self.__class__.CTOR(parent.ptr)
+
+	def free(self):
+		if self.ptr:
+			self.__class__.DTOR(self.ptr)
+			self.ptr = None
@@ -24,6 +24,11 @@ from sio_smoltcp.tcp import TCPSocket
 
 from sio_smoltcp.utils.address import ipv4IntoIpv6, addressFromRawBytes
 from sio_smoltcp.builder import makeDevice
+from sio_smoltcp.ctypes.enums import LinkMode
+from sio_smoltcp.errors import SmolTCPError
+from sio_smoltcp.link import Link
+from sio_smoltcp.router import Router
+from sio_smoltcp.switch import Switch
 
 
 environ["RUST_BACKTRACE"] = "1"
@@ -158,5 +163,80 @@ class TestConnections(unittest.TestCase):
 
 
 
+class TestNetworks(unittest.TestCase):
+	# sends a datagram from the first device to the second one, moving the frames with `tick`, and returns what the second one has received and from where
+	def exchange(self, tick: typing.Callable[[int], int], src: Device, dst: Device, dstAddr: IPv4Address) -> typing.Tuple[bytes, typing.Tuple[IPv4Address, int]]:
+		s1 = src.udpSocket()
+		s2 = dst.udpSocket()
+		s1.bind(5678)
+		s2.bind(1234)
+
+		sent = b"hello" * 20
+		s1.send((dstAddr, 1234), sent)
+		for millis in range(0, 3000, 100):
+			tick(millis)
+
+		received, senderEndpoint = s2.receive()
+		self.assertEqual(bytes(received), sent)
+		return senderEndpoint
+
+	def testLink(self):
+		ifc1 = IPv4Interface("192.168.1.10/24")
+		ifc2 = IPv4Interface("192.168.1.11/24")
+		d1 = makeDevice(mtu=1500, my_ip=ifc1, gateway=IPv4Address("192.168.1.1"), mac=EUI("02:00:00:00:00:01"))
+		d2 = makeDevice(mtu=1500, my_ip=ifc2, gateway=IPv4Address("192.168.1.1"), mac=EUI("02:00:00:00:00:02"))
+		link = Link(LinkMode.PointToPoint, (d1, d2))
+
+		d3 = makeDevice(mtu=1500, my_ip=IPv4Interface("192.168.1.12/24"), gateway=IPv4Address("192.168.1.1"), mac=EUI("02:00:00:00:00:03"))
+		with self.assertRaises(SmolTCPError):
+			link.attach(d3)
+
+		self.assertEqual(self.exchange(link.tick, d1, d2, ifc2.ip), (ifc1.ip, 5678))
+		self.assertGreater(link.delivered, 0)
+		self.assertEqual(link.dropped, 0)
+
+	def testSwitch(self):
+		ifcs = [IPv4Interface("192.168.1." + str(10 + i) + "/24") for i in range(3)]
+		devices = [makeDevice(mtu=1500, my_ip=ifc, gateway=IPv4Address("192.168.1.1"), mac=EUI("02:00:00:00:00:0" + str(i + 1))) for i, ifc in enumerate(ifcs)]
+		switch = Switch(devices)
+
+		self.assertEqual(self.exchange(switch.tick, devices[0], devices[2], ifcs[2].ip), (ifcs[0].ip, 5678))
+		stats = switch.stats
+		# the ARP request is flooded, the rest is forwarded to the learned ports only
+		self.assertGreater(stats.flooded, 0)
+		self.assertGreater(stats.forwarded, 0)
+		self.assertEqual(switch.macTableSize, 2)
+		# the host port gets the flooded frames
+		self.assertIsNotNone(switch.pop())
+
+	def makeRoutedDevices(self) -> typing.Tuple[Router, Device, Device]:
+		router = Router()
+		inside = router.addPort(mac=EUI("02:00:00:00:01:01"))
+		outside = router.addPort(mac=EUI("02:00:00:00:01:02"))
+		router.addAddress(inside, IPv4Interface("192.168.1.1/24"))
+		router.addAddress(outside, IPv4Interface("192.168.2.1/24"))
+
+		d1 = makeDevice(mtu=1500, my_ip=IPv4Interface("192.168.1.10/24"), gateway=IPv4Address("192.168.1.1"), mac=EUI("02:00:00:00:00:01"))
+		d2 = makeDevice(mtu=1500, my_ip=IPv4Interface("192.168.2.11/24"), gateway=IPv4Address("192.168.2.1"), mac=EUI("02:00:00:00:00:02"))
+		router.connect(inside, d1)
+		router.connect(outside, d2)
+		return router, d1, d2
+
+	def testRouter(self):
+		router, d1, d2 = self.makeRoutedDevices()
+		self.assertEqual(self.exchange(router.tick, d1, d2, IPv4Address("192.168.2.11")), (IPv4Address("192.168.1.10"), 5678))
+		self.assertGreater(router.stats.forwarded, 0)
+
+	def testNat(self):
+		router, d1, d2 = self.makeRoutedDevices()
+		router.setNat(1, IPv4Address("192.168.2.1"))
+		# the port of the inside is free on the outside, so it is kept
+		self.assertEqual(self.exchange(router.tick, d1, d2, IPv4Address("192.168.2.11")), (IPv4Address("192.168.2.1"), 5678))
+		stats = router.natStats
+		self.assertEqual(stats.connections, 1)
+		self.assertEqual(stats.outbound, 1)
+
+
+
 if __name__ == "__main__":
 	unittest.main()
@@ -0,0 +1,58 @@
+use smoltcp::phy::Medium;
+
+use crate::cdevice::CMedium;
+use crate::socket::socket::CSocketKind;
+
+/// Bumped on every change of the exported functions or of the layouts of the `repr(C)` types that breaks the bindings built for the previous version.
+pub const ABI_VERSION: u32 = 1;
+
+/// Bits of the mask returned by `getSupportedProtocols`.
+pub const PROTOCOL_IPV4: u32 = 1 << 0;
+pub const PROTOCOL_IPV6: u32 = 1 << 1;
+pub const PROTOCOL_IGMP: u32 = 1 << 2;
+pub const PROTOCOL_DHCPV4: u32 = 1 << 3;
+pub const PROTOCOL_DNS: u32 = 1 << 4;
+pub const PROTOCOL_IPV4_FRAGMENTATION: u32 = 1 << 5;
+pub const PROTOCOL_SIXLOWPAN: u32 = 1 << 6;
+
+/// The protocols smoltcp is built with. Must be kept in sync with the features of the dependency in `Cargo.toml`.
+const SUPPORTED_PROTOCOLS: u32 =
+	PROTOCOL_IPV4 | PROTOCOL_IPV6 | PROTOCOL_IGMP | PROTOCOL_DHCPV4 | PROTOCOL_DNS | PROTOCOL_IPV4_FRAGMENTATION;
+
+/// The kinds of sockets that can be created through the FFI.
+const SUPPORTED_SOCKET_KINDS: [CSocketKind; 4] = [CSocketKind::Tcp, CSocketKind::Udp, CSocketKind::Icmp, CSocketKind::Dns];
+
+/// The mediums `builderFinalize` and `routerAddPort` accept.
+const SUPPORTED_MEDIUMS: [CMedium; 2] = [CMedium::Ethernet, CMedium::Ip];
+
+/// The medium of a `CMedium` passed by the host, if it is one of `SUPPORTED_MEDIUMS`.
+pub fn supported_medium(medium: u8) -> Option<Medium> {
+	match CMedium::try_from(medium) {
+		Ok(medium) if SUPPORTED_MEDIUMS.contains(&medium) => Some(medium.into()),
+		_ => None,
+	}
+}
+
+/// Returns the version of the ABI of the library, see `ABI_VERSION`. Bindings should refuse to use a library with a version they were not written for.
+#[no_mangle]
+pub extern "C" fn getAbiVersion() -> u32 {
+	ABI_VERSION
+}
+
+/// Returns the mask of the `PROTOCOL_*` bits of the protocols the library is built with.
+#[no_mangle]
+pub extern "C" fn getSupportedProtocols() -> u32 {
+	SUPPORTED_PROTOCOLS
+}
+
+/// Returns the mask of the kinds of sockets the library can create, the bit `1 << kind` being set for each `CSocketKind`.
+#[no_mangle]
+pub extern "C" fn getSupportedSocketKinds() -> u32 {
+	SUPPORTED_SOCKET_KINDS.iter().fold(0, |mask, &kind| mask | (1 << kind as u32))
+}
+
+/// Returns the mask of the mediums the library can create devices with, the bit `1 << medium` being set for each `CMedium`.
+#[no_mangle]
+pub extern "C" fn getSupportedMediums() -> u32 {
+	SUPPORTED_MEDIUMS.iter().fold(0, |mask, &medium| mask | (1 << medium as u32))
+}
@@ -63,6 +63,21 @@ impl From<IpAddress> for CAddress {
 	}
 }
 
+impl CInterface {
+	/// The subnet, or why it is invalid, instead of panicking like `IpCidr::from`.
+	pub fn checked_cidr(self) -> Result<IpCidr, String> {
+		let addr = IpAddress::from(self.addr);
+		let (prefix, max) = match addr {
+			IpAddress::Ipv4(_) => (self.prefix.checked_sub((IPV4_PREFIX.len() * 8) as u8), 32),
+			IpAddress::Ipv6(_) => (Some(self.prefix), 128),
+		};
+		match prefix {
+			Some(prefix) if prefix <= max => Ok(IpCidr::new(addr, prefix)),
+			_ => Err(format!("the prefix length {} is invalid for {}", self.prefix, addr)),
+		}
+	}
+}
+
 impl From<CInterface> for IpCidr {
 	fn from(ifc: CInterface) -> Self {
 		let addr = ifc.addr.into();
@@ -1,23 +1,28 @@
-use core::ffi::c_void;
-
 use smoltcp::iface::InterfaceBuilder;
 use smoltcp::iface::NeighborCache;
 use smoltcp::iface::ReassemblyBuffer;
 use smoltcp::iface::Routes;
-use smoltcp::iface::SocketSet;
-use smoltcp::time::Instant;
-use smoltcp::wire::{HardwareAddress::Ethernet, IpCidr, Ipv4Address};
+use smoltcp::wire::{HardwareAddress::Ethernet, IpAddress};
 use std::collections::BTreeMap;
+use std::mem;
+use std::ptr;
 
 use crate::address::CAddress;
 use crate::address::CInterface;
 use crate::address::CMacAddress;
+use crate::abi::supported_medium;
 use crate::cdevice::CDevice;
 use crate::cdevice::CDevicePtr;
-use crate::cdevice::CMedium;
 use crate::device::SansIO;
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+/// What `CBuilderPtr` points to, opaque to C. Behind it is a boxed `InterfaceBuilder`.
+pub struct CBuilder {
+	_private: [u8; 0],
+}
 
-pub type CBuilderPtr = *mut c_void;
+pub type CBuilderPtr = *mut CBuilder;
 
 pub unsafe fn builder_from_opaque_ptr<'b, 'a>(
 	c_builder: CBuilderPtr,
@@ -32,29 +37,40 @@ pub fn box_builder(b: InterfaceBuilder) -> CBuilderPtr {
 	return Box::into_raw(Box::new(b)).cast();
 }
 
+/// Replaces the builder behind `c_builder` with what `f` makes of it, and returns `c_builder`. The builder is only borrowed, so the pointer stays the caller's even if a setter fails: everything that can fail must be done before calling this, as a panic in `f` leaves a new empty builder behind.
+unsafe fn update_builder<'a, F>(c_builder: CBuilderPtr, f: F) -> CBuilderPtr
+where
+	F: FnOnce(InterfaceBuilder<'a>) -> InterfaceBuilder<'a>,
+{
+	let builder = builder_from_opaque_ptr(c_builder);
+	let taken = mem::replace(builder, InterfaceBuilder::new());
+	*builder = f(taken);
+	c_builder
+}
+
 #[no_mangle]
 pub extern "C" fn newBuilder() -> CBuilderPtr {
-	return box_builder(InterfaceBuilder::new());
+	guard("newBuilder", || {
+		return box_builder(InterfaceBuilder::new());
+	})
 }
 
 #[no_mangle]
 pub extern "C" fn deleteBuilder(c_builder: CBuilderPtr) {
-	unsafe { drop::<Box<InterfaceBuilder<'_>>>(Box::from_raw(builder_from_opaque_ptr(c_builder))) }
+	guard("deleteBuilder", || unsafe { drop::<Box<InterfaceBuilder<'_>>>(Box::from_raw(builder_from_opaque_ptr(c_builder))) })
 }
 
 #[no_mangle]
 pub extern "C" fn builderSetHardwareAddr(c_builder: CBuilderPtr, mac: CMacAddress) -> CBuilderPtr {
-	let mut builder_box = unsafe { Box::from_raw(builder_from_opaque_ptr(c_builder)) };
-	*builder_box = (*builder_box).hardware_addr(Ethernet(mac.into()));
-	return Box::into_raw(builder_box).cast();
+	guard("builderSetHardwareAddr", || unsafe { update_builder(c_builder, |builder| builder.hardware_addr(Ethernet(mac.into()))) })
 }
 
 #[no_mangle]
 pub extern "C" fn builderInitNeighbourCache(c_builder: CBuilderPtr) -> CBuilderPtr {
-	let mut builder_box = unsafe { Box::from_raw(builder_from_opaque_ptr(c_builder)) };
-	let neighbor_cache = NeighborCache::new(BTreeMap::new()); // [None; 8]
-	*builder_box = (*builder_box).neighbor_cache(neighbor_cache);
-	return Box::into_raw(builder_box).cast();
+	guard("builderInitNeighbourCache", || {
+		let neighbor_cache = NeighborCache::new(BTreeMap::new()); // [None; 8]
+		unsafe { update_builder(c_builder, |builder| builder.neighbor_cache(neighbor_cache)) }
+	})
 }
 
 /*
@@ -72,53 +88,77 @@ pub extern "C" fn builderInitSixlowpan(c_builder: CBuilderPtr) -> CBuilderPtr{
 
 #[no_mangle]
 pub extern "C" fn builderInitIPv4ReassemblyBuffer(c_builder: CBuilderPtr, size: u32) -> CBuilderPtr {
-	let mut builder_box = unsafe { Box::from_raw(builder_from_opaque_ptr(c_builder)) };
-
-	let ipv4_frag_cache = ReassemblyBuffer::new(vec![], BTreeMap::new());
-	let ipv4_out_packet_cache = vec![0u8; size.try_into().unwrap()];
-
-	*builder_box = (*builder_box).ipv4_reassembly_buffer(ipv4_frag_cache);
-	*builder_box = (*builder_box).ipv4_fragmentation_buffer(ipv4_out_packet_cache);
-
-	return Box::into_raw(builder_box).cast();
+	guard("builderInitIPv4ReassemblyBuffer", || {
+		let ipv4_frag_cache = ReassemblyBuffer::new(vec![], BTreeMap::new());
+		let ipv4_out_packet_cache = vec![0u8; size as usize];
+		unsafe {
+			update_builder(c_builder, |builder| {
+				builder.ipv4_reassembly_buffer(ipv4_frag_cache).ipv4_fragmentation_buffer(ipv4_out_packet_cache)
+			})
+		}
+	})
 }
 
+/// Returns NULL, leaving the builder as it was, if the prefix length doesn't fit the address.
 #[no_mangle]
 pub extern "C" fn builderSetIPAddr(c_builder: CBuilderPtr, my_ip: CInterface) -> CBuilderPtr {
-	let mut builder_box = unsafe { Box::from_raw(builder_from_opaque_ptr(c_builder)) };
-	let ifc = IpCidr::from(my_ip);
-	let ip_addrs = [ifc];
-	*builder_box = (*builder_box).ip_addrs(ip_addrs);
-	return Box::into_raw(builder_box).cast();
+	guard("builderSetIPAddr", || {
+		let ifc = match my_ip.checked_cidr() {
+			Ok(ifc) => ifc,
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("builderSetIPAddr: {}", err));
+				return ptr::null_mut();
+			}
+		};
+		let ip_addrs = [ifc];
+		unsafe { update_builder(c_builder, |builder| builder.ip_addrs(ip_addrs)) }
+	})
 }
 
+/// Returns NULL, leaving the builder as it was, if the gateway is not an IPv4 address.
 #[no_mangle]
 pub extern "C" fn builderSetRoutes(c_builder: CBuilderPtr, gateway_ip: CAddress) -> CBuilderPtr {
-	let mut builder_box = unsafe { Box::from_raw(builder_from_opaque_ptr(c_builder)) };
-	let default_v4_gw = Ipv4Address::from(gateway_ip);
-	let mut routes_storage = [None; 2];
-	let mut routes = Routes::new(&mut routes_storage[..]);
-	routes.add_default_ipv4_route(default_v4_gw).unwrap();
-	//routes.add_default_ipv6_route(default_v6_gw).unwrap();
-	*builder_box = (*builder_box).routes(routes);
-	return Box::into_raw(builder_box).cast();
+	guard("builderSetRoutes", || {
+		let default_v4_gw = match IpAddress::from(gateway_ip) {
+			IpAddress::Ipv4(addr) => addr,
+			addr => {
+				set_last_error(ResultCode::Illegal, format!("builderSetRoutes: the gateway {} is not IPv4", addr));
+				return ptr::null_mut();
+			}
+		};
+		let mut routes_storage = [None; 2];
+		let mut routes = Routes::new(&mut routes_storage[..]);
+		if let Err(err) = routes.add_default_ipv4_route(default_v4_gw) {
+			set_last_error(ResultCode::Illegal, format!("builderSetRoutes: cannot add the default route: {}", err));
+			return ptr::null_mut();
+		}
+		//routes.add_default_ipv6_route(default_v6_gw).unwrap();
+		unsafe { update_builder(c_builder, |builder| builder.routes(routes)) }
+	})
 }
 
+/// Makes a device out of the builder, which is freed if this succeeds. If this fails and returns NULL, the builder stays the caller's, to be freed with `deleteBuilder`. `medium` is a `CMedium`, the ones the library doesn't support are refused.
 #[no_mangle]
 pub extern "C" fn builderFinalize(
 	c_builder: CBuilderPtr,
-	medium: CMedium,
+	medium: u8,
 	mtu: usize,
 ) -> CDevicePtr {
-	let builder_box = unsafe { Box::from_raw(builder_from_opaque_ptr(c_builder)) };
-	let mut device = SansIO::new(mtu, medium.into());
-	let iface = (*builder_box).finalize(&mut device);
-	let c_dev = CDevice {
-		device: device,
-		timestamp: Instant::now(),
-		sockets: SocketSet::new(vec![]),
-		iface: iface,
-	};
-
-	return Box::into_raw(Box::new(c_dev)).cast();
+	guard("builderFinalize", || {
+		let medium = match supported_medium(medium) {
+			Some(medium) => medium,
+			None => {
+				set_last_error(ResultCode::Illegal, format!("builderFinalize: the medium {} is not supported", medium));
+				return ptr::null_mut();
+			}
+		};
+		let builder = unsafe { builder_from_opaque_ptr(c_builder) };
+		let mut device = SansIO::new(mtu, medium);
+		// Taken out of its box, so the box is still there for the caller to free if finalizing panics.
+		let iface = mem::replace(builder, InterfaceBuilder::new()).finalize(&mut device);
+		unsafe { drop(Box::from_raw(builder as *mut InterfaceBuilder)) };
+		let c_dev = CDevice::new(device, iface);
+
+		return Box::into_raw(Box::new(c_dev)).cast();
+	})
 }
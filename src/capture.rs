@@ -0,0 +1,291 @@
+use std::ffi::{c_char, CStr};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use log::debug;
+
+use smoltcp::phy::Medium;
+use smoltcp::time::Instant;
+
+use crate::cdevice::{device_from_opaque_ptr, CDevicePtr};
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+const BLOCK_TYPE_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_TYPE_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_TYPE_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPTION_END: u16 = 0;
+const OPTION_EPB_FLAGS: u16 = 2;
+
+pub const LINKTYPE_ETHERNET: u16 = 1;
+pub const LINKTYPE_RAW: u16 = 101;
+pub const LINKTYPE_IEEE802_15_4_NOFCS: u16 = 230;
+
+/// Frames are never truncated.
+const SNAP_LEN: u32 = 0;
+
+/// Which way a captured frame went, recorded in the `epb_flags` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureDirection {
+	/// Put into the device by the host.
+	Inbound,
+	/// Transmitted by the stack.
+	Outbound,
+}
+
+impl CaptureDirection {
+	fn epb_flags(self) -> u32 {
+		match self {
+			CaptureDirection::Inbound => 0b01,
+			CaptureDirection::Outbound => 0b10,
+		}
+	}
+}
+
+/// The pcapng link type of the frames of a device of `medium`.
+pub fn link_type(medium: Medium) -> u16 {
+	match medium {
+		Medium::Ethernet => LINKTYPE_ETHERNET,
+		Medium::Ip => LINKTYPE_RAW,
+		#[allow(unreachable_patterns)]
+		_ => LINKTYPE_IEEE802_15_4_NOFCS,
+	}
+}
+
+#[derive(Debug)]
+enum CaptureSink {
+	File(BufWriter<File>),
+	Memory(Vec<u8>),
+}
+
+impl Write for CaptureSink {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self {
+			CaptureSink::File(file) => file.write(buf),
+			CaptureSink::Memory(buffer) => buffer.write(buf),
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match self {
+			CaptureSink::File(file) => file.flush(),
+			CaptureSink::Memory(buffer) => buffer.flush(),
+		}
+	}
+}
+
+/// Writes the frames passing through a device to a pcapng file or to memory, with their timestamps on the clock of the stack and their directions. The file is a single section with a single interface, with microsecond timestamps.
+#[derive(Debug)]
+pub struct Capture {
+	sink: CaptureSink,
+	/// Count of frames written.
+	frames: u64,
+	/// The first failure to write. Nothing is written after it.
+	error: Option<io::Error>,
+}
+
+impl Capture {
+	/// Starts a capture into a new file at `path`, replacing it if it exists.
+	pub fn to_file(path: &str, medium: Medium) -> io::Result<Capture> {
+		let file = File::create(path)?;
+		let mut capture = Capture::new(CaptureSink::File(BufWriter::new(file)));
+		capture.write_header(medium)?;
+		Ok(capture)
+	}
+
+	/// Starts a capture into a memory buffer, see `buffer`.
+	pub fn to_memory(medium: Medium) -> Capture {
+		let mut capture = Capture::new(CaptureSink::Memory(Vec::new()));
+		// Writing into a `Vec` cannot fail.
+		let _ = capture.write_header(medium);
+		capture
+	}
+
+	fn new(sink: CaptureSink) -> Capture {
+		Capture {
+			sink,
+			frames: 0,
+			error: None,
+		}
+	}
+
+	fn write_header(&mut self, medium: Medium) -> io::Result<()> {
+		let mut block = Vec::with_capacity(28);
+		block.extend_from_slice(&BLOCK_TYPE_SECTION_HEADER.to_le_bytes());
+		block.extend_from_slice(&28u32.to_le_bytes());
+		block.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+		block.extend_from_slice(&1u16.to_le_bytes());
+		block.extend_from_slice(&0u16.to_le_bytes());
+		// The length of the section is not known in advance.
+		block.extend_from_slice(&(-1i64).to_le_bytes());
+		block.extend_from_slice(&28u32.to_le_bytes());
+
+		block.extend_from_slice(&BLOCK_TYPE_INTERFACE_DESCRIPTION.to_le_bytes());
+		block.extend_from_slice(&20u32.to_le_bytes());
+		block.extend_from_slice(&link_type(medium).to_le_bytes());
+		block.extend_from_slice(&0u16.to_le_bytes());
+		block.extend_from_slice(&SNAP_LEN.to_le_bytes());
+		block.extend_from_slice(&20u32.to_le_bytes());
+
+		self.sink.write_all(&block)
+	}
+
+	/// Writes a frame. A failure is remembered and stops the capture, see `error`.
+	pub fn record(&mut self, timestamp: Instant, direction: CaptureDirection, frame: &[u8]) {
+		if self.error.is_some() {
+			return;
+		}
+		if let Err(err) = self.write_packet(timestamp, direction, frame) {
+			debug!("capture failed: {}", err);
+			self.error = Some(err);
+		} else {
+			self.frames += 1;
+		}
+	}
+
+	fn write_packet(&mut self, timestamp: Instant, direction: CaptureDirection, frame: &[u8]) -> io::Result<()> {
+		let padded_len = (frame.len() + 3) & !3;
+		let total_len = 32 + padded_len + 12;
+		let block_len = u32::try_from(total_len).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the frame is too big"))?;
+		let micros = timestamp.total_micros().max(0) as u64;
+
+		let mut block = Vec::with_capacity(total_len);
+		block.extend_from_slice(&BLOCK_TYPE_ENHANCED_PACKET.to_le_bytes());
+		block.extend_from_slice(&block_len.to_le_bytes());
+		// The index of the only interface.
+		block.extend_from_slice(&0u32.to_le_bytes());
+		block.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+		block.extend_from_slice(&(micros as u32).to_le_bytes());
+		block.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+		block.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+		block.extend_from_slice(frame);
+		block.resize(32 + padded_len - 4, 0);
+
+		block.extend_from_slice(&OPTION_EPB_FLAGS.to_le_bytes());
+		block.extend_from_slice(&4u16.to_le_bytes());
+		block.extend_from_slice(&direction.epb_flags().to_le_bytes());
+		block.extend_from_slice(&OPTION_END.to_le_bytes());
+		block.extend_from_slice(&0u16.to_le_bytes());
+
+		block.extend_from_slice(&block_len.to_le_bytes());
+		self.sink.write_all(&block)
+	}
+
+	/// Count of frames written so far.
+	pub fn frames(&self) -> u64 {
+		self.frames
+	}
+
+	/// The failure that has stopped the capture, if any.
+	pub fn error(&self) -> Option<&io::Error> {
+		self.error.as_ref()
+	}
+
+	/// The capture written so far, if it goes to memory.
+	pub fn buffer(&self) -> Option<&[u8]> {
+		match &self.sink {
+			CaptureSink::Memory(buffer) => Some(buffer.as_slice()),
+			CaptureSink::File(_) => None,
+		}
+	}
+
+	/// Flushes the capture. Returns the first failure to write, if any.
+	pub fn finish(mut self) -> io::Result<()> {
+		if let Some(err) = self.error.take() {
+			return Err(err);
+		}
+		self.sink.flush()
+	}
+}
+
+/// Starts writing all the frames put into the device and transmitted by it into a pcapng file at `path` (a NUL-terminated UTF-8 string), replacing the capture going on, if any. Returns `Illegal` if the file cannot be created.
+#[no_mangle]
+pub extern "C" fn captureStartFile(c_device: CDevicePtr, path: *const c_char) -> ResultCode {
+	guard("captureStartFile", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if path.is_null() {
+			set_last_error(ResultCode::Illegal, "captureStartFile: got NULL path".to_string());
+			return ResultCode::Illegal;
+		}
+		let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+			Ok(path) => path,
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("captureStartFile: the path is not UTF-8: {}", err));
+				return ResultCode::Illegal;
+			}
+		};
+		match Capture::to_file(path, c_device.device.medium()) {
+			Ok(capture) => {
+				c_device.device.capture = Some(capture);
+				ResultCode::OK
+			}
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("captureStartFile: cannot write {}: {}", path, err));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Starts writing all the frames put into the device and transmitted by it into a pcapng capture in memory, replacing the capture going on, if any. The capture can be read with `captureGetBuffer`.
+#[no_mangle]
+pub extern "C" fn captureStartMemory(c_device: CDevicePtr) {
+	guard("captureStartMemory", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.device.capture = Some(Capture::to_memory(c_device.device.medium()));
+	})
+}
+
+/// Stops the capture going on, flushing its file or dropping its memory buffer. Returns `Illegal` if writing the file has failed at some point.
+#[no_mangle]
+pub extern "C" fn captureStop(c_device: CDevicePtr) -> ResultCode {
+	guard("captureStop", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match c_device.device.capture.take().map(Capture::finish) {
+			None | Some(Ok(())) => ResultCode::OK,
+			Some(Err(err)) => {
+				set_last_error(ResultCode::Illegal, format!("captureStop: the capture has failed: {}", err));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Returns the count of frames captured since the start of the capture going on, 0 if there is none.
+#[no_mangle]
+pub extern "C" fn captureGetFrameCount(c_device: CDevicePtr) -> u64 {
+	guard("captureGetFrameCount", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.device.capture.as_ref().map_or(0, Capture::frames)
+	})
+}
+
+/// Copies the in-memory capture going on into `dst`. Its size is written to `capture_size` (if not NULL). If `size` is too small, nothing is copied and `BufferInsufficient` is returned; if there is no in-memory capture going on, `Illegal` is returned. The capture is a valid pcapng file at any moment.
+#[no_mangle]
+pub extern "C" fn captureGetBuffer(c_device: CDevicePtr, dst: *mut u8, size: usize, capture_size: *mut usize) -> ResultCode {
+	guard("captureGetBuffer", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let buffer = match c_device.device.capture.as_ref().and_then(Capture::buffer) {
+			Some(buffer) => buffer,
+			None => {
+				set_last_error(ResultCode::Illegal, "captureGetBuffer: no in-memory capture is going on".to_string());
+				return ResultCode::Illegal;
+			}
+		};
+		if let Some(capture_size) = unsafe { capture_size.as_mut() } {
+			*capture_size = buffer.len();
+		}
+		if buffer.len() > size {
+			set_last_error(
+				ResultCode::BufferInsufficient,
+				format!("captureGetBuffer: the capture is {} bytes, but the buffer is only {}", buffer.len(), size),
+			);
+			return ResultCode::BufferInsufficient;
+		}
+		let dst = host_slice_mut!("captureGetBuffer", dst, buffer.len());
+		dst.copy_from_slice(buffer);
+		ResultCode::OK
+	})
+}
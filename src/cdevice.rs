@@ -1,26 +1,112 @@
 use std::ffi::c_void;
 
 use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use log::debug;
 
 use smoltcp::phy::{Medium};
-use smoltcp::iface::{Interface, SocketSet};
+use std::collections::{BTreeMap, BTreeSet};
+
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::socket::tcp;
+use smoltcp::wire::IpAddress;
 //use smoltcp::iface::interface::InterfaceInner;
 
-use crate::device::SansIO;
-use smoltcp::time::Instant;
+use crate::device::{
+	HostBufferRelease, HostBufferReleaseCallback, QueueOverflowPolicy, SansIO, TxCallback, TxSink,
+	TxSlotAcquireCallback, TxSlotCommitCallback,
+};
+use crate::ffi::{guard, guard_or, host_slice, host_slice_mut, pop_frame_to_host, set_last_error};
+use crate::result_codes::ResultCode;
+use crate::ring::RingPair;
+use smoltcp::time::{Duration, Instant};
+
+/// Returned by `ifaceGetPollAt` and `ifaceGetPollDelay` when the stack has nothing scheduled, or when they have failed.
+pub const POLL_NO_DEADLINE: i64 = -1;
+
+/// Source of `CDevice::id`.
+static NEXT_DEVICE_ID: AtomicU64 = AtomicU64::new(1);
 
 pub struct CDevice<'a> {
+	/// Unique among the devices created by the process, to tell whose socket handle is whose.
+	pub id: u64,
 	pub device: SansIO,
+	/// ICMP datagrams taken out of their sockets but not yet copied out to the host, since ICMP sockets cannot be peeked.
+	pub icmp_pending: BTreeMap<SocketHandle, (Vec<u8>, IpAddress)>,
+	/// TCP sockets deleted with a graceful close, removed from `sockets` once their connections are over.
+	pub closing: Vec<SocketHandle>,
+	/// The sockets the host has live handles of. `freeDevice` is refused while it is not empty.
+	pub handles: BTreeSet<SocketHandle>,
+	/// Count of the links and other in-process networks the device is attached to, which hold pointers to it. `freeDevice` is refused while it is not 0.
+	pub attachments: usize,
 	pub timestamp: Instant,
 	pub sockets: SocketSet<'a>,
 	pub iface: Interface<'a>,
 	//pub cx: &mut InterfaceInner<'a>
 }
 
+impl<'a> CDevice<'a> {
+	pub fn new(device: SansIO, iface: Interface<'a>) -> Self {
+		CDevice {
+			id: NEXT_DEVICE_ID.fetch_add(1, Ordering::Relaxed),
+			device,
+			icmp_pending: BTreeMap::new(),
+			closing: Vec::new(),
+			handles: BTreeSet::new(),
+			attachments: 0,
+			timestamp: Instant::now(),
+			sockets: SocketSet::new(vec![]),
+			iface,
+		}
+	}
+
+	/// Moves the device clock to `timestamp` and polls the interface. Returns whether the readiness of any socket may have changed.
+	pub fn poll(&mut self, timestamp: Instant) -> bool {
+		self.timestamp = timestamp;
+		self.device.timestamp = timestamp;
+		let readiness_changed = match self.iface.poll(self.timestamp, &mut self.device, &mut self.sockets) {
+			Ok(readiness_changed) => readiness_changed,
+			Err(e) => {
+				debug!("poll error: {}", e);
+				false
+			}
+		};
+		self.device.release_tx(self.timestamp);
+		self.reap_closed_sockets();
+		readiness_changed
+	}
+
+	/// Removes the sockets in `closing` whose connections are over.
+	fn reap_closed_sockets(&mut self) {
+		let sockets = &mut self.sockets;
+		self.closing.retain(|&handle| {
+			if sockets.get::<tcp::Socket>(handle).is_open() {
+				return true;
+			}
+			sockets.remove(handle);
+			false
+		});
+	}
+
+	/// Returns the moment the interface should be polled next, or `None` if neither a socket nor an impairment stage has a deadline.
+	pub fn poll_at(&mut self) -> Option<Instant> {
+		let sockets = self.iface.poll_at(self.timestamp, &self.sockets);
+		match (sockets, self.device.next_impairment_release()) {
+			(Some(sockets), Some(release)) => Some(if release < sockets { release } else { sockets }),
+			(sockets, release) => sockets.or(release),
+		}
+	}
+
+	/// Returns how long after the last poll the interface should be polled again, or `None` if neither a socket nor an impairment stage has a deadline.
+	pub fn poll_delay(&mut self) -> Option<Duration> {
+		let at = self.poll_at()?;
+		Some(if at > self.timestamp { at - self.timestamp } else { Duration::ZERO })
+	}
+}
+
 /// Type of medium of a device.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum CMedium {
 	Invalid = 0,
@@ -29,6 +115,20 @@ pub enum CMedium {
 	Ieee802154 = 4,
 }
 
+impl TryFrom<u8> for CMedium {
+	type Error = u8;
+
+	fn try_from(value: u8) -> Result<Self, u8> {
+		match value {
+			0 => Ok(CMedium::Invalid),
+			2 => Ok(CMedium::Ethernet),
+			3 => Ok(CMedium::Ip),
+			4 => Ok(CMedium::Ieee802154),
+			other => Err(other),
+		}
+	}
+}
+
 impl From<CMedium> for Medium {
 	fn from(medium: CMedium) -> Self {
 		match medium {
@@ -45,7 +145,29 @@ impl From<CMedium> for Medium {
 	}
 }
 
-pub type CDevicePtr = *mut c_void;
+/// Points to a `CDevice`, which C only sees as an opaque struct, so it cannot be mixed up with the other pointers.
+pub type CDevicePtr = *mut CDevice<'static>;
+
+/// Describes one frame for `putRxPackets` and `getTxPackets`.
+#[repr(C)]
+pub struct CPacketIoVec {
+	/// The frame (RX) or the buffer to copy a frame into (TX).
+	pub data: *mut u8,
+	/// The size of the frame (RX) or of the buffer (TX). For TX it is set to the size of the frame, even if it didn't fit.
+	pub size: u32,
+	/// Set to the outcome for this frame.
+	pub status: ResultCode,
+}
+
+unsafe fn io_vecs_from_raw<'b>(vecs: *mut CPacketIoVec, count: usize) -> &'b mut [CPacketIoVec] {
+	if count == 0 {
+		&mut []
+	} else if vecs.is_null() {
+		panic!("Fatal error, got NULL `CPacketIoVec` pointer for {} frames", count);
+	} else {
+		slice::from_raw_parts_mut(vecs, count)
+	}
+}
 
 pub unsafe fn device_from_opaque_ptr<'b, 'a>(c_device: CDevicePtr) -> &'b mut CDevice<'a> {
 	if c_device.is_null() {
@@ -54,75 +176,422 @@ pub unsafe fn device_from_opaque_ptr<'b, 'a>(c_device: CDevicePtr) -> &'b mut CD
 	&mut *(c_device.cast())
 }
 
+/// Frees the device. Refused with `Illegal` while the host holds handles of its sockets or the device is attached to a link, a switch or a router, since they would point into the freed device: the sockets must be deleted and the device detached first. Sockets being closed gracefully don't count.
 #[no_mangle]
-pub extern "C" fn freeDevice(c_device: CDevicePtr) {
-	unsafe { drop::<Box<CDevice<'_>>>(Box::from_raw(device_from_opaque_ptr(c_device))) }
+pub extern "C" fn freeDevice(c_device: CDevicePtr) -> ResultCode {
+	guard("freeDevice", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if !c_device.handles.is_empty() {
+			set_last_error(
+				ResultCode::Illegal,
+				format!("freeDevice: {} sockets of the device still have handles", c_device.handles.len()),
+			);
+			return ResultCode::Illegal;
+		}
+		if c_device.attachments != 0 {
+			set_last_error(
+				ResultCode::Illegal,
+				format!("freeDevice: the device is still attached to {} links, switches or routers", c_device.attachments),
+			);
+			return ResultCode::Illegal;
+		}
+		unsafe { drop::<Box<CDevice<'_>>>(Box::from_raw(c_device)) };
+		ResultCode::OK
+	})
+}
+
+/// Returns the count of sockets of the device the host holds handles of, which must be deleted before `freeDevice`.
+#[no_mangle]
+pub extern "C" fn getCountOfSocketHandles(c_device: CDevicePtr) -> usize {
+	guard("getCountOfSocketHandles", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.handles.len()
+	})
 }
 
 #[no_mangle]
 pub extern "C" fn getCountOfPacketsInTxQueue(c_device: CDevicePtr) -> usize {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	return c_device.device.tx.len();
+	guard("getCountOfPacketsInTxQueue", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		return c_device.device.tx.len();
+	})
 }
 
 #[no_mangle]
 pub extern "C" fn getLastTxPacketSize(c_device: CDevicePtr) -> usize {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	match c_device.device.tx.get(0) {
-		Some(last_packet) => {
-			return last_packet.len();
+	guard("getLastTxPacketSize", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match c_device.device.tx.front() {
+			Some(last_packet) => {
+				return last_packet.len();
+			}
+			None => {
+				return 0;
+			}
+		}
+	})
+}
+
+/// Copies the oldest frame of the TX queue into `dst` and removes it from the queue. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is left in the queue and `BufferInsufficient` is returned; if the queue is empty, `Exhausted` is returned.
+#[no_mangle]
+pub extern "C" fn getLastTxPacket(
+	c_device: CDevicePtr,
+	dst: *mut u8,
+	size: u32,
+	frame_size: *mut usize,
+) -> ResultCode {
+	guard("getLastTxPacket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		unsafe { pop_frame_to_host("getLastTxPacket", &mut c_device.device.tx, dst, size, frame_size) }
+	})
+}
+
+/// Puts a received frame into the RX queue. Returns `Dropped` if the queue is full and its overflow policy is `DropNewest`, so the frame was dropped, and `Exhausted` if the policy is `Reject`.
+#[no_mangle]
+pub extern "C" fn putRxPacket(c_device: CDevicePtr, src: *const u8, size: u32) -> ResultCode {
+	guard("putRxPacket", || {
+		let src = host_slice!("putRxPacket", src, size);
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match c_device.device.put_rx(src.to_vec().into()) {
+			Ok(()) => ResultCode::OK,
+			Err(smoltcp::Error::Dropped) => {
+				set_last_error(ResultCode::Dropped, "putRxPacket: the RX queue is full, the frame was dropped".to_string());
+				ResultCode::Dropped
+			}
+			Err(err) => {
+				set_last_error(ResultCode::Exhausted, format!("putRxPacket: the RX queue refused the frame: {}", err));
+				ResultCode::Exhausted
+			}
 		}
-		None => {
-			return 0;
+	})
+}
+
+/// Puts up to `count` received frames into the RX queue in one call. Stops at the first frame the queue refuses, marking it `Exhausted`, or whose data is NULL, marking it `Illegal`. The frames the queue drops because of the `DropNewest` policy are marked `Dropped` and taken, and `Dropped` is returned if nothing was refused. The count of frames taken is written to `consumed` (if not NULL).
+#[no_mangle]
+pub extern "C" fn putRxPackets(
+	c_device: CDevicePtr,
+	vecs: *mut CPacketIoVec,
+	count: usize,
+	consumed: *mut usize,
+) -> ResultCode {
+	guard("putRxPackets", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let vecs = unsafe { io_vecs_from_raw(vecs, count) };
+
+		let mut taken = 0;
+		let mut res = ResultCode::OK;
+		for vec in vecs.iter_mut() {
+			let src = match unsafe { host_slice(vec.data, vec.size as usize) } {
+				Some(src) => src,
+				None => {
+					set_last_error(ResultCode::Illegal, format!("putRxPackets: got NULL data for {} bytes", vec.size));
+					vec.status = ResultCode::Illegal;
+					res = ResultCode::Illegal;
+					break;
+				}
+			};
+			match c_device.device.put_rx(src.to_vec().into()) {
+				Ok(()) => {
+					vec.status = ResultCode::OK;
+					taken += 1;
+				}
+				Err(smoltcp::Error::Dropped) => {
+					vec.status = ResultCode::Dropped;
+					res = ResultCode::Dropped;
+					taken += 1;
+				}
+				Err(_) => {
+					vec.status = ResultCode::Exhausted;
+					res = ResultCode::Exhausted;
+					break;
+				}
+			}
 		}
-	}
+
+		if let Some(consumed) = unsafe { consumed.as_mut() } {
+			*consumed = taken;
+		}
+		res
+	})
 }
 
+/// Copies up to `count` frames out of the TX queue in one call. Stops at the first frame that doesn't fit its buffer: it is left in the queue, its slot gets `BufferInsufficient` and the required size, and `BufferInsufficient` is returned. It also stops at a slot whose buffer is NULL, marking it `Illegal`. The count of frames copied is written to `produced` (if not NULL).
 #[no_mangle]
-pub extern "C" fn getLastTxPacket(c_device: CDevicePtr, dst: *mut u8, size: u32) -> usize {
-	let dst = unsafe { slice::from_raw_parts_mut(dst, size as usize) };
+pub extern "C" fn getTxPackets(
+	c_device: CDevicePtr,
+	vecs: *mut CPacketIoVec,
+	count: usize,
+	produced: *mut usize,
+) -> ResultCode {
+	guard("getTxPackets", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let vecs = unsafe { io_vecs_from_raw(vecs, count) };
 
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	match c_device.device.tx.pop_front() {
-		Some(last_packet) => {
-			dst.copy_from_slice(last_packet.as_slice());
-			return last_packet.len();
+		let mut copied = 0;
+		let mut res = ResultCode::OK;
+		for vec in vecs.iter_mut() {
+			let frame_size = match c_device.device.tx.front() {
+				Some(frame) => frame.len(),
+				None => break,
+			};
+			vec.status = if frame_size > vec.size as usize {
+				ResultCode::BufferInsufficient
+			} else if let Some(dst) = unsafe { host_slice_mut(vec.data, frame_size) } {
+				let frame = c_device.device.tx.pop().unwrap();
+				dst.copy_from_slice(&frame);
+				copied += 1;
+				ResultCode::OK
+			} else {
+				set_last_error(ResultCode::Illegal, format!("getTxPackets: got NULL data for {} bytes", frame_size));
+				ResultCode::Illegal
+			};
+			vec.size = frame_size as u32;
+			if vec.status != ResultCode::OK {
+				res = vec.status;
+				break;
+			}
 		}
-		None => {
-			return 0;
+
+		if let Some(produced) = unsafe { produced.as_mut() } {
+			*produced = copied;
 		}
-	}
+		res
+	})
 }
 
+/// Sets the callback giving the buffers put with `putRxPacketBorrowed` back to the host. Frames already in the RX queue keep the callback they were put with.
 #[no_mangle]
-pub extern "C" fn putRxPacket(c_device: CDevicePtr, src: *const u8, size: u32) {
-	let src = unsafe { slice::from_raw_parts(src, size as usize) };
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	c_device.device.rx.push_back(src.to_vec());
+pub extern "C" fn setRxReleaseCallback(
+	c_device: CDevicePtr,
+	callback: HostBufferReleaseCallback,
+	user_data: *mut c_void,
+) {
+	guard("setRxReleaseCallback", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.device.rx_release = callback.map(|callback| HostBufferRelease {
+			callback,
+			user_data,
+		});
+	})
 }
 
+/// Puts a received frame into the RX queue without copying it. The stack processes the frame right in the host buffer (and may modify it), so the buffer must stay valid until it is passed to the callback set with `setRxReleaseCallback`. If the frame is not accepted, the buffer is released before this function returns. Returns `Illegal` if `src` is NULL or no release callback is set, `Dropped` if the queue is full and its overflow policy is `DropNewest`, and `Exhausted` if the policy is `Reject`.
 #[no_mangle]
-pub extern "C" fn ifacePoll(c_device: CDevicePtr) {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-
-	c_device.timestamp = Instant::now();
-	match c_device.iface.poll(
-		c_device.timestamp,
-		&mut c_device.device,
-		&mut c_device.sockets,
-	) {
-		Ok(_) => {}
-		Err(e) => {
-			debug!("poll error: {}", e);
+pub extern "C" fn putRxPacketBorrowed(c_device: CDevicePtr, src: *mut u8, size: u32) -> ResultCode {
+	guard("putRxPacketBorrowed", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if src.is_null() {
+			set_last_error(ResultCode::Illegal, "putRxPacketBorrowed: got NULL `src`".to_string());
+			return ResultCode::Illegal;
+		}
+		match unsafe { c_device.device.put_rx_borrowed(src, size as usize) } {
+			Ok(()) => ResultCode::OK,
+			Err(smoltcp::Error::Illegal) => {
+				set_last_error(ResultCode::Illegal, "putRxPacketBorrowed: no release callback is set".to_string());
+				ResultCode::Illegal
+			}
+			Err(smoltcp::Error::Dropped) => {
+				set_last_error(ResultCode::Dropped, "putRxPacketBorrowed: the RX queue is full, the frame was dropped".to_string());
+				ResultCode::Dropped
+			}
+			Err(err) => {
+				set_last_error(ResultCode::Exhausted, format!("putRxPacketBorrowed: the RX queue refused the frame: {}", err));
+				ResultCode::Exhausted
+			}
 		}
+	})
+}
+
+/// Whether the device is attached to a link, a switch or a router, which take its frames from its TX queue, so they cannot be sent elsewhere. Records the error of `name` if so.
+fn tx_sink_taken(name: &str, c_device: &CDevice) -> bool {
+	if c_device.attachments == 0 {
+		return false;
 	}
+	set_last_error(
+		ResultCode::Illegal,
+		format!("{}: the device is attached to {} links, switches or routers, which take its TX queue", name, c_device.attachments),
+	);
+	true
 }
 
-/*
+/// Makes the stack write the transmitted frames directly into buffers provided by the host instead of queuing them. Pass NULL for both callbacks to go back to the TX queue. Returns `Illegal` if only one of the callbacks is NULL, or if the device is attached to a link, a switch or a router.
 #[no_mangle]
-pub extern "C" fn phyWait(c_device: CDevicePtr){
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
+pub extern "C" fn setTxSlotProvider(
+	c_device: CDevicePtr,
+	acquire: TxSlotAcquireCallback,
+	commit: TxSlotCommitCallback,
+	user_data: *mut c_void,
+) -> ResultCode {
+	guard("setTxSlotProvider", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if tx_sink_taken("setTxSlotProvider", c_device) {
+			return ResultCode::Illegal;
+		}
+		match (acquire, commit) {
+			(Some(acquire), Some(commit)) => {
+				c_device.device.tx_sink = TxSink::HostSlots {
+					acquire,
+					commit,
+					user_data,
+				};
+				ResultCode::OK
+			}
+			(None, None) => {
+				c_device.device.tx_sink = TxSink::Queue;
+				ResultCode::OK
+			}
+			_ => {
+				set_last_error(ResultCode::Illegal, "setTxSlotProvider: only one of the callbacks is NULL".to_string());
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Makes the stack push each transmitted frame to `callback` synchronously, from within `ifacePoll`, instead of queuing it. Pass NULL to go back to the TX queue. Returns `Illegal` if the device is attached to a link, a switch or a router.
+#[no_mangle]
+pub extern "C" fn setTxCallback(c_device: CDevicePtr, callback: TxCallback, user_data: *mut c_void) -> ResultCode {
+	guard("setTxCallback", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if tx_sink_taken("setTxCallback", c_device) {
+			return ResultCode::Illegal;
+		}
+		c_device.device.tx_sink = match callback {
+			Some(callback) => TxSink::Callback { callback, user_data },
+			None => TxSink::Queue,
+		};
+		ResultCode::OK
+	})
+}
 
-	phy_wait(fd, c_device.iface.poll_delay(c_device.timestamp, &c_device.sockets)).expect("wait error");
+/// Makes the device exchange frames with the host through a pair of single-producer/single-consumer rings in the shared memory region of `size` bytes at `region`, without an FFI call per frame. The region (aligned to `RING_REGION_ALIGN`) is split into two equal halves, each starting with a `CRingHeader`: the host produces received frames into the first one and consumes transmitted frames from the second one. The rings are initialized by this function, and the region must stay valid until `detachRingTransport` or `freeDevice`. Returns `Illegal` if the region is misaligned or too small, or if the device is attached to a link, a switch or a router.
+#[no_mangle]
+pub extern "C" fn attachRingTransport(c_device: CDevicePtr, region: *mut u8, size: usize) -> ResultCode {
+	guard("attachRingTransport", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if tx_sink_taken("attachRingTransport", c_device) {
+			return ResultCode::Illegal;
+		}
+		match unsafe { RingPair::init(region, size) } {
+			Ok(rings) => {
+				c_device.device.rx_ring = Some(rings.rx);
+				c_device.device.tx_sink = TxSink::Ring(rings.tx);
+				ResultCode::OK
+			}
+			Err(err) => {
+				set_last_error(
+					ResultCode::Illegal,
+					format!("attachRingTransport: the region of {} bytes cannot hold the rings: {}", size, err),
+				);
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Stops using the rings set up by `attachRingTransport` and goes back to the TX queue. Frames left in the rings are not touched.
+#[no_mangle]
+pub extern "C" fn detachRingTransport(c_device: CDevicePtr) {
+	guard("detachRingTransport", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.device.rx_ring = None;
+		if let TxSink::Ring(_) = c_device.device.tx_sink {
+			c_device.device.tx_sink = TxSink::Queue;
+		}
+	})
+}
+
+/// Limits the count of frames in the RX queue, `0` means unlimited. `policy` is a `QueueOverflowPolicy`, `Illegal` is returned for other values.
+#[no_mangle]
+pub extern "C" fn setRxQueueLimit(c_device: CDevicePtr, capacity: usize, policy: u8) -> ResultCode {
+	guard("setRxQueueLimit", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match QueueOverflowPolicy::try_from(policy) {
+			Ok(policy) => {
+				c_device.device.rx.set_limit(capacity, policy);
+				ResultCode::OK
+			}
+			Err(policy) => {
+				set_last_error(ResultCode::Illegal, format!("setRxQueueLimit: there is no overflow policy {}", policy));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Limits the count of frames in the TX queue, `0` means unlimited. `policy` is a `QueueOverflowPolicy`, `Illegal` is returned for other values. With the `Reject` policy the stack keeps the data in the socket buffers until the queue is drained.
+#[no_mangle]
+pub extern "C" fn setTxQueueLimit(c_device: CDevicePtr, capacity: usize, policy: u8) -> ResultCode {
+	guard("setTxQueueLimit", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match QueueOverflowPolicy::try_from(policy) {
+			Ok(policy) => {
+				c_device.device.tx.set_limit(capacity, policy);
+				ResultCode::OK
+			}
+			Err(policy) => {
+				set_last_error(ResultCode::Illegal, format!("setTxQueueLimit: there is no overflow policy {}", policy));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Returns the count of frames dropped or rejected because the RX queue was full.
+#[no_mangle]
+pub extern "C" fn getRxQueueDropCount(c_device: CDevicePtr) -> u64 {
+	guard("getRxQueueDropCount", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.device.rx.dropped()
+	})
+}
+
+/// Returns the count of frames dropped or rejected because the TX queue was full.
+#[no_mangle]
+pub extern "C" fn getTxQueueDropCount(c_device: CDevicePtr) -> u64 {
+	guard("getTxQueueDropCount", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.device.tx.dropped()
+	})
+}
+
+#[no_mangle]
+pub extern "C" fn ifacePoll(c_device: CDevicePtr) {
+	guard("ifacePoll", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.poll(Instant::now());
+	})
+}
+
+/// Polls the interface at the moment `millis` (in milliseconds) of the caller's clock instead of the wall clock. The device clock is only moved by the caller, so a simulated clock gives reproducible timing.
+#[no_mangle]
+pub extern "C" fn ifacePollAt(c_device: CDevicePtr, millis: i64) {
+	guard("ifacePollAt", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		c_device.poll(Instant::from_millis(millis));
+	})
+}
+
+/// Returns the moment (in milliseconds of the device clock) at which `ifacePoll`/`ifacePollAt` should be called next, e.g. for a TCP retransmit, a DNS retry or a DHCP renewal. Returns `POLL_NO_DEADLINE` if nothing is scheduled.
+#[no_mangle]
+pub extern "C" fn ifaceGetPollAt(c_device: CDevicePtr) -> i64 {
+	guard_or("ifaceGetPollAt", || POLL_NO_DEADLINE, || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match c_device.poll_at() {
+			Some(instant) => instant.total_millis(),
+			None => POLL_NO_DEADLINE,
+		}
+	})
+}
+
+/// Returns how many milliseconds after the last poll the interface should be polled again. Returns `POLL_NO_DEADLINE` if nothing is scheduled.
+#[no_mangle]
+pub extern "C" fn ifaceGetPollDelay(c_device: CDevicePtr) -> i64 {
+	guard_or("ifaceGetPollDelay", || POLL_NO_DEADLINE, || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		match c_device.poll_delay() {
+			Some(delay) => delay.total_millis() as i64,
+			None => POLL_NO_DEADLINE,
+		}
+	})
 }
-*/ 
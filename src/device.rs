@@ -1,14 +1,252 @@
-use std::collections::VecDeque;
+use core::ffi::c_void;
+use std::collections::vec_deque::{Drain, VecDeque};
+use std::slice;
 
 use std::vec::Vec;
 
+use log::debug;
+
 use smoltcp::phy::{self, Device, DeviceCapabilities, Medium};
 use smoltcp::time::Instant;
 use smoltcp::wire::EthernetFrame;
-use smoltcp::Result;
+use smoltcp::{Error, Result};
+
+use crate::capture::{Capture, CaptureDirection};
+use crate::impairment::{Impairment, MAX_HELD_FRAMES};
+use crate::ring::SpscRing;
+
+/// What a full `SansIOQueue` does with one more frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum QueueOverflowPolicy {
+	/// Drop the oldest queued frame to make room for the new one.
+	DropOldest = 0,
+	/// Drop the new frame.
+	DropNewest = 1,
+	/// Refuse the new frame with `Exhausted`.
+	Reject = 2,
+}
+
+impl TryFrom<u8> for QueueOverflowPolicy {
+	type Error = u8;
+
+	fn try_from(value: u8) -> core::result::Result<Self, u8> {
+		match value {
+			0 => Ok(QueueOverflowPolicy::DropOldest),
+			1 => Ok(QueueOverflowPolicy::DropNewest),
+			2 => Ok(QueueOverflowPolicy::Reject),
+			other => Err(other),
+		}
+	}
+}
+
+/// Called when the stack is done with a buffer lent by the host, so the host can reuse or free it.
+pub type HostBufferReleaseCallback = Option<extern "C" fn(user_data: *mut c_void, buffer: *mut u8)>;
+
+/// Called to get a host buffer of `size` bytes the stack writes a frame to be transmitted into. Returning NULL drops the frame.
+pub type TxSlotAcquireCallback = Option<extern "C" fn(user_data: *mut c_void, size: usize) -> *mut u8>;
+
+/// Called when the frame in a buffer obtained from `TxSlotAcquireCallback` is ready to be transmitted. `size` is 0 if the stack has failed to produce the frame and the buffer can be just reused.
+pub type TxSlotCommitCallback = Option<extern "C" fn(user_data: *mut c_void, buffer: *mut u8, size: usize)>;
+
+/// Called synchronously for each frame transmitted by the stack. The frame is only valid during the call.
+pub type TxCallback = Option<extern "C" fn(user_data: *mut c_void, frame: *const u8, size: usize)>;
+
+/// A release callback together with its user data.
+#[derive(Debug, Clone, Copy)]
+pub struct HostBufferRelease {
+	pub callback: extern "C" fn(user_data: *mut c_void, buffer: *mut u8),
+	pub user_data: *mut c_void,
+}
+
+/// A frame in a buffer lent by the host. The buffer is given back to the host when the frame is dropped.
+#[derive(Debug)]
+pub struct BorrowedFrame {
+	ptr: *mut u8,
+	len: usize,
+	release: HostBufferRelease,
+}
+
+impl BorrowedFrame {
+	/// # Safety
+	/// `ptr` must point to `len` bytes that stay valid and untouched by the host until `release` is called for them.
+	pub unsafe fn new(ptr: *mut u8, len: usize, release: HostBufferRelease) -> Self {
+		BorrowedFrame { ptr, len, release }
+	}
+}
+
+impl Drop for BorrowedFrame {
+	fn drop(&mut self) {
+		(self.release.callback)(self.release.user_data, self.ptr);
+	}
+}
+
+/// A received frame, either copied from the host or lent by it.
+#[derive(Debug)]
+pub enum Frame {
+	Owned(Vec<u8>),
+	Borrowed(BorrowedFrame),
+}
+
+impl Frame {
+	pub fn len(&self) -> usize {
+		match self {
+			Frame::Owned(buffer) => buffer.len(),
+			Frame::Borrowed(borrowed) => borrowed.len,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn as_slice(&self) -> &[u8] {
+		match self {
+			Frame::Owned(buffer) => buffer.as_slice(),
+			Frame::Borrowed(borrowed) => unsafe { slice::from_raw_parts(borrowed.ptr, borrowed.len) },
+		}
+	}
+
+	pub fn as_mut_slice(&mut self) -> &mut [u8] {
+		match self {
+			Frame::Owned(buffer) => buffer.as_mut_slice(),
+			Frame::Borrowed(borrowed) => unsafe { slice::from_raw_parts_mut(borrowed.ptr, borrowed.len) },
+		}
+	}
+}
+
+impl From<Vec<u8>> for Frame {
+	fn from(buffer: Vec<u8>) -> Self {
+		Frame::Owned(buffer)
+	}
+}
+
+/// Where the frames transmitted by the stack go.
+#[derive(Debug, Clone, Copy)]
+pub enum TxSink {
+	/// Into the `tx` queue, to be pulled by the host.
+	Queue,
+	/// Directly into buffers provided by the host.
+	HostSlots {
+		acquire: extern "C" fn(user_data: *mut c_void, size: usize) -> *mut u8,
+		commit: extern "C" fn(user_data: *mut c_void, buffer: *mut u8, size: usize),
+		user_data: *mut c_void,
+	},
+	/// Into a shared memory ring consumed by the host.
+	Ring(SpscRing),
+	/// Pushed to the host through a callback.
+	Callback {
+		callback: extern "C" fn(user_data: *mut c_void, frame: *const u8, size: usize),
+		user_data: *mut c_void,
+	},
+}
+
+/// A queue for "sent" and "received" data. Frames are taken out in the order they were put in.
+#[derive(Debug)]
+pub struct SansIOQueue<T = Vec<u8>> {
+	frames: VecDeque<T>,
+	/// Max count of frames in the queue, `0` means unlimited.
+	capacity: usize,
+	overflow_policy: QueueOverflowPolicy,
+	/// Count of frames dropped (or rejected) because the queue was full.
+	dropped: u64,
+}
+
+impl<T> Default for SansIOQueue<T> {
+	fn default() -> Self {
+		SansIOQueue {
+			frames: VecDeque::new(),
+			capacity: 0,
+			overflow_policy: QueueOverflowPolicy::DropOldest,
+			dropped: 0,
+		}
+	}
+}
+
+impl<T> SansIOQueue<T> {
+	/// Limits the count of frames in the queue, `0` means unlimited. Frames already in the queue are kept even if there are more of them than `capacity`.
+	pub fn set_limit(&mut self, capacity: usize, overflow_policy: QueueOverflowPolicy) {
+		self.capacity = capacity;
+		self.overflow_policy = overflow_policy;
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	pub fn overflow_policy(&self) -> QueueOverflowPolicy {
+		self.overflow_policy
+	}
 
-/// A queue for "sent" and "received" data
-pub type SansIOQueue = VecDeque<Vec<u8>>;
+	pub fn dropped(&self) -> u64 {
+		self.dropped
+	}
+
+	pub fn len(&self) -> usize {
+		self.frames.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.frames.is_empty()
+	}
+
+	pub fn is_full(&self) -> bool {
+		self.capacity != 0 && self.frames.len() >= self.capacity
+	}
+
+	/// Puts a frame to the back of the queue. If the queue is full, it is handled according to the overflow policy: `Error::Dropped` is returned if the frame was dropped, and `Error::Exhausted` if it was rejected.
+	pub fn push(&mut self, frame: T) -> Result<()> {
+		if self.is_full() {
+			match self.overflow_policy {
+				QueueOverflowPolicy::DropOldest => {
+					while self.is_full() {
+						self.frames.pop_front();
+						self.dropped += 1;
+					}
+				}
+				QueueOverflowPolicy::DropNewest => {
+					self.dropped += 1;
+					return Err(Error::Dropped);
+				}
+				QueueOverflowPolicy::Reject => {
+					self.dropped += 1;
+					return Err(Error::Exhausted);
+				}
+			}
+		}
+		self.frames.push_back(frame);
+		Ok(())
+	}
+
+	/// Counts a frame dropped (or rejected) because of the limit of the queue before it got there, e.g. by a full impairment stage.
+	pub fn count_dropped(&mut self) {
+		self.dropped += 1;
+	}
+
+	/// Max count of frames an impairment stage of the direction of the queue holds: as many as the queue does, or `MAX_HELD_FRAMES` if it is unlimited.
+	pub fn stage_limit(&self) -> usize {
+		if self.capacity != 0 {
+			self.capacity
+		} else {
+			MAX_HELD_FRAMES
+		}
+	}
+
+	/// Takes the oldest frame out of the queue.
+	pub fn pop(&mut self) -> Option<T> {
+		self.frames.pop_front()
+	}
+
+	/// The oldest frame in the queue.
+	pub fn front(&self) -> Option<&T> {
+		self.frames.front()
+	}
+
+	/// Takes all the frames out of the queue, oldest first.
+	pub fn drain(&mut self) -> Drain<'_, T> {
+		self.frames.drain(..)
+	}
+}
 
 /// A "device" that just allows to get frames as memory buffers.
 #[derive(Debug)]
@@ -16,7 +254,22 @@ pub struct SansIO {
 	medium: Medium,
 	mtu: usize,
 	pub tx: SansIOQueue,
-	pub rx: SansIOQueue,
+	pub rx: SansIOQueue<Frame>,
+	pub tx_sink: TxSink,
+	/// Reused for the frames pushed to `TxSink::Callback`.
+	tx_scratch: Vec<u8>,
+	/// Gives the buffers of frames put with `put_rx_borrowed` back to the host.
+	pub rx_release: Option<HostBufferRelease>,
+	/// A shared memory ring the host produces received frames into, read after the `rx` queue is empty.
+	pub rx_ring: Option<SpscRing>,
+	/// Where the frames received and transmitted are captured to, if anywhere.
+	pub capture: Option<Capture>,
+	/// The moment of the last poll, set by `CDevice::poll`. Received frames are captured with it.
+	pub timestamp: Instant,
+	/// Damages and delays the received frames on their way from the `rx` queue to the stack, if set. It holds as many frames as the `rx` queue may, or `MAX_HELD_FRAMES` if the queue is unlimited.
+	pub rx_impairment: Option<Impairment>,
+	/// Damages and delays the transmitted frames on their way from the stack to the `tx_sink`, if set. It holds as many frames as the `tx` queue may, or `MAX_HELD_FRAMES` if the queue is unlimited, the frames beyond being handled by the overflow policy of the queue.
+	pub tx_impairment: Option<Impairment>,
 }
 
 impl SansIO {
@@ -32,6 +285,128 @@ impl SansIO {
 			mtu,
 			tx: SansIOQueue::default(),
 			rx: SansIOQueue::default(),
+			tx_sink: TxSink::Queue,
+			tx_scratch: Vec::new(),
+			rx_release: None,
+			rx_ring: None,
+			capture: None,
+			timestamp: Instant::from_millis(0),
+			rx_impairment: None,
+			tx_impairment: None,
+		}
+	}
+
+	pub fn medium(&self) -> Medium {
+		self.medium
+	}
+
+	/// Captures a received frame and puts it into the RX queue. It is captured even if the queue drops or refuses it.
+	pub fn put_rx(&mut self, frame: Frame) -> Result<()> {
+		if let Some(capture) = &mut self.capture {
+			capture.record(self.timestamp, CaptureDirection::Inbound, frame.as_slice());
+		}
+		self.rx.push(frame)
+	}
+
+	/// Puts a frame lent by the host into the RX queue without copying it, capturing it. Fails with `Illegal` if no release callback is set.
+	///
+	/// # Safety
+	/// `ptr` must point to `len` bytes that stay valid and untouched by the host until the release callback is called for them.
+	pub unsafe fn put_rx_borrowed(&mut self, ptr: *mut u8, len: usize) -> Result<()> {
+		match self.rx_release {
+			Some(release) => self.put_rx(Frame::Borrowed(BorrowedFrame::new(ptr, len, release))),
+			None => Err(Error::Illegal),
+		}
+	}
+
+	/// Takes the next frame put by the host, from the `rx` queue and then from the `rx_ring`. The frames of the ring are captured here, as they only reach the library now.
+	fn take_rx(&mut self) -> Option<Frame> {
+		if let Some(frame) = self.rx.pop() {
+			return Some(frame);
+		}
+		let frame = Frame::from(self.rx_ring?.pop()?);
+		if let Some(capture) = &mut self.capture {
+			capture.record(self.timestamp, CaptureDirection::Inbound, frame.as_slice());
+		}
+		Some(frame)
+	}
+
+	/// Hands a frame of `len` bytes, written by `f`, over to the `tx_sink`, capturing it.
+	fn emit<R, F>(&mut self, timestamp: Instant, len: usize, f: F) -> Result<R>
+	where
+		F: FnOnce(&mut [u8]) -> Result<R>,
+	{
+		let capture = &mut self.capture;
+		let f = |buffer: &mut [u8]| {
+			let result = f(buffer);
+			if let (Ok(_), Some(capture)) = (&result, capture) {
+				capture.record(timestamp, CaptureDirection::Outbound, buffer);
+			}
+			result
+		};
+		match self.tx_sink {
+			TxSink::Queue => {
+				let mut buffer = vec![0; len];
+				let result = f(&mut buffer);
+				match self.tx.push(buffer) {
+					// Dropping the frame is what the host has asked for, it is not an error of the stack.
+					Ok(()) | Err(Error::Dropped) => result,
+					Err(err) => Err(err),
+				}
+			}
+			TxSink::HostSlots {
+				acquire,
+				commit,
+				user_data,
+			} => {
+				let ptr = acquire(user_data, len);
+				if ptr.is_null() {
+					return Err(Error::Exhausted);
+				}
+				let result = f(unsafe { slice::from_raw_parts_mut(ptr, len) });
+				commit(user_data, ptr, if result.is_ok() { len } else { 0 });
+				result
+			}
+			TxSink::Ring(ring) => ring.push_with(len, f),
+			TxSink::Callback { callback, user_data } => {
+				let buffer = &mut self.tx_scratch;
+				buffer.clear();
+				buffer.resize(len, 0);
+				let result = f(buffer);
+				if result.is_ok() {
+					callback(user_data, buffer.as_ptr(), len);
+				}
+				result
+			}
+		}
+	}
+
+	/// Hands the transmitted frames whose time has come by `now` over from the `tx_impairment` stage to the `tx_sink`.
+	pub fn release_tx(&mut self, now: Instant) {
+		let mut stage = match self.tx_impairment.take() {
+			Some(stage) => stage,
+			None => return,
+		};
+		while let Some(frame) = stage.pop_ready(now) {
+			let frame = frame.as_slice();
+			let result = self.emit(now, frame.len(), |buffer| {
+				buffer.copy_from_slice(frame);
+				Ok(())
+			});
+			if let Err(err) = result {
+				debug!("cannot transmit a frame released by the impairment stage: {}", err);
+			}
+		}
+		self.tx_impairment = Some(stage);
+	}
+
+	/// When a frame held by an impairment stage is to be released next, if any is held.
+	pub fn next_impairment_release(&self) -> Option<Instant> {
+		let rx = self.rx_impairment.as_ref().and_then(Impairment::next_release);
+		let tx = self.tx_impairment.as_ref().and_then(Impairment::next_release);
+		match (rx, tx) {
+			(Some(rx), Some(tx)) => Some(if rx < tx { rx } else { tx }),
+			(rx, tx) => rx.or(tx),
 		}
 	}
 }
@@ -48,11 +423,25 @@ impl<'a> Device<'a> for SansIO {
 	}
 
 	fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
-		let item = self.rx.pop_back();
-		eprintln!("item {:?}", item);
+		let item = if self.rx_impairment.is_some() {
+			let timestamp = self.timestamp;
+			// The frames beyond what the stage may hold are left where the host put them, so the limits of the queue and of the ring keep applying.
+			let limit = self.rx.stage_limit();
+			while self.rx_impairment.as_ref().is_some_and(|stage| stage.len() < limit) {
+				let frame = match self.take_rx() {
+					Some(frame) => frame,
+					None => break,
+				};
+				if let Some(stage) = &mut self.rx_impairment {
+					stage.push(timestamp, frame);
+				}
+			}
+			self.rx_impairment.as_mut().and_then(|stage| stage.pop_ready(timestamp))
+		} else {
+			self.take_rx()
+		};
 		match item {
 			Some(el) => {
-				eprintln!("el {:?}", el);
 				let rx = RxToken { buffer: el };
 				let tx = TxToken { parent: self };
 				Some((rx, tx))
@@ -65,13 +454,21 @@ impl<'a> Device<'a> for SansIO {
 		// lower: Rc<RefCell<sys::TunTapInterfaceDesc>>,
 		// Some(TxToken {lower: self.lower.clone(),})
 
+		if self.tx.overflow_policy() == QueueOverflowPolicy::Reject {
+			let queue_full = matches!(self.tx_sink, TxSink::Queue) && self.tx.is_full();
+			let stage_full = self.tx_impairment.as_ref().is_some_and(|stage| stage.len() >= self.tx.stage_limit());
+			if queue_full || stage_full {
+				// Leave the data in the socket buffers until the host drains the queue or the stage releases frames.
+				return None;
+			}
+		}
 		Some(TxToken { parent: self })
 	}
 }
 
 #[doc(hidden)]
 pub struct RxToken {
-	buffer: Vec<u8>,
+	buffer: Frame,
 }
 
 impl phy::RxToken for RxToken {
@@ -79,7 +476,7 @@ impl phy::RxToken for RxToken {
 	where
 		F: FnOnce(&mut [u8]) -> Result<R>,
 	{
-		f(&mut self.buffer[..])
+		f(self.buffer.as_mut_slice())
 	}
 }
 
@@ -89,14 +486,32 @@ pub struct TxToken<'a> {
 }
 
 impl<'a> phy::TxToken for TxToken<'a> {
-	fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> Result<R>
+	fn consume<R, F>(self, timestamp: Instant, len: usize, f: F) -> Result<R>
 	where
 		F: FnOnce(&mut [u8]) -> Result<R>,
 	{
 		let parent = self.parent;
-		let mut buffer = vec![0; len];
-		let result = f(&mut buffer);
-		parent.tx.push_back(buffer[..].to_vec());
-		result
+		let limit = parent.tx.stage_limit();
+		match &mut parent.tx_impairment {
+			Some(stage) => {
+				let mut buffer = vec![0; len];
+				let result = f(&mut buffer);
+				if result.is_ok() {
+					// A full stage is handled as the TX queue would be, a rejected frame only gets here if the stage has filled up since `transmit`.
+					if parent.tx.overflow_policy() == QueueOverflowPolicy::DropOldest {
+						while stage.len() >= limit && stage.pop_first().is_some() {
+							parent.tx.count_dropped();
+						}
+					}
+					if stage.len() < limit {
+						stage.push(timestamp, buffer.into());
+					} else {
+						parent.tx.count_dropped();
+					}
+				}
+				result
+			}
+			None => parent.emit(timestamp, len, f),
+		}
 	}
 }
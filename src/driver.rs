@@ -0,0 +1,128 @@
+use std::vec::Vec;
+
+use smoltcp::time::{Duration, Instant};
+
+use crate::cdevice::CDevice;
+
+/// Drives a `CDevice` by a virtual clock instead of the wall clock, so that tests don't depend on real time passing.
+pub struct VirtualClockDriver<'d, 'a> {
+	device: &'d mut CDevice<'a>,
+	now: Instant,
+}
+
+impl<'d, 'a> VirtualClockDriver<'d, 'a> {
+	pub fn new(device: &'d mut CDevice<'a>, start: Instant) -> Self {
+		VirtualClockDriver { device, now: start }
+	}
+
+	/// The current moment of the virtual clock.
+	pub fn now(&self) -> Instant {
+		self.now
+	}
+
+	/// The driven device, e.g. to put RX frames into it or to access its sockets.
+	pub fn device(&mut self) -> &mut CDevice<'a> {
+		self.device
+	}
+
+	/// Moves the virtual clock forward without polling.
+	pub fn advance(&mut self, delta: Duration) {
+		self.now += delta;
+	}
+
+	/// Polls the interface once at the current virtual moment and returns the frames it has transmitted.
+	pub fn poll(&mut self) -> Vec<Vec<u8>> {
+		let mut frames = Vec::new();
+		self.device.poll(self.now);
+		self.drain_tx(&mut frames);
+		frames
+	}
+
+	/// Polls the interface, jumping the virtual clock to each next `poll_at` deadline, until no deadline is pending or `budget` of virtual time is spent. Returns all the frames transmitted meanwhile.
+	pub fn run_until_idle(&mut self, budget: Duration) -> Vec<Vec<u8>> {
+		let deadline = self.now + budget;
+		let mut frames = Vec::new();
+		loop {
+			let readiness_changed = self.device.poll(self.now);
+			let transmitted = self.drain_tx(&mut frames);
+
+			let next = match self.device.poll_at() {
+				Some(next) => next,
+				None => break,
+			};
+
+			let next = if next > self.now {
+				next
+			} else if readiness_changed || transmitted != 0 {
+				self.now
+			} else {
+				// The stack wants to be polled right away, but the last poll has done nothing, so step the clock to avoid spinning.
+				self.now + Duration::from_millis(1)
+			};
+
+			if next > deadline {
+				self.now = deadline;
+				break;
+			}
+			self.now = next;
+		}
+		frames
+	}
+
+	fn drain_tx(&mut self, frames: &mut Vec<Vec<u8>>) -> usize {
+		let count = self.device.device.tx.len();
+		frames.extend(self.device.device.tx.drain());
+		count
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use smoltcp::iface::InterfaceBuilder;
+	use smoltcp::phy::Medium;
+	use smoltcp::socket::tcp;
+	use smoltcp::wire::{IpAddress, IpCidr, IpEndpoint, IpProtocol, Ipv4Address, Ipv4Packet, TcpPacket};
+
+	use super::*;
+	use crate::device::SansIO;
+
+	#[test]
+	fn run_until_idle_jumps_through_retransmit_deadlines() {
+		let mut sans_io = SansIO::new(1500, Medium::Ip);
+		let iface = InterfaceBuilder::new()
+			.ip_addrs(vec![IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24)])
+			.finalize(&mut sans_io);
+		let mut device = CDevice::new(sans_io, iface);
+
+		let socket = tcp::Socket::new(tcp::SocketBuffer::new(vec![0; 1024]), tcp::SocketBuffer::new(vec![0; 1024]));
+		let handle = device.sockets.add(socket);
+		let cx = device.iface.context();
+		device
+			.sockets
+			.get_mut::<tcp::Socket>(handle)
+			.connect(cx, IpEndpoint::new(IpAddress::v4(10, 0, 0, 2), 80), 49152)
+			.unwrap();
+
+		let start = Instant::from_secs(100);
+		let mut driver = VirtualClockDriver::new(&mut device, start);
+		let frames = driver.run_until_idle(Duration::from_secs(10));
+
+		// Nobody answers, so the SYN is retransmitted on each expired deadline, seconds apart in virtual time only.
+		assert!(frames.len() >= 3, "{} frames", frames.len());
+		for frame in &frames {
+			let ip = Ipv4Packet::new_checked(&frame[..]).unwrap();
+			assert_eq!(ip.dst_addr(), Ipv4Address::new(10, 0, 0, 2));
+			assert_eq!(ip.next_header(), IpProtocol::Tcp);
+			let tcp = TcpPacket::new_checked(ip.payload()).unwrap();
+			assert!(tcp.syn() && !tcp.ack());
+		}
+		assert!(driver.now() > start + Duration::from_secs(1));
+		assert!(driver.now() <= start + Duration::from_secs(10));
+
+		// Without polling, advancing only moves the clock.
+		let now = driver.now();
+		driver.advance(Duration::from_millis(5));
+		assert_eq!(driver.now(), now + Duration::from_millis(5));
+		assert!(driver.device().device.tx.is_empty());
+	}
+}
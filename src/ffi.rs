@@ -0,0 +1,222 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use log::debug;
+
+use crate::device::SansIOQueue;
+use crate::result_codes::ResultCode;
+
+/// What an exported function returns if it has panicked or refused its arguments, and whether a value it returns means a failure.
+pub trait FfiReturn {
+	/// The value returned instead of unwinding into the host or of a result.
+	fn fallback() -> Self;
+
+	/// The code to record as the last error, if this value means the call has failed.
+	fn failure(&self) -> Option<ResultCode> {
+		None
+	}
+}
+
+impl FfiReturn for () {
+	fn fallback() -> Self {}
+}
+
+impl FfiReturn for bool {
+	fn fallback() -> Self {
+		false
+	}
+}
+
+macro_rules! ffi_return_zero {
+	($($ty:ty),+) => {
+		$(
+		impl FfiReturn for $ty {
+			fn fallback() -> Self {
+				0
+			}
+		}
+		)+
+	}
+}
+
+ffi_return_zero!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl<T> FfiReturn for *mut T {
+	fn fallback() -> Self {
+		ptr::null_mut()
+	}
+}
+
+impl<T> FfiReturn for *const T {
+	fn fallback() -> Self {
+		ptr::null()
+	}
+}
+
+impl FfiReturn for ResultCode {
+	fn fallback() -> Self {
+		ResultCode::Illegal
+	}
+
+	fn failure(&self) -> Option<ResultCode> {
+		match self {
+			ResultCode::OK => None,
+			other => Some(*other),
+		}
+	}
+}
+
+/// The most recent failure of an exported function on this thread.
+#[derive(Debug, Clone)]
+pub struct LastError {
+	pub code: ResultCode,
+	/// Kept NUL-terminated, so `getLastErrorMessage` can give it out without copying.
+	pub message: CString,
+}
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+	/// Bumped on each recorded failure, so `guard` can tell whether the body has recorded a more detailed one itself.
+	static ERROR_SERIAL: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Records a failure as the last error of the calling thread.
+pub fn set_last_error(code: ResultCode, message: String) {
+	debug!("{}", message);
+	let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+	LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(LastError { code, message }));
+	ERROR_SERIAL.with(|serial| serial.set(serial.get().wrapping_add(1)));
+}
+
+/// The last error of the calling thread, if any.
+pub fn last_error() -> Option<LastError> {
+	LAST_ERROR.with(|last_error| last_error.borrow().clone())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+	if let Some(message) = payload.downcast_ref::<&str>() {
+		message
+	} else if let Some(message) = payload.downcast_ref::<String>() {
+		message
+	} else {
+		"unknown panic"
+	}
+}
+
+/// Runs the body of the exported function `name`, so that a panic in it never unwinds into the host. A panic is turned into `R::fallback()` and recorded as the last error, as is any failure code returned.
+pub fn guard<R: FfiReturn, F: FnOnce() -> R>(name: &str, body: F) -> R {
+	guard_or(name, R::fallback, body)
+}
+
+/// Like `guard`, for the functions whose value on a panic must be other than `R::fallback()`, e.g. because 0 means something.
+pub fn guard_or<R: FfiReturn, D: FnOnce() -> R, F: FnOnce() -> R>(name: &str, fallback: D, body: F) -> R {
+	let serial = ERROR_SERIAL.with(Cell::get);
+	match panic::catch_unwind(AssertUnwindSafe(body)) {
+		Ok(res) => {
+			if let Some(code) = res.failure() {
+				if ERROR_SERIAL.with(Cell::get) == serial {
+					set_last_error(code, format!("{}: {:?}: {}", name, code, code));
+				}
+			}
+			res
+		}
+		Err(payload) => {
+			set_last_error(ResultCode::Illegal, format!("{} panicked: {}", name, panic_message(&*payload)));
+			fallback()
+		}
+	}
+}
+
+/// The `size` bytes at `data`, passed by the host, or `None` if `data` is NULL while `size` is not 0. `data` is not used if `size` is 0, so the host may pass NULL for an empty buffer.
+///
+/// # Safety
+/// Unless NULL, `data` must be valid for reading `size` bytes during `'a`.
+pub unsafe fn host_slice<'a>(data: *const u8, size: usize) -> Option<&'a [u8]> {
+	if size == 0 {
+		Some(&[])
+	} else if data.is_null() {
+		None
+	} else {
+		Some(slice::from_raw_parts(data, size))
+	}
+}
+
+/// Like `host_slice`, for a buffer to write to.
+///
+/// # Safety
+/// Unless NULL, `data` must be valid for writing `size` bytes during `'a`.
+pub unsafe fn host_slice_mut<'a>(data: *mut u8, size: usize) -> Option<&'a mut [u8]> {
+	if size == 0 {
+		Some(&mut [])
+	} else if data.is_null() {
+		None
+	} else {
+		Some(slice::from_raw_parts_mut(data, size))
+	}
+}
+
+/// Copies the oldest frame of `queue` into the `size` bytes at `dst` and removes it from the queue, for the exported function `name`. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is left in the queue and `BufferInsufficient` is returned; if the queue is empty, `Exhausted` is returned.
+///
+/// # Safety
+/// Unless NULL, `dst` must be valid for writing `size` bytes, and `frame_size` for writing a `usize`.
+pub unsafe fn pop_frame_to_host(name: &str, queue: &mut SansIOQueue, dst: *mut u8, size: u32, frame_size: *mut usize) -> ResultCode {
+	let frame_len = queue.front().map_or(0, Vec::len);
+	if let Some(frame_size) = frame_size.as_mut() {
+		*frame_size = frame_len;
+	}
+
+	match queue.front() {
+		None => ResultCode::Exhausted,
+		Some(_) if frame_len > size as usize => {
+			set_last_error(
+				ResultCode::BufferInsufficient,
+				format!("{}: the frame is {} bytes, but the buffer is only {}", name, frame_len, size),
+			);
+			ResultCode::BufferInsufficient
+		}
+		Some(_) => {
+			let dst = host_slice_mut!(name, dst, frame_len);
+			let frame = queue.pop().unwrap();
+			dst.copy_from_slice(&frame);
+			ResultCode::OK
+		}
+	}
+}
+
+/// Returns the code of the last failure of an exported function on the calling thread, `OK` if there was none since `clearLastError`.
+#[no_mangle]
+pub extern "C" fn getLastError() -> ResultCode {
+	match last_error() {
+		Some(last_error) => last_error.code,
+		None => ResultCode::OK,
+	}
+}
+
+/// Forgets the last error of the calling thread.
+#[no_mangle]
+pub extern "C" fn clearLastError() {
+	LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+}
+
+/// Returns the details of the last failure of an exported function on the calling thread, such as the smoltcp error or the pointer that was NULL, or NULL if there was none. The string is owned by the library and stays valid until the next failure on the thread or `clearLastError`.
+#[no_mangle]
+pub extern "C" fn getLastErrorMessage() -> *const c_char {
+	LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+		Some(last_error) => last_error.message.as_ptr(),
+		None => ptr::null(),
+	})
+}
+
+/// Returns a static NUL-terminated description of a `ResultCode` (or of a socket error code, which share the values).
+#[no_mangle]
+pub extern "C" fn resultCodeToString(code: u8) -> *const c_char {
+	let description = match ResultCode::from_u8(code) {
+		Some(code) => code.description_with_nul(),
+		None => "unknown result code\0",
+	};
+	description.as_ptr().cast()
+}
@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+
+use smoltcp::time::{Duration, Instant};
+
+use crate::cdevice::{device_from_opaque_ptr, CDevicePtr};
+use crate::device::Frame;
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+/// Max count of frames an impairment stage holds when the queue of its direction is unlimited.
+pub const MAX_HELD_FRAMES: usize = 1024;
+
+/// Used instead of a zero seed, which would make the generator output only zeros.
+const DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// A xorshift64* generator. Fast, and gives the same sequence for the same seed on every platform, which is all the impairments need.
+#[derive(Debug, Clone)]
+pub struct Rng {
+	state: u64,
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Rng {
+		Rng {
+			state: if seed == 0 { DEFAULT_SEED } else { seed },
+		}
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+	}
+
+	/// A number uniformly distributed in `[0, 1)`.
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// Whether an event of `probability` happens this time.
+	pub fn chance(&mut self, probability: f64) -> bool {
+		probability > 0.0 && self.next_f64() < probability
+	}
+
+	/// A number uniformly distributed in `[0, bound]`.
+	pub fn up_to(&mut self, bound: u64) -> u64 {
+		if bound == 0 {
+			0
+		} else {
+			self.next_u64() % (bound + 1)
+		}
+	}
+}
+
+/// Which way the frames an impairment stage applies to go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CImpairmentDirection {
+	/// The frames put into the device by the host, before the stack gets them.
+	Rx = 0,
+	/// The frames transmitted by the stack, before the host gets them.
+	Tx = 1,
+}
+
+impl TryFrom<u8> for CImpairmentDirection {
+	type Error = u8;
+
+	fn try_from(value: u8) -> Result<Self, u8> {
+		match value {
+			0 => Ok(CImpairmentDirection::Rx),
+			1 => Ok(CImpairmentDirection::Tx),
+			other => Err(other),
+		}
+	}
+}
+
+/// Parses the direction passed by the host, recording the error of `name` for an unknown one.
+fn impairment_direction(name: &str, direction: u8) -> Option<CImpairmentDirection> {
+	match CImpairmentDirection::try_from(direction) {
+		Ok(direction) => Some(direction),
+		Err(direction) => {
+			set_last_error(ResultCode::Illegal, format!("{}: there is no impairment direction {}", name, direction));
+			None
+		}
+	}
+}
+
+/// What an impairment stage does to the frames. All the times are on the clock of the stack, the probabilities are in `[0, 1]`.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CImpairmentConfig {
+	/// Seeds the generator deciding what happens to each frame. The same seed and the same frames at the same moments give the same outcome.
+	pub seed: u64,
+	/// Probability of a frame being lost.
+	pub drop_probability: f64,
+	/// Delay added to every frame, in milliseconds.
+	pub delay_ms: u32,
+	/// Max random delay added to the fixed one, in milliseconds. Frames can be reordered by it.
+	pub jitter_ms: u32,
+	/// Probability of a frame being delivered twice.
+	pub duplicate_probability: f64,
+	/// Probability of a frame being held back by a random extra delay of up to `reorder_window_ms`, so the frames after it overtake it.
+	pub reorder_probability: f64,
+	pub reorder_window_ms: u32,
+	/// Probability of a single random bit of a frame being flipped.
+	pub bit_flip_probability: f64,
+	/// Rate of the link in bits per second, frames wait for the ones before them to be "sent". `0` means unlimited.
+	pub bandwidth_bps: u64,
+}
+
+impl CImpairmentConfig {
+	/// Describes what is wrong with the config, if anything.
+	pub fn check(&self) -> Result<(), String> {
+		let probabilities = [
+			("drop_probability", self.drop_probability),
+			("duplicate_probability", self.duplicate_probability),
+			("reorder_probability", self.reorder_probability),
+			("bit_flip_probability", self.bit_flip_probability),
+		];
+		for (name, probability) in probabilities {
+			if !(0.0..=1.0).contains(&probability) {
+				return Err(format!("{} is {}, which is not in [0, 1]", name, probability));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// What an impairment stage has done so far.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CImpairmentStats {
+	/// Frames that entered the stage.
+	pub frames: u64,
+	pub dropped: u64,
+	pub duplicated: u64,
+	pub reordered: u64,
+	pub corrupted: u64,
+}
+
+/// Holds frames back and damages them according to a `CImpairmentConfig`, releasing them when their time comes.
+#[derive(Debug)]
+pub struct Impairment {
+	config: CImpairmentConfig,
+	rng: Rng,
+	/// The frames held, by their release moment and then by the order they came in.
+	pending: BTreeMap<(Instant, u64), Frame>,
+	next_seq: u64,
+	/// When the link, limited by `bandwidth_bps`, is done "sending" the frames before.
+	link_free_at: Instant,
+	stats: CImpairmentStats,
+}
+
+impl Impairment {
+	pub fn new(config: CImpairmentConfig) -> Impairment {
+		Impairment {
+			config,
+			rng: Rng::new(config.seed),
+			pending: BTreeMap::new(),
+			next_seq: 0,
+			link_free_at: Instant::from_millis(0),
+			stats: CImpairmentStats::default(),
+		}
+	}
+
+	pub fn stats(&self) -> CImpairmentStats {
+		self.stats
+	}
+
+	/// Count of frames held.
+	pub fn len(&self) -> usize {
+		self.pending.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+
+	/// Takes a frame coming at `now`.
+	pub fn push(&mut self, now: Instant, mut frame: Frame) {
+		self.stats.frames += 1;
+		if self.rng.chance(self.config.drop_probability) {
+			self.stats.dropped += 1;
+			return;
+		}
+
+		if !frame.is_empty() && self.rng.chance(self.config.bit_flip_probability) {
+			let bit = self.rng.up_to(frame.len() as u64 * 8 - 1) as usize;
+			frame.as_mut_slice()[bit / 8] ^= 1 << (bit % 8);
+			self.stats.corrupted += 1;
+		}
+
+		let mut release = now;
+		if let Some(micros) = (frame.len() as u64 * 8).saturating_mul(1_000_000).checked_div(self.config.bandwidth_bps) {
+			let start = if self.link_free_at > now { self.link_free_at } else { now };
+			self.link_free_at = start + Duration::from_micros(micros);
+			release = self.link_free_at;
+		}
+		release += Duration::from_millis(u64::from(self.config.delay_ms) + self.rng.up_to(u64::from(self.config.jitter_ms)));
+		if self.rng.chance(self.config.reorder_probability) {
+			release += Duration::from_millis(1 + self.rng.up_to(u64::from(self.config.reorder_window_ms)));
+			self.stats.reordered += 1;
+		}
+
+		if self.rng.chance(self.config.duplicate_probability) {
+			self.stats.duplicated += 1;
+			self.hold(release, Frame::Owned(frame.as_slice().to_vec()));
+		}
+		self.hold(release, frame);
+	}
+
+	fn hold(&mut self, release: Instant, frame: Frame) {
+		self.pending.insert((release, self.next_seq), frame);
+		self.next_seq += 1;
+	}
+
+	/// Takes out the frame to be released first, if its time has come by `now`.
+	pub fn pop_ready(&mut self, now: Instant) -> Option<Frame> {
+		let key = *self.pending.keys().next()?;
+		if key.0 > now {
+			return None;
+		}
+		self.pending.remove(&key)
+	}
+
+	/// Takes out the frame to be released first, whether its time has come or not.
+	pub fn pop_first(&mut self) -> Option<Frame> {
+		self.pending.pop_first().map(|(_, frame)| frame)
+	}
+
+	/// When the next frame is to be released, if any is held.
+	pub fn next_release(&self) -> Option<Instant> {
+		self.pending.keys().next().map(|&(release, _)| release)
+	}
+}
+
+/// Puts an impairment stage with `config` on the way of the frames going in `direction`, a `CImpairmentDirection`, replacing the one there, if any. Passing NULL removes the stage. The frames held by a replaced or removed stage are dropped. Returns `Illegal` if the direction or the config is invalid.
+#[no_mangle]
+pub extern "C" fn setImpairment(
+	c_device: CDevicePtr,
+	direction: u8,
+	config: *const CImpairmentConfig,
+) -> ResultCode {
+	guard("setImpairment", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let direction = match impairment_direction("setImpairment", direction) {
+			Some(direction) => direction,
+			None => return ResultCode::Illegal,
+		};
+		let stage = match unsafe { config.as_ref() } {
+			Some(config) => {
+				if let Err(err) = config.check() {
+					set_last_error(ResultCode::Illegal, format!("setImpairment: {}", err));
+					return ResultCode::Illegal;
+				}
+				Some(Impairment::new(*config))
+			}
+			None => None,
+		};
+		match direction {
+			CImpairmentDirection::Rx => c_device.device.rx_impairment = stage,
+			CImpairmentDirection::Tx => c_device.device.tx_impairment = stage,
+		}
+		ResultCode::OK
+	})
+}
+
+/// Writes what the impairment stage of `direction`, a `CImpairmentDirection`, has done so far into `stats`. Returns `Illegal` if the direction is invalid or there is no stage there.
+#[no_mangle]
+pub extern "C" fn getImpairmentStats(
+	c_device: CDevicePtr,
+	direction: u8,
+	stats: *mut CImpairmentStats,
+) -> ResultCode {
+	guard("getImpairmentStats", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let direction = match impairment_direction("getImpairmentStats", direction) {
+			Some(direction) => direction,
+			None => return ResultCode::Illegal,
+		};
+		let stage = match direction {
+			CImpairmentDirection::Rx => c_device.device.rx_impairment.as_ref(),
+			CImpairmentDirection::Tx => c_device.device.tx_impairment.as_ref(),
+		};
+		match (stage, unsafe { stats.as_mut() }) {
+			(Some(stage), Some(stats)) => {
+				*stats = stage.stats();
+				ResultCode::OK
+			}
+			(None, _) => {
+				set_last_error(ResultCode::Illegal, format!("getImpairmentStats: no {:?} impairment stage is set", direction));
+				ResultCode::Illegal
+			}
+			(_, None) => {
+				set_last_error(ResultCode::Illegal, "getImpairmentStats: got NULL stats pointer".to_string());
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::device::{QueueOverflowPolicy, SansIO};
+	use smoltcp::phy::{Device, Medium, TxToken};
+
+	/// Runs 200 numbered frames through a stage, one per millisecond, and returns them as released, with the moment of their release.
+	fn run(config: CImpairmentConfig) -> (Vec<(i64, Vec<u8>)>, CImpairmentStats) {
+		let mut stage = Impairment::new(config);
+		let mut released = Vec::new();
+		for millis in 0..1000 {
+			let now = Instant::from_millis(millis);
+			if millis < 200 {
+				stage.push(now, Frame::from(vec![millis as u8; 16]));
+			}
+			while let Some(frame) = stage.pop_ready(now) {
+				released.push((millis, frame.as_slice().to_vec()));
+			}
+		}
+		assert!(stage.is_empty());
+		(released, stage.stats())
+	}
+
+	fn everything(seed: u64) -> CImpairmentConfig {
+		CImpairmentConfig {
+			seed,
+			drop_probability: 0.1,
+			delay_ms: 5,
+			jitter_ms: 3,
+			duplicate_probability: 0.1,
+			reorder_probability: 0.1,
+			reorder_window_ms: 20,
+			bit_flip_probability: 0.1,
+			bandwidth_bps: 0,
+		}
+	}
+
+	#[test]
+	fn the_same_seed_gives_the_same_outcome() {
+		let (first, first_stats) = run(everything(42));
+		let (second, second_stats) = run(everything(42));
+		assert_eq!(first, second);
+		assert_eq!(format!("{:?}", first_stats), format!("{:?}", second_stats));
+		for count in [first_stats.dropped, first_stats.duplicated, first_stats.reordered, first_stats.corrupted] {
+			assert!(count > 0 && count < 60, "{:?}", first_stats);
+		}
+		assert_eq!(first.len() as u64, first_stats.frames - first_stats.dropped + first_stats.duplicated);
+
+		let (other, _) = run(everything(43));
+		assert_ne!(first, other);
+	}
+
+	#[test]
+	fn a_zero_seed_still_gives_random_numbers() {
+		let mut rng = Rng::new(0);
+		let numbers: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+		assert!(numbers.iter().all(|&number| number != 0));
+		assert_eq!(numbers, (0..4).scan(Rng::new(DEFAULT_SEED), |rng, _| Some(rng.next_u64())).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn delay_and_bandwidth_hold_the_frames_back_in_order() {
+		let (released, _) = run(CImpairmentConfig {
+			delay_ms: 10,
+			..CImpairmentConfig::default()
+		});
+		let expected: Vec<(i64, Vec<u8>)> = (0..200).map(|millis| (millis + 10, vec![millis as u8; 16])).collect();
+		assert_eq!(released, expected);
+
+		// 16 bytes take 2 ms at 64 kbit/s, so the link is busy from the second frame on.
+		let (released, _) = run(CImpairmentConfig {
+			bandwidth_bps: 64_000,
+			..CImpairmentConfig::default()
+		});
+		let moments: Vec<i64> = released.iter().map(|(millis, _)| *millis).collect();
+		assert_eq!(&moments[..4], &[2, 4, 6, 8]);
+		assert_eq!(moments.last(), Some(&400));
+	}
+
+	#[test]
+	fn invalid_probabilities_are_refused() {
+		assert!(everything(1).check().is_ok());
+		for probability in [-0.1, 1.5, f64::NAN] {
+			assert!(CImpairmentConfig {
+				drop_probability: probability,
+				..CImpairmentConfig::default()
+			}
+			.check()
+			.is_err());
+		}
+	}
+
+	#[test]
+	fn the_rx_stage_holds_no_more_than_the_queue_would() {
+		let mut device = SansIO::new(1500, Medium::Ip);
+		device.rx_impairment = Some(Impairment::new(CImpairmentConfig {
+			delay_ms: 10,
+			..CImpairmentConfig::default()
+		}));
+		for _ in 0..MAX_HELD_FRAMES + 10 {
+			device.put_rx(Frame::from(vec![0x45; 20])).unwrap();
+		}
+		assert!(device.receive().is_none());
+		assert_eq!(device.rx_impairment.as_ref().map(Impairment::len), Some(MAX_HELD_FRAMES));
+		assert_eq!(device.rx.len(), 10);
+
+		device.rx.set_limit(4, QueueOverflowPolicy::DropOldest);
+		device.rx_impairment = Some(Impairment::new(CImpairmentConfig {
+			delay_ms: 10,
+			..CImpairmentConfig::default()
+		}));
+		assert!(device.receive().is_none());
+		assert_eq!(device.rx_impairment.as_ref().map(Impairment::len), Some(4));
+		assert_eq!(device.rx.len(), 6);
+	}
+
+	#[test]
+	fn the_tx_stage_holds_no_more_than_the_queue_would() {
+		let held = |device: &SansIO| device.tx_impairment.as_ref().map(Impairment::len);
+		let transmit = |device: &mut SansIO, byte: u8| match device.transmit() {
+			Some(token) => {
+				token
+					.consume(Instant::from_millis(0), 20, |buffer| {
+						buffer.fill(byte);
+						Ok(())
+					})
+					.unwrap();
+				true
+			}
+			None => false,
+		};
+		let delayed = || {
+			Some(Impairment::new(CImpairmentConfig {
+				delay_ms: 10,
+				..CImpairmentConfig::default()
+			}))
+		};
+
+		let mut device = SansIO::new(1500, Medium::Ip);
+		device.tx_impairment = delayed();
+		for byte in 0..MAX_HELD_FRAMES + 10 {
+			assert!(transmit(&mut device, byte as u8));
+		}
+		assert_eq!(held(&device), Some(MAX_HELD_FRAMES));
+		assert_eq!(device.tx.dropped(), 10);
+
+		device.tx.set_limit(4, QueueOverflowPolicy::DropOldest);
+		device.tx_impairment = delayed();
+		for byte in 0..6 {
+			assert!(transmit(&mut device, byte));
+		}
+		assert_eq!(held(&device), Some(4));
+		assert_eq!(device.tx.dropped(), 12);
+		let stage = device.tx_impairment.as_mut().unwrap();
+		let kept: Vec<u8> = std::iter::from_fn(|| stage.pop_ready(Instant::from_millis(10))).map(|frame| frame.as_slice()[0]).collect();
+		assert_eq!(kept, vec![2, 3, 4, 5]);
+
+		device.tx.set_limit(4, QueueOverflowPolicy::DropNewest);
+		for byte in 0..6 {
+			assert!(transmit(&mut device, byte));
+		}
+		assert_eq!(held(&device), Some(4));
+		assert_eq!(device.tx.dropped(), 14);
+
+		// The stack keeps the data while the stage is full.
+		device.tx.set_limit(4, QueueOverflowPolicy::Reject);
+		assert!(!transmit(&mut device, 6));
+		assert_eq!(held(&device), Some(4));
+		assert!(device.tx.is_empty());
+	}
+}
@@ -1,38 +1,21 @@
 #[macro_use]
 mod macros;
 
+pub mod abi;
 pub mod address;
 pub mod builder;
+pub mod capture;
 pub mod device;
+pub mod driver;
+pub mod ffi;
+pub mod impairment;
+pub mod link;
+pub mod logging;
+pub mod nat;
 pub mod cdevice;
 pub mod result_codes;
+pub mod replay;
+pub mod router;
+pub mod ring;
 pub mod socket;
-
-use log::debug;
-use log::{LevelFilter, Metadata, Record};
-
-struct SimpleLogger;
-
-impl log::Log for SimpleLogger {
-	fn enabled(&self, _metadata: &Metadata) -> bool {
-		true
-	}
-
-	fn log(&self, record: &Record) {
-		if self.enabled(record.metadata()) {
-			eprintln!("sio_smoltcp: {} - {}", record.level(), record.args());
-		}
-	}
-
-	fn flush(&self) {}
-}
-
-static LOGGER: SimpleLogger = SimpleLogger;
-
-#[no_mangle]
-pub extern "C" fn initLogging() {
-	eprintln!("sio_smoltcp: Trying to setup logging...");
-	log::set_logger(&LOGGER).unwrap();
-	log::set_max_level(LevelFilter::Trace);
-	debug!("logging set up");
-}
+pub mod switch;
@@ -0,0 +1,242 @@
+use std::ptr;
+
+use smoltcp::phy::Medium;
+use smoltcp::time::Instant;
+
+use crate::cdevice::{device_from_opaque_ptr, CDevice, CDevicePtr};
+use crate::device::TxSink;
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+/// Max count of rounds of polling and passing frames on in one tick of a link, a switch or a router, so devices bouncing frames off each other cannot hang it.
+pub const MAX_ROUNDS_PER_TICK: usize = 64;
+
+/// How the frames of a device attached to a link get to the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CLinkMode {
+	/// A cable between exactly two devices.
+	PointToPoint = 0,
+	/// A hub or a bus: every frame goes to every other device.
+	Shared = 1,
+}
+
+impl TryFrom<u8> for CLinkMode {
+	type Error = u8;
+
+	fn try_from(value: u8) -> Result<Self, u8> {
+		match value {
+			0 => Ok(CLinkMode::PointToPoint),
+			1 => Ok(CLinkMode::Shared),
+			other => Err(other),
+		}
+	}
+}
+
+/// Checks that a device can be attached to an in-process network, which takes its frames from its TX queue, and counts the attachment. While it is counted, the TX sink of the device cannot be changed.
+pub fn attach_device(c_device: &mut CDevice, medium: Option<Medium>) -> Result<(), String> {
+	if !matches!(c_device.device.tx_sink, TxSink::Queue) {
+		return Err("the device transmits into a host sink instead of its TX queue".to_string());
+	}
+	if let Some(medium) = medium {
+		if c_device.device.medium() != medium {
+			return Err(format!("the medium of the device is {:?}, but {:?} is expected", c_device.device.medium(), medium));
+		}
+	}
+	c_device.attachments += 1;
+	Ok(())
+}
+
+/// Undoes `attach_device`.
+pub fn detach_device(c_device: &mut CDevice) {
+	c_device.attachments = c_device.attachments.saturating_sub(1);
+}
+
+/// Gets the link, switch or router behind a pointer returned to C.
+///
+/// # Safety
+/// `ptr` must be NULL, which panics, or point to a live `T`.
+pub unsafe fn network_from_opaque_ptr<'b, T>(ptr: *mut T) -> &'b mut T {
+	if ptr.is_null() {
+		let name = std::any::type_name::<T>().rsplit("::").next().unwrap_or_default();
+		panic!("Fatal error, got NULL `{}` pointer", name);
+	}
+	&mut *ptr
+}
+
+/// Joins devices of the same medium within the process, passing the frames transmitted by each to the RX queues of the others.
+#[derive(Debug)]
+pub struct Link {
+	mode: CLinkMode,
+	devices: Vec<CDevicePtr>,
+	/// Count of frames put into the RX queues of the devices.
+	delivered: u64,
+	/// Count of frames refused by the RX queues of the devices.
+	dropped: u64,
+}
+
+impl Link {
+	pub fn new(mode: CLinkMode) -> Link {
+		Link {
+			mode,
+			devices: Vec::new(),
+			delivered: 0,
+			dropped: 0,
+		}
+	}
+
+	/// Attaches a device, which must transmit into its TX queue and stay alive until it is detached.
+	///
+	/// # Safety
+	/// `device` must point to a live `CDevice`.
+	pub unsafe fn attach(&mut self, device: CDevicePtr) -> Result<(), String> {
+		if self.devices.contains(&device) {
+			return Err("the device is already attached".to_string());
+		}
+		if self.mode == CLinkMode::PointToPoint && self.devices.len() >= 2 {
+			return Err("a point-to-point link has both ends attached".to_string());
+		}
+		let medium = self.devices.first().map(|&first| device_from_opaque_ptr(first).device.medium());
+		attach_device(device_from_opaque_ptr(device), medium)?;
+		self.devices.push(device);
+		Ok(())
+	}
+
+	/// Detaches a device. Returns whether it was attached.
+	///
+	/// # Safety
+	/// `device` must point to a live `CDevice`.
+	pub unsafe fn detach(&mut self, device: CDevicePtr) -> bool {
+		match self.devices.iter().position(|&attached| ptr::eq(attached, device)) {
+			Some(index) => {
+				self.devices.remove(index);
+				detach_device(device_from_opaque_ptr(device));
+				true
+			}
+			None => false,
+		}
+	}
+
+	pub fn delivered(&self) -> u64 {
+		self.delivered
+	}
+
+	pub fn dropped(&self) -> u64 {
+		self.dropped
+	}
+
+	/// Polls every device at `timestamp` and passes the frames they have transmitted on, over and over until no frame is left in flight, so an exchange whose steps don't wait for timers is done in one tick. Returns the count of frames delivered.
+	pub fn tick(&mut self, timestamp: Instant) -> u64 {
+		let delivered_before = self.delivered;
+		for _ in 0..MAX_ROUNDS_PER_TICK {
+			let mut frames = Vec::new();
+			for (index, &device) in self.devices.iter().enumerate() {
+				let c_device = unsafe { device_from_opaque_ptr(device) };
+				c_device.poll(timestamp);
+				frames.extend(c_device.device.tx.drain().map(|frame| (index, frame)));
+			}
+			if frames.is_empty() {
+				break;
+			}
+			for (from, frame) in frames {
+				for (index, &device) in self.devices.iter().enumerate() {
+					if index == from {
+						continue;
+					}
+					let c_device = unsafe { device_from_opaque_ptr(device) };
+					match c_device.device.put_rx(frame.clone().into()) {
+						Ok(()) => self.delivered += 1,
+						Err(_) => self.dropped += 1,
+					}
+				}
+			}
+		}
+		self.delivered - delivered_before
+	}
+}
+
+impl Drop for Link {
+	fn drop(&mut self) {
+		for &device in &self.devices {
+			detach_device(unsafe { device_from_opaque_ptr(device) });
+		}
+	}
+}
+
+pub type CLinkPtr = *mut Link;
+
+/// Creates a link joining no devices yet. `mode` is a `CLinkMode`, NULL is returned for other values.
+#[no_mangle]
+pub extern "C" fn newLink(mode: u8) -> CLinkPtr {
+	guard("newLink", || match CLinkMode::try_from(mode) {
+		Ok(mode) => Box::into_raw(Box::new(Link::new(mode))),
+		Err(mode) => {
+			set_last_error(ResultCode::Illegal, format!("newLink: there is no link mode {}", mode));
+			ptr::null_mut()
+		}
+	})
+}
+
+/// Frees the link, detaching the devices from it.
+#[no_mangle]
+pub extern "C" fn deleteLink(c_link: CLinkPtr) {
+	guard("deleteLink", || unsafe { drop(Box::from_raw(network_from_opaque_ptr(c_link))) })
+}
+
+/// Attaches the device to the link. The device must transmit into its TX queue (no TX callback, slot provider or ring) and have the medium of the devices attached before. It cannot be freed or given another TX sink until detached. Returns `Illegal` otherwise, or if both ends of a point-to-point link are taken.
+#[no_mangle]
+pub extern "C" fn linkAttach(c_link: CLinkPtr, c_device: CDevicePtr) -> ResultCode {
+	guard("linkAttach", || {
+		let link = unsafe { network_from_opaque_ptr(c_link) };
+		unsafe { device_from_opaque_ptr(c_device) };
+		match unsafe { link.attach(c_device) } {
+			Ok(()) => ResultCode::OK,
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("linkAttach: {}", err));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Detaches the device from the link. Returns `Illegal` if it is not attached.
+#[no_mangle]
+pub extern "C" fn linkDetach(c_link: CLinkPtr, c_device: CDevicePtr) -> ResultCode {
+	guard("linkDetach", || {
+		let link = unsafe { network_from_opaque_ptr(c_link) };
+		unsafe { device_from_opaque_ptr(c_device) };
+		if unsafe { link.detach(c_device) } {
+			ResultCode::OK
+		} else {
+			set_last_error(ResultCode::Illegal, "linkDetach: the device is not attached to the link".to_string());
+			ResultCode::Illegal
+		}
+	})
+}
+
+/// Polls the devices of the link at the moment `millis` of the caller's clock and passes the frames they transmit on to each other, see `Link::tick`. Returns the count of frames delivered.
+#[no_mangle]
+pub extern "C" fn linkTick(c_link: CLinkPtr, millis: i64) -> u64 {
+	guard("linkTick", || {
+		let link = unsafe { network_from_opaque_ptr(c_link) };
+		link.tick(Instant::from_millis(millis))
+	})
+}
+
+/// Returns the count of frames delivered to the devices of the link since it was created.
+#[no_mangle]
+pub extern "C" fn linkGetDeliveredCount(c_link: CLinkPtr) -> u64 {
+	guard("linkGetDeliveredCount", || {
+		let link = unsafe { network_from_opaque_ptr(c_link) };
+		link.delivered()
+	})
+}
+
+/// Returns the count of frames the RX queues of the devices have refused, because they were full.
+#[no_mangle]
+pub extern "C" fn linkGetDropCount(c_link: CLinkPtr) -> u64 {
+	guard("linkGetDropCount", || {
+		let link = unsafe { network_from_opaque_ptr(c_link) };
+		link.dropped()
+	})
+}
@@ -0,0 +1,175 @@
+use std::ffi::{c_char, c_void, CString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+use log::{debug, LevelFilter, Log, Metadata, Record};
+
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+/// Called for each log record. `target` and `message` are NUL-terminated and only valid during the call. Can be called from any thread calling into the library.
+pub type LogCallback = Option<extern "C" fn(user_data: *mut c_void, level: CLogLevel, target: *const c_char, message: *const c_char)>;
+
+/// Level of a log record, or of the filter of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CLogLevel {
+	/// Only used as a filter, to log nothing.
+	Off = 0,
+	Error = 1,
+	Warn = 2,
+	Info = 3,
+	Debug = 4,
+	Trace = 5,
+}
+
+impl From<log::Level> for CLogLevel {
+	fn from(level: log::Level) -> Self {
+		match level {
+			log::Level::Error => CLogLevel::Error,
+			log::Level::Warn => CLogLevel::Warn,
+			log::Level::Info => CLogLevel::Info,
+			log::Level::Debug => CLogLevel::Debug,
+			log::Level::Trace => CLogLevel::Trace,
+		}
+	}
+}
+
+impl TryFrom<u8> for CLogLevel {
+	type Error = u8;
+
+	fn try_from(value: u8) -> Result<Self, u8> {
+		match value {
+			0 => Ok(CLogLevel::Off),
+			1 => Ok(CLogLevel::Error),
+			2 => Ok(CLogLevel::Warn),
+			3 => Ok(CLogLevel::Info),
+			4 => Ok(CLogLevel::Debug),
+			5 => Ok(CLogLevel::Trace),
+			other => Err(other),
+		}
+	}
+}
+
+impl From<CLogLevel> for LevelFilter {
+	fn from(level: CLogLevel) -> Self {
+		match level {
+			CLogLevel::Off => LevelFilter::Off,
+			CLogLevel::Error => LevelFilter::Error,
+			CLogLevel::Warn => LevelFilter::Warn,
+			CLogLevel::Info => LevelFilter::Info,
+			CLogLevel::Debug => LevelFilter::Debug,
+			CLogLevel::Trace => LevelFilter::Trace,
+		}
+	}
+}
+
+/// A log callback together with its user data.
+#[derive(Debug, Clone, Copy)]
+struct HostLogSink {
+	callback: extern "C" fn(user_data: *mut c_void, level: CLogLevel, target: *const c_char, message: *const c_char),
+	user_data: *mut c_void,
+}
+
+// The host is responsible for its callback being callable from the threads it calls the library from.
+unsafe impl Send for HostLogSink {}
+unsafe impl Sync for HostLogSink {}
+
+/// Passes the records to the host callback if there is one, prints them to stderr otherwise.
+struct HostLogger {
+	sink: RwLock<Option<HostLogSink>>,
+}
+
+impl Log for HostLogger {
+	fn enabled(&self, _metadata: &Metadata) -> bool {
+		true
+	}
+
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		let sink = match self.sink.read() {
+			Ok(sink) => *sink,
+			Err(poisoned) => *poisoned.into_inner(),
+		};
+		match sink {
+			Some(sink) => {
+				let target = CString::new(record.target().replace('\0', "")).unwrap_or_default();
+				let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+				(sink.callback)(sink.user_data, record.level().into(), target.as_ptr(), message.as_ptr());
+			}
+			None => {
+				eprintln!("sio_smoltcp: {} - {}", record.level(), record.args());
+			}
+		}
+	}
+
+	fn flush(&self) {}
+}
+
+static LOGGER: HostLogger = HostLogger { sink: RwLock::new(None) };
+
+/// Whether installing the logger succeeded, or why it failed, so later calls report the same outcome.
+static INIT_LOGGING: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Set once the host has chosen a level, so setting up logging doesn't override it.
+static LEVEL_SET: AtomicBool = AtomicBool::new(false);
+
+/// Installs the logger of the library as the one of the `log` crate, unless it is already installed. Fails with `Illegal`, on this call and all the later ones, if another logger is installed in the process.
+fn install_logger() -> ResultCode {
+	let outcome = INIT_LOGGING.get_or_init(|| match log::set_logger(&LOGGER) {
+		Ok(()) => {
+			if !LEVEL_SET.load(Ordering::Relaxed) {
+				log::set_max_level(LevelFilter::Trace);
+			}
+			debug!("logging set up");
+			Ok(())
+		}
+		Err(err) => Err(format!("cannot set up logging: {}", err)),
+	});
+	match outcome {
+		Ok(()) => ResultCode::OK,
+		Err(err) => {
+			set_last_error(ResultCode::Illegal, err.clone());
+			ResultCode::Illegal
+		}
+	}
+}
+
+/// Sets up logging to stderr, or to the callback set with `setLogCallback`, at the `Trace` level unless another one was set with `setLogLevel`. Calling it again does nothing.
+#[no_mangle]
+pub extern "C" fn initLogging() {
+	guard("initLogging", || {
+		install_logger();
+	})
+}
+
+/// Routes the log records to `callback` instead of stderr, setting up logging if it is not yet. Passing NULL routes them back to stderr.
+#[no_mangle]
+pub extern "C" fn setLogCallback(callback: LogCallback, user_data: *mut c_void) -> ResultCode {
+	guard("setLogCallback", || {
+		let sink = callback.map(|callback| HostLogSink { callback, user_data });
+		match LOGGER.sink.write() {
+			Ok(mut slot) => *slot = sink,
+			Err(poisoned) => *poisoned.into_inner() = sink,
+		}
+		install_logger()
+	})
+}
+
+/// Sets the level of the least severe records logged. Records below it are not even formatted. `level` is a `CLogLevel`, `Illegal` is returned for other values.
+#[no_mangle]
+pub extern "C" fn setLogLevel(level: u8) -> ResultCode {
+	guard("setLogLevel", || match CLogLevel::try_from(level) {
+		Ok(level) => {
+			LEVEL_SET.store(true, Ordering::Relaxed);
+			log::set_max_level(level.into());
+			ResultCode::OK
+		}
+		Err(level) => {
+			set_last_error(ResultCode::Illegal, format!("setLogLevel: there is no log level {}", level));
+			ResultCode::Illegal
+		}
+	})
+}
@@ -15,8 +15,25 @@ macro_rules! error_code_enum {
 			OK = (ResultCode::OK as u8),
 			$(
 			$( #[$variant_attr] )*
-			$variant
+			$variant = (ResultCode::$variant as u8)
 			),*,
+			/// The call was refused, e.g. because of a NULL pointer, or has panicked.
+			Illegal = (ResultCode::Illegal as u8),
+			/// The buffer provided by the caller is too small.
+			BufferInsufficient = (ResultCode::BufferInsufficient as u8),
+		}
+
+		impl $crate::ffi::FfiReturn for $name {
+			fn fallback() -> Self {
+				$name::Illegal
+			}
+
+			fn failure(&self) -> Option<ResultCode> {
+				match self {
+					$name::OK => None,
+					other => Some((*other).into()),
+				}
+			}
 		}
 
 		impl ::core::convert::From<$ty> for $name {
@@ -27,11 +44,15 @@ macro_rules! error_code_enum {
 			}
 		}
 
-		impl ::core::convert::From<$name> for Result<(), $ty> {
-			fn from(value: $name) -> Self {
+		/// Fails with the code itself if it has no counterpart in the library error type.
+		impl ::core::convert::TryFrom<$name> for Result<(), $ty> {
+			type Error = $name;
+
+			fn try_from(value: $name) -> Result<Self, $name> {
 				match value {
-					$( $name::$variant => Err(<$ty>::$variant) ),*,
-					$name::OK => Ok(())
+					$( $name::$variant => Ok(Err(<$ty>::$variant)) ),*,
+					$name::OK => Ok(Ok(())),
+					other => Err(other),
 				}
 			}
 		}
@@ -60,3 +81,55 @@ macro_rules! error_code_enum {
 		}*/
 	}
 }
+
+/// Gets the `SocketHandle` behind a socket handle pointer of the given kind, or makes the enclosing `guard`ed function body return its fallback value (recording why) if the pointer is not a live handle of that kind on the device.
+macro_rules! checked_handle {
+	($c_device:expr, $c_handle:expr, $kind:expr) => {
+		match $crate::socket::socket::checked_handle($c_device, $c_handle, $kind) {
+			Some(handle) => handle,
+			None => return $crate::ffi::FfiReturn::fallback(),
+		}
+	};
+}
+
+/// Gets the device and the `SocketHandle` behind a socket handle pointer of the given kind, or makes the enclosing `guard`ed function body return its fallback value (recording why) if the pointer is not a live handle of that kind.
+macro_rules! checked_socket {
+	($c_handle:expr, $kind:expr) => {
+		match unsafe { $crate::socket::socket::checked_socket($c_handle, $kind) } {
+			Some(socket) => socket,
+			None => return $crate::ffi::FfiReturn::fallback(),
+		}
+	};
+}
+
+/// Gets the `size` bytes of a buffer passed by the host as a slice, or makes the enclosing `guard`ed function body return its fallback value (recording why) if the buffer is NULL while `size` is not 0.
+macro_rules! host_slice {
+	($fn_name:expr, $data:expr, $size:expr) => {
+		match unsafe { $crate::ffi::host_slice($data, $size as usize) } {
+			Some(slice) => slice,
+			None => {
+				$crate::ffi::set_last_error(
+					$crate::result_codes::ResultCode::Illegal,
+					format!("{}: got NULL `{}` for {} bytes", $fn_name, stringify!($data), $size),
+				);
+				return $crate::ffi::FfiReturn::fallback();
+			}
+		}
+	};
+}
+
+/// Like `host_slice!`, for a buffer to write to.
+macro_rules! host_slice_mut {
+	($fn_name:expr, $data:expr, $size:expr) => {
+		match unsafe { $crate::ffi::host_slice_mut($data, $size as usize) } {
+			Some(slice) => slice,
+			None => {
+				$crate::ffi::set_last_error(
+					$crate::result_codes::ResultCode::Illegal,
+					format!("{}: got NULL `{}` for {} bytes", $fn_name, stringify!($data), $size),
+				);
+				return $crate::ffi::FfiReturn::fallback();
+			}
+		}
+	};
+}
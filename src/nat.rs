@@ -0,0 +1,735 @@
+use std::collections::BTreeMap;
+
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{Icmpv4Packet, IpAddress, IpProtocol, Ipv4Address, Ipv4Packet};
+
+use crate::address::CAddress;
+use crate::ffi::{guard, set_last_error};
+use crate::link::network_from_opaque_ptr;
+use crate::result_codes::ResultCode;
+use crate::router::CRouterPtr;
+
+/// The defaults of the timeouts, after RFC 5382 for TCP, RFC 4787 for UDP and RFC 5508 for ICMP.
+const DEFAULT_TCP_ESTABLISHED_TIMEOUT: Duration = Duration::from_secs(7440);
+const DEFAULT_TCP_TRANSITORY_TIMEOUT: Duration = Duration::from_secs(240);
+const DEFAULT_UDP_TIMEOUT: Duration = Duration::from_secs(120);
+const DEFAULT_ICMP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default range of the ports and ICMP identifiers given to the connections on the outside.
+const DEFAULT_PORT_MIN: u16 = 1024;
+const DEFAULT_PORT_MAX: u16 = 65535;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DST_UNREACHABLE: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMP_PARAM_PROBLEM: u8 = 12;
+
+/// How a NAT treats the connections. A field left 0 gets its default.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CNatConfig {
+	/// How long an established TCP connection is kept without packets, in milliseconds. 7440 seconds by default.
+	pub tcp_established_ms: u32,
+	/// How long a TCP connection being opened or closed is kept without packets, in milliseconds. 240 seconds by default.
+	pub tcp_transitory_ms: u32,
+	/// 120 seconds by default.
+	pub udp_ms: u32,
+	/// For the echo requests, 60 seconds by default.
+	pub icmp_ms: u32,
+	/// The range of the ports and ICMP identifiers on the outside, 1024 to 65535 by default. A port of the inside is kept if it is in the range and free.
+	pub port_min: u16,
+	pub port_max: u16,
+}
+
+impl CNatConfig {
+	/// Describes what is wrong with the config, if anything.
+	pub fn check(&self) -> Result<(), String> {
+		if self.port_range().0 > self.port_range().1 {
+			return Err(format!("port_min {} is above port_max {}", self.port_range().0, self.port_range().1));
+		}
+		Ok(())
+	}
+
+	fn port_range(&self) -> (u16, u16) {
+		(
+			if self.port_min == 0 { DEFAULT_PORT_MIN } else { self.port_min },
+			if self.port_max == 0 { DEFAULT_PORT_MAX } else { self.port_max },
+		)
+	}
+
+	fn timeout(ms: u32, default: Duration) -> Duration {
+		if ms == 0 {
+			default
+		} else {
+			Duration::from_millis(u64::from(ms))
+		}
+	}
+}
+
+/// What a NAT has done so far.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CNatStats {
+	/// Connections in the table now.
+	pub connections: u64,
+	pub created: u64,
+	pub expired: u64,
+	/// Packets translated on their way out.
+	pub outbound: u64,
+	/// Packets translated on their way in.
+	pub inbound: u64,
+	/// Packets that could not be translated on their way out: of other protocols, fragments but the first ones, truncated, or finding no free port.
+	pub dropped: u64,
+	/// Packets coming in through the outside port to an address other than the ones of the router, answering none of the connections, dropped.
+	pub unsolicited: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TcpState {
+	Opening,
+	Established,
+	Closing,
+}
+
+/// A connection from the inside, seen from the outside as coming from the public address and `outside_port`.
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+	inside_addr: Ipv4Address,
+	inside_port: u16,
+	/// Only set for TCP.
+	tcp: Option<TcpState>,
+	expires_at: Instant,
+}
+
+/// Identifies a connection on the inside: the protocol, the inside address and port, the remote address and port. ICMP echoes have the identifier as the inside port and 0 as the remote one.
+type InsideKey = (IpProtocol, Ipv4Address, u16, Ipv4Address, u16);
+
+/// Identifies a connection on the outside: the protocol, the outside port, the remote address and port.
+type OutsideKey = (IpProtocol, u16, Ipv4Address, u16);
+
+/// A checksum covering a field being changed: its offset, and whether 0 means there is none, as for UDP.
+#[derive(Debug, Clone, Copy)]
+struct Checksum {
+	at: usize,
+	optional: bool,
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+	u16::from_be_bytes([buf[at], buf[at + 1]])
+}
+
+/// Updates `checksum` for the 16-bit aligned `old` bytes replaced with `new`, see RFC 1624.
+fn adjust_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+	let mut sum = u32::from(!checksum);
+	for (old, new) in old.chunks(2).zip(new.chunks(2)) {
+		sum += u32::from(!u16::from_be_bytes([old[0], old.get(1).copied().unwrap_or(0)]));
+		sum += u32::from(u16::from_be_bytes([new[0], new.get(1).copied().unwrap_or(0)]));
+	}
+	while sum >> 16 != 0 {
+		sum = (sum & 0xffff) + (sum >> 16);
+	}
+	!(sum as u16)
+}
+
+/// Replaces the bytes at `at` with `new`, updating the `checksums` covering them. The checksums not within `buf` are left alone, they are in the truncated part of a quoted packet.
+fn patch(buf: &mut [u8], at: usize, new: &[u8], checksums: &[Checksum]) {
+	let old = buf[at..at + new.len()].to_vec();
+	buf[at..at + new.len()].copy_from_slice(new);
+	for checksum in checksums {
+		if checksum.at + 2 > buf.len() {
+			continue;
+		}
+		let value = read_u16(buf, checksum.at);
+		if checksum.optional && value == 0 {
+			continue;
+		}
+		let mut adjusted = adjust_checksum(value, &old, new);
+		if checksum.optional && adjusted == 0 {
+			adjusted = 0xffff;
+		}
+		buf[checksum.at..checksum.at + 2].copy_from_slice(&adjusted.to_be_bytes());
+	}
+}
+
+/// Where the ports of a transport header are, and the checksum covering them and the addresses of the pseudo header, if the checksum has one.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+	src_port_at: usize,
+	dst_port_at: usize,
+	checksum: Checksum,
+	pseudo_header: bool,
+}
+
+/// The layout of the transport header of `protocol` starting at `at`, `None` for the protocols the NAT doesn't translate. The ICMP identifier is both the source and the destination "port".
+fn layout(protocol: IpProtocol, at: usize) -> Option<Layout> {
+	match protocol {
+		IpProtocol::Tcp => Some(Layout {
+			src_port_at: at,
+			dst_port_at: at + 2,
+			checksum: Checksum { at: at + 16, optional: false },
+			pseudo_header: true,
+		}),
+		IpProtocol::Udp => Some(Layout {
+			src_port_at: at,
+			dst_port_at: at + 2,
+			checksum: Checksum { at: at + 6, optional: true },
+			pseudo_header: true,
+		}),
+		IpProtocol::Icmp => Some(Layout {
+			src_port_at: at + 4,
+			dst_port_at: at + 4,
+			checksum: Checksum { at: at + 2, optional: false },
+			pseudo_header: false,
+		}),
+		_ => None,
+	}
+}
+
+fn is_icmp_error(tp: u8) -> bool {
+	matches!(tp, ICMP_DST_UNREACHABLE | ICMP_TIME_EXCEEDED | ICMP_PARAM_PROBLEM)
+}
+
+/// Source NAT of IPv4, masquerading the connections going out of the outside port of a router as coming from its public address. Only the packets answering these connections get in, translated back, the other ones coming in through the outside port are only accepted by the router itself. TCP, UDP and ICMP echoes are translated, as are the ICMP errors about them, together with the packets they quote.
+#[derive(Debug)]
+pub struct Nat {
+	outside_port: usize,
+	public_addr: Ipv4Address,
+	config: CNatConfig,
+	inside: BTreeMap<InsideKey, u16>,
+	outside: BTreeMap<OutsideKey, Connection>,
+	/// Where to look for a free port next.
+	next_port: u16,
+	stats: CNatStats,
+}
+
+impl Nat {
+	pub fn new(outside_port: usize, public_addr: Ipv4Address, config: CNatConfig) -> Nat {
+		Nat {
+			outside_port,
+			public_addr,
+			config,
+			inside: BTreeMap::new(),
+			outside: BTreeMap::new(),
+			next_port: config.port_range().0,
+			stats: CNatStats::default(),
+		}
+	}
+
+	/// The index of the router port the connections go out of.
+	pub fn outside_port(&self) -> usize {
+		self.outside_port
+	}
+
+	pub fn public_addr(&self) -> Ipv4Address {
+		self.public_addr
+	}
+
+	pub fn stats(&self) -> CNatStats {
+		CNatStats {
+			connections: self.outside.len() as u64,
+			..self.stats
+		}
+	}
+
+	fn timeout(&self, protocol: IpProtocol, tcp: Option<TcpState>) -> Duration {
+		match (protocol, tcp) {
+			(IpProtocol::Tcp, Some(TcpState::Established)) => {
+				CNatConfig::timeout(self.config.tcp_established_ms, DEFAULT_TCP_ESTABLISHED_TIMEOUT)
+			}
+			(IpProtocol::Tcp, _) => CNatConfig::timeout(self.config.tcp_transitory_ms, DEFAULT_TCP_TRANSITORY_TIMEOUT),
+			(IpProtocol::Udp, _) => CNatConfig::timeout(self.config.udp_ms, DEFAULT_UDP_TIMEOUT),
+			_ => CNatConfig::timeout(self.config.icmp_ms, DEFAULT_ICMP_TIMEOUT),
+		}
+	}
+
+	/// Counts a packet coming in through the outside port that was dropped, as it answers no connection and is not for the router.
+	pub fn refuse_unsolicited(&mut self) {
+		self.stats.unsolicited += 1;
+	}
+
+	/// Forgets the connections that have timed out by `now`.
+	pub fn expire(&mut self, now: Instant) {
+		let before = self.outside.len();
+		let inside = &mut self.inside;
+		self.outside.retain(|&(protocol, _, remote_addr, remote_port), connection| {
+			let alive = connection.expires_at > now;
+			if !alive {
+				inside.remove(&(protocol, connection.inside_addr, connection.inside_port, remote_addr, remote_port));
+			}
+			alive
+		});
+		self.stats.expired += (before - self.outside.len()) as u64;
+	}
+
+	/// The outside port of the connection, creating it if there is none yet. `None` if all the ports for the remote end are taken.
+	fn map(&mut self, key: InsideKey, now: Instant) -> Option<u16> {
+		if let Some(&port) = self.inside.get(&key) {
+			return Some(port);
+		}
+		let (protocol, inside_addr, inside_port, remote_addr, remote_port) = key;
+		let (port_min, port_max) = self.config.port_range();
+		let is_free = |outside: &BTreeMap<OutsideKey, Connection>, port: u16| !outside.contains_key(&(protocol, port, remote_addr, remote_port));
+
+		let port = if (port_min..=port_max).contains(&inside_port) && is_free(&self.outside, inside_port) {
+			inside_port
+		} else {
+			let count = u32::from(port_max - port_min) + 1;
+			let mut found = None;
+			for _ in 0..count {
+				let candidate = self.next_port;
+				self.next_port = if candidate >= port_max { port_min } else { candidate + 1 };
+				if is_free(&self.outside, candidate) {
+					found = Some(candidate);
+					break;
+				}
+			}
+			found?
+		};
+
+		let tcp = if protocol == IpProtocol::Tcp { Some(TcpState::Opening) } else { None };
+		self.inside.insert(key, port);
+		self.outside.insert(
+			(protocol, port, remote_addr, remote_port),
+			Connection {
+				inside_addr,
+				inside_port,
+				tcp,
+				expires_at: now + self.timeout(protocol, tcp),
+			},
+		);
+		self.stats.created += 1;
+		Some(port)
+	}
+
+	/// Follows the state of a TCP connection through the flags of a packet of it, and pushes its expiry back.
+	fn refresh(&mut self, key: OutsideKey, tcp_flags: Option<u8>, inbound: bool, now: Instant) {
+		let timeout = match self.outside.get_mut(&key) {
+			Some(connection) => {
+				if let (Some(state), Some(flags)) = (connection.tcp.as_mut(), tcp_flags) {
+					if flags & (TCP_FIN | TCP_RST) != 0 {
+						*state = TcpState::Closing;
+					} else if inbound && *state == TcpState::Opening && flags & (TCP_SYN | TCP_ACK) == TCP_SYN | TCP_ACK {
+						*state = TcpState::Established;
+					}
+				}
+				connection.tcp
+			}
+			None => return,
+		};
+		let timeout = self.timeout(key.0, timeout);
+		if let Some(connection) = self.outside.get_mut(&key) {
+			connection.expires_at = now + timeout;
+		}
+	}
+
+	/// Translates a packet going out of the outside port: its source becomes the public address and a port of it. Returns `false` if the packet cannot be translated and must be dropped.
+	pub fn translate_outbound(&mut self, packet: &mut [u8], now: Instant) -> bool {
+		let translated = self.translate_outbound_inner(packet, now);
+		if translated {
+			Ipv4Packet::new_unchecked(&mut packet[..]).fill_checksum();
+			self.stats.outbound += 1;
+		} else {
+			self.stats.dropped += 1;
+		}
+		translated
+	}
+
+	fn translate_outbound_inner(&mut self, packet: &mut [u8], now: Instant) -> bool {
+		let ipv4 = Ipv4Packet::new_unchecked(&*packet);
+		let (src, dst, protocol) = (ipv4.src_addr(), ipv4.dst_addr(), ipv4.next_header());
+		let at = usize::from(ipv4.header_len());
+		if ipv4.frag_offset() != 0 {
+			return false;
+		}
+		let layout = match layout(protocol, at) {
+			Some(layout) if layout.checksum.at + 2 <= packet.len() && layout.dst_port_at + 2 <= packet.len() => layout,
+			_ => return false,
+		};
+		let public_addr = self.public_addr;
+
+		let (local_port, remote_port) = match protocol {
+			IpProtocol::Icmp => match packet[at] {
+				ICMP_ECHO_REQUEST => (read_u16(packet, layout.src_port_at), 0),
+				tp if is_icmp_error(tp) => return self.translate_outbound_error(packet, at),
+				_ => return false,
+			},
+			_ => (read_u16(packet, layout.src_port_at), read_u16(packet, layout.dst_port_at)),
+		};
+		let outside_port = match self.map((protocol, src, local_port, dst, remote_port), now) {
+			Some(port) => port,
+			None => return false,
+		};
+		let tcp_flags = if protocol == IpProtocol::Tcp { Some(packet[at + 13]) } else { None };
+
+		let address_checksums: &[Checksum] = if layout.pseudo_header { &[layout.checksum] } else { &[] };
+		patch(packet, 12, public_addr.as_bytes(), address_checksums);
+		patch(packet, layout.src_port_at, &outside_port.to_be_bytes(), &[layout.checksum]);
+		self.refresh((protocol, outside_port, dst, remote_port), tcp_flags, false, now);
+		true
+	}
+
+	/// Translates an ICMP error sent from the inside about a packet that came in translated, so the packet it quotes looks like the one that was sent to the public address.
+	fn translate_outbound_error(&mut self, packet: &mut [u8], at: usize) -> bool {
+		let quoted_at = at + 8;
+		// The quoted packet may be truncated, only its header must be whole.
+		if packet.len() < quoted_at + 20 || packet.len() < quoted_at + usize::from(Ipv4Packet::new_unchecked(&packet[quoted_at..]).header_len()) {
+			return false;
+		}
+		let quoted = Ipv4Packet::new_unchecked(&packet[quoted_at..]);
+		let (quoted_src, quoted_dst, protocol) = (quoted.src_addr(), quoted.dst_addr(), quoted.next_header());
+		let transport_at = quoted_at + usize::from(quoted.header_len());
+		let layout = match layout(protocol, transport_at) {
+			Some(layout) if layout.dst_port_at + 2 <= packet.len() => layout,
+			_ => return false,
+		};
+		let (remote_port, local_port) = match protocol {
+			IpProtocol::Icmp => (0, read_u16(packet, layout.dst_port_at)),
+			_ => (read_u16(packet, layout.src_port_at), read_u16(packet, layout.dst_port_at)),
+		};
+		let outside_port = match self.inside.get(&(protocol, quoted_dst, local_port, quoted_src, remote_port)) {
+			Some(&port) => port,
+			None => return false,
+		};
+		let public_addr = self.public_addr;
+
+		let address_checksums: &[Checksum] = if layout.pseudo_header { &[layout.checksum] } else { &[] };
+		patch(packet, quoted_at + 16, public_addr.as_bytes(), address_checksums);
+		patch(packet, layout.dst_port_at, &outside_port.to_be_bytes(), &[layout.checksum]);
+		Ipv4Packet::new_unchecked(&mut packet[quoted_at..]).fill_checksum();
+		patch(packet, 12, public_addr.as_bytes(), &[]);
+		Icmpv4Packet::new_unchecked(&mut packet[at..]).fill_checksum();
+		true
+	}
+
+	/// Translates a packet that came in through the outside port to the public address: its destination becomes the inside end of its connection. Returns `false` if it belongs to no connection, then it is for the router itself.
+	pub fn translate_inbound(&mut self, packet: &mut [u8], now: Instant) -> bool {
+		let translated = self.translate_inbound_inner(packet, now);
+		if translated {
+			Ipv4Packet::new_unchecked(&mut packet[..]).fill_checksum();
+			self.stats.inbound += 1;
+		}
+		translated
+	}
+
+	fn translate_inbound_inner(&mut self, packet: &mut [u8], now: Instant) -> bool {
+		let ipv4 = Ipv4Packet::new_unchecked(&*packet);
+		let (src, protocol) = (ipv4.src_addr(), ipv4.next_header());
+		let at = usize::from(ipv4.header_len());
+		if ipv4.frag_offset() != 0 {
+			return false;
+		}
+		let layout = match layout(protocol, at) {
+			Some(layout) if layout.checksum.at + 2 <= packet.len() && layout.dst_port_at + 2 <= packet.len() => layout,
+			_ => return false,
+		};
+
+		let (local_port, remote_port) = match protocol {
+			IpProtocol::Icmp => match packet[at] {
+				ICMP_ECHO_REPLY => (read_u16(packet, layout.dst_port_at), 0),
+				tp if is_icmp_error(tp) => return self.translate_inbound_error(packet, at),
+				_ => return false,
+			},
+			_ => (read_u16(packet, layout.dst_port_at), read_u16(packet, layout.src_port_at)),
+		};
+		let key = (protocol, local_port, src, remote_port);
+		let connection = match self.outside.get(&key) {
+			Some(&connection) => connection,
+			None => return false,
+		};
+		let tcp_flags = if protocol == IpProtocol::Tcp { Some(packet[at + 13]) } else { None };
+
+		let address_checksums: &[Checksum] = if layout.pseudo_header { &[layout.checksum] } else { &[] };
+		patch(packet, 16, connection.inside_addr.as_bytes(), address_checksums);
+		patch(packet, layout.dst_port_at, &connection.inside_port.to_be_bytes(), &[layout.checksum]);
+		self.refresh(key, tcp_flags, true, now);
+		true
+	}
+
+	/// Translates an ICMP error sent to the public address about a packet that went out translated, so it reaches the inside end with the packet it quotes looking like the one it sent.
+	fn translate_inbound_error(&mut self, packet: &mut [u8], at: usize) -> bool {
+		let quoted_at = at + 8;
+		if packet.len() < quoted_at + 20 || packet.len() < quoted_at + usize::from(Ipv4Packet::new_unchecked(&packet[quoted_at..]).header_len()) {
+			return false;
+		}
+		let quoted = Ipv4Packet::new_unchecked(&packet[quoted_at..]);
+		let (quoted_src, quoted_dst, protocol) = (quoted.src_addr(), quoted.dst_addr(), quoted.next_header());
+		if quoted_src != self.public_addr {
+			return false;
+		}
+		let transport_at = quoted_at + usize::from(quoted.header_len());
+		let layout = match layout(protocol, transport_at) {
+			Some(layout) if layout.dst_port_at + 2 <= packet.len() => layout,
+			_ => return false,
+		};
+		let (local_port, remote_port) = match protocol {
+			IpProtocol::Icmp => (read_u16(packet, layout.src_port_at), 0),
+			_ => (read_u16(packet, layout.src_port_at), read_u16(packet, layout.dst_port_at)),
+		};
+		let connection = match self.outside.get(&(protocol, local_port, quoted_dst, remote_port)) {
+			Some(&connection) => connection,
+			None => return false,
+		};
+
+		let address_checksums: &[Checksum] = if layout.pseudo_header { &[layout.checksum] } else { &[] };
+		patch(packet, quoted_at + 12, connection.inside_addr.as_bytes(), address_checksums);
+		patch(packet, layout.src_port_at, &connection.inside_port.to_be_bytes(), &[layout.checksum]);
+		Ipv4Packet::new_unchecked(&mut packet[quoted_at..]).fill_checksum();
+		patch(packet, 16, connection.inside_addr.as_bytes(), &[]);
+		Icmpv4Packet::new_unchecked(&mut packet[at..]).fill_checksum();
+		true
+	}
+}
+
+/// Masquerades the IPv4 connections going out of the port `outside_port` of the router as coming from `public_addr`, which must be an IPv4 address of that port, replacing the NAT set before, if any. `config` can be NULL for the defaults. Returns `Illegal` if the port, the address or the config is invalid.
+#[no_mangle]
+pub extern "C" fn routerSetNat(c_router: CRouterPtr, outside_port: usize, public_addr: CAddress, config: *const CNatConfig) -> ResultCode {
+	guard("routerSetNat", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		let config = unsafe { config.as_ref() }.copied().unwrap_or_default();
+		if let Err(err) = config.check() {
+			set_last_error(ResultCode::Illegal, format!("routerSetNat: {}", err));
+			return ResultCode::Illegal;
+		}
+		let public_addr = match IpAddress::from(public_addr) {
+			IpAddress::Ipv4(addr) => addr,
+			addr => {
+				set_last_error(ResultCode::Illegal, format!("routerSetNat: the public address {} is not IPv4", addr));
+				return ResultCode::Illegal;
+			}
+		};
+		match router.set_nat(Some(Nat::new(outside_port, public_addr, config))) {
+			Ok(()) => ResultCode::OK,
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("routerSetNat: {}", err));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Stops translating, forgetting all the connections.
+#[no_mangle]
+pub extern "C" fn routerRemoveNat(c_router: CRouterPtr) {
+	guard("routerRemoveNat", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		// Removing cannot fail.
+		let _ = router.set_nat(None);
+	})
+}
+
+/// Writes what the NAT of the router has done so far into `stats`. Returns `Illegal` if the router has no NAT.
+#[no_mangle]
+pub extern "C" fn routerGetNatStats(c_router: CRouterPtr, stats: *mut CNatStats) -> ResultCode {
+	guard("routerGetNatStats", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		match (router.nat(), unsafe { stats.as_mut() }) {
+			(Some(nat), Some(stats)) => {
+				*stats = nat.stats();
+				ResultCode::OK
+			}
+			(None, _) => {
+				set_last_error(ResultCode::Illegal, "routerGetNatStats: the router has no NAT".to_string());
+				ResultCode::Illegal
+			}
+			(_, None) => {
+				set_last_error(ResultCode::Illegal, "routerGetNatStats: got NULL stats pointer".to_string());
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const INSIDE: Ipv4Address = Ipv4Address([10, 0, 0, 2]);
+	const PUBLIC: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+	const REMOTE: Ipv4Address = Ipv4Address([198, 51, 100, 7]);
+
+	/// The one's complement sum of `data`, as 16-bit big-endian words.
+	fn sum(data: &[u8]) -> u32 {
+		data.chunks(2).map(|word| u32::from(u16::from_be_bytes([word[0], word.get(1).copied().unwrap_or(0)]))).sum()
+	}
+
+	fn fold(mut sum: u32) -> u16 {
+		while sum >> 16 != 0 {
+			sum = (sum & 0xffff) + (sum >> 16);
+		}
+		sum as u16
+	}
+
+	/// Whether the checksum of the transport header of an IPv4 packet is right, pseudo header included for TCP and UDP.
+	fn transport_checksum_ok(packet: &[u8]) -> bool {
+		let ipv4 = Ipv4Packet::new_unchecked(packet);
+		let at = usize::from(ipv4.header_len());
+		let segment = &packet[at..usize::from(ipv4.total_len())];
+		let pseudo_header = match ipv4.next_header() {
+			IpProtocol::Icmp => 0,
+			protocol => sum(&packet[12..20]) + u32::from(u8::from(protocol)) + segment.len() as u32,
+		};
+		fold(pseudo_header + sum(segment)) == 0xffff
+	}
+
+	fn ipv4_packet(src: Ipv4Address, dst: Ipv4Address, protocol: IpProtocol, payload: &[u8]) -> Vec<u8> {
+		let mut packet = vec![0; 20];
+		packet[0] = 0x45;
+		packet[2..4].copy_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+		packet[8] = 64;
+		packet[9] = protocol.into();
+		packet[12..16].copy_from_slice(src.as_bytes());
+		packet[16..20].copy_from_slice(dst.as_bytes());
+		packet.extend_from_slice(payload);
+		Ipv4Packet::new_unchecked(&mut packet[..]).fill_checksum();
+		packet
+	}
+
+	/// Sets the checksum of the transport header at `checksum_at`, from the start of the IPv4 packet.
+	fn fill_transport_checksum(packet: &mut [u8], checksum_at: usize) {
+		packet[checksum_at..checksum_at + 2].copy_from_slice(&[0, 0]);
+		let ipv4 = Ipv4Packet::new_unchecked(&*packet);
+		let at = usize::from(ipv4.header_len());
+		let segment = &packet[at..];
+		let pseudo_header = match ipv4.next_header() {
+			IpProtocol::Icmp => 0,
+			protocol => sum(&packet[12..20]) + u32::from(u8::from(protocol)) + segment.len() as u32,
+		};
+		let checksum = !fold(pseudo_header + sum(segment));
+		packet[checksum_at..checksum_at + 2].copy_from_slice(&checksum.to_be_bytes());
+	}
+
+	fn udp_packet(src: Ipv4Address, src_port: u16, dst: Ipv4Address, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+		let mut udp = Vec::new();
+		udp.extend_from_slice(&src_port.to_be_bytes());
+		udp.extend_from_slice(&dst_port.to_be_bytes());
+		udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+		udp.extend_from_slice(&[0, 0]);
+		udp.extend_from_slice(payload);
+		let mut packet = ipv4_packet(src, dst, IpProtocol::Udp, &udp);
+		fill_transport_checksum(&mut packet, 26);
+		packet
+	}
+
+	fn addresses_and_ports(packet: &[u8]) -> (Ipv4Address, u16, Ipv4Address, u16) {
+		let ipv4 = Ipv4Packet::new_unchecked(packet);
+		(ipv4.src_addr(), read_u16(packet, 20), ipv4.dst_addr(), read_u16(packet, 22))
+	}
+
+	#[test]
+	fn adjusted_checksums_match_recomputed_ones() {
+		let mut data: Vec<u8> = (0..40u8).map(|i| i.wrapping_mul(37).wrapping_add(11)).collect();
+		for (at, new) in [(4, &[0xff, 0xff][..]), (12, &[192, 0, 2, 1]), (30, &[0, 0, 0x80, 0x01])] {
+			let checksum = !fold(sum(&data));
+			let old = data[at..at + new.len()].to_vec();
+			data[at..at + new.len()].copy_from_slice(new);
+			assert_eq!(adjust_checksum(checksum, &old, new), !fold(sum(&data)));
+		}
+	}
+
+	#[test]
+	fn udp_is_masqueraded_and_the_answers_get_back() {
+		let mut nat = Nat::new(1, PUBLIC, CNatConfig::default());
+		let now = Instant::from_millis(0);
+
+		let mut request = udp_packet(INSIDE, 5000, REMOTE, 53, b"query");
+		assert!(nat.translate_outbound(&mut request, now));
+		assert_eq!(addresses_and_ports(&request), (PUBLIC, 5000, REMOTE, 53));
+		assert!(Ipv4Packet::new_unchecked(&request[..]).verify_checksum());
+		assert!(transport_checksum_ok(&request));
+
+		let mut answer = udp_packet(REMOTE, 53, PUBLIC, 5000, b"answer!");
+		assert!(nat.translate_inbound(&mut answer, now));
+		assert_eq!(addresses_and_ports(&answer), (REMOTE, 53, INSIDE, 5000));
+		assert!(Ipv4Packet::new_unchecked(&answer[..]).verify_checksum());
+		assert!(transport_checksum_ok(&answer));
+
+		let mut stray = udp_packet(REMOTE, 54, PUBLIC, 5000, b"stray");
+		assert!(!nat.translate_inbound(&mut stray, now));
+	}
+
+	#[test]
+	fn ports_out_of_the_range_are_remapped_and_udp_without_checksum_keeps_none() {
+		let config = CNatConfig {
+			port_min: 40000,
+			port_max: 40001,
+			..CNatConfig::default()
+		};
+		let mut nat = Nat::new(1, PUBLIC, config);
+		let now = Instant::from_millis(0);
+
+		let mut first = udp_packet(INSIDE, 5000, REMOTE, 53, b"query");
+		first[26..28].copy_from_slice(&[0, 0]);
+		assert!(nat.translate_outbound(&mut first, now));
+		assert_eq!(addresses_and_ports(&first), (PUBLIC, 40000, REMOTE, 53));
+		assert_eq!(read_u16(&first, 26), 0);
+
+		let mut second = udp_packet(Ipv4Address([10, 0, 0, 3]), 5000, REMOTE, 53, b"query");
+		assert!(nat.translate_outbound(&mut second, now));
+		assert_eq!(addresses_and_ports(&second).1, 40001);
+		assert!(transport_checksum_ok(&second));
+
+		let mut third = udp_packet(Ipv4Address([10, 0, 0, 4]), 5000, REMOTE, 53, b"query");
+		assert!(!nat.translate_outbound(&mut third, now));
+		assert_eq!(nat.stats().dropped, 1);
+	}
+
+	#[test]
+	fn idle_connections_expire() {
+		let config = CNatConfig {
+			udp_ms: 1000,
+			..CNatConfig::default()
+		};
+		let mut nat = Nat::new(1, PUBLIC, config);
+		let mut request = udp_packet(INSIDE, 5000, REMOTE, 53, b"query");
+		assert!(nat.translate_outbound(&mut request, Instant::from_millis(0)));
+
+		nat.expire(Instant::from_millis(999));
+		assert_eq!(nat.stats().connections, 1);
+		nat.expire(Instant::from_millis(1000));
+		assert_eq!(nat.stats().connections, 0);
+		assert_eq!(nat.stats().expired, 1);
+		let mut answer = udp_packet(REMOTE, 53, PUBLIC, 5000, b"answer");
+		assert!(!nat.translate_inbound(&mut answer, Instant::from_millis(1000)));
+	}
+
+	#[test]
+	fn icmp_errors_translate_the_packets_they_quote() {
+		let mut nat = Nat::new(1, PUBLIC, CNatConfig::default());
+		let now = Instant::from_millis(0);
+		let mut request = udp_packet(INSIDE, 5000, REMOTE, 53, b"query");
+		let sent = request.clone();
+		assert!(nat.translate_outbound(&mut request, now));
+
+		// Port unreachable, quoting the packet as it went out.
+		let mut icmp = vec![ICMP_DST_UNREACHABLE, 3, 0, 0, 0, 0, 0, 0];
+		icmp.extend_from_slice(&request);
+		let mut error = ipv4_packet(REMOTE, PUBLIC, IpProtocol::Icmp, &icmp);
+		fill_transport_checksum(&mut error, 22);
+		assert!(nat.translate_inbound(&mut error, now));
+		assert_eq!(Ipv4Packet::new_unchecked(&error[..]).dst_addr(), INSIDE);
+		assert_eq!(&error[28..], &sent[..]);
+		assert!(transport_checksum_ok(&error));
+	}
+
+	#[test]
+	fn truncated_icmp_errors_are_refused() {
+		let mut nat = Nat::new(1, PUBLIC, CNatConfig::default());
+		let now = Instant::from_millis(0);
+		// From an ICMP header cut before its end to a quoted header cut before its addresses.
+		for icmp_len in [6, 7, 8, 12, 18, 27] {
+			let mut icmp = vec![ICMP_DST_UNREACHABLE, 3, 0, 0, 0, 0, 0, 0];
+			icmp.resize(icmp_len.max(8), 0x45);
+			icmp.truncate(icmp_len);
+			let error = ipv4_packet(INSIDE, REMOTE, IpProtocol::Icmp, &icmp);
+			assert!(!nat.translate_outbound(&mut error.clone(), now));
+			let error = ipv4_packet(REMOTE, PUBLIC, IpProtocol::Icmp, &icmp);
+			assert!(!nat.translate_inbound(&mut error.clone(), now));
+		}
+	}
+}
@@ -0,0 +1,565 @@
+use std::ffi::{c_char, CStr};
+use std::fmt;
+use std::fs;
+use std::io;
+
+use smoltcp::phy::Medium;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetFrame, HardwareAddress, Ipv4Packet, Ipv6Packet};
+
+use crate::capture::{self, LINKTYPE_ETHERNET, LINKTYPE_RAW};
+use crate::cdevice::{device_from_opaque_ptr, CDevice, CDevicePtr};
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+const PCAPNG_BLOCK_TYPE_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BLOCK_TYPE_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const PCAPNG_BLOCK_TYPE_SIMPLE_PACKET: u32 = 0x0000_0003;
+const PCAPNG_BLOCK_TYPE_ENHANCED_PACKET: u32 = 0x0000_0006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_OPTION_END: u16 = 0;
+const PCAPNG_OPTION_IF_TSRESOL: u16 = 9;
+
+/// `LINKTYPE_IPV4` and `LINKTYPE_IPV6`, which carry raw IP packets like `LINKTYPE_RAW`.
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_IPV6: u16 = 229;
+
+/// Why a capture cannot be read.
+#[derive(Debug)]
+pub enum ReplayError {
+	Io(io::Error),
+	/// Neither a pcap nor a pcapng file.
+	UnknownFormat,
+	/// The file is cut short or its structure is broken.
+	Malformed(&'static str),
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ReplayError::Io(err) => write!(f, "{}", err),
+			ReplayError::UnknownFormat => write!(f, "not a pcap or pcapng file"),
+			ReplayError::Malformed(what) => write!(f, "malformed capture: {}", what),
+		}
+	}
+}
+
+impl From<io::Error> for ReplayError {
+	fn from(err: io::Error) -> Self {
+		ReplayError::Io(err)
+	}
+}
+
+/// A frame read from a capture.
+#[derive(Debug, Clone)]
+pub struct ReplayFrame {
+	/// Microseconds since the epoch of the capture, `None` for the pcapng simple packet blocks, which have no timestamp.
+	pub timestamp: Option<i64>,
+	pub link_type: u16,
+	pub data: Vec<u8>,
+}
+
+/// Reads integers of the byte order of the section of the capture being read.
+#[derive(Debug, Clone, Copy)]
+struct Reader<'b> {
+	data: &'b [u8],
+	big_endian: bool,
+}
+
+impl<'b> Reader<'b> {
+	fn bytes(&self, offset: usize, len: usize) -> Result<&'b [u8], ReplayError> {
+		offset
+			.checked_add(len)
+			.and_then(|end| self.data.get(offset..end))
+			.ok_or(ReplayError::Malformed("truncated"))
+	}
+
+	fn u16(&self, offset: usize) -> Result<u16, ReplayError> {
+		let bytes = self.bytes(offset, 2)?;
+		let bytes = [bytes[0], bytes[1]];
+		Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+	}
+
+	fn u32(&self, offset: usize) -> Result<u32, ReplayError> {
+		let bytes = self.bytes(offset, 4)?;
+		let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+		Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+	}
+}
+
+/// Converts a timestamp in units of `10^-exponent` seconds to microseconds.
+fn to_micros(ticks: u64, exponent: u8) -> i64 {
+	let micros = if exponent >= 6 {
+		ticks / 10u64.saturating_pow(u32::from(exponent - 6))
+	} else {
+		ticks.saturating_mul(10u64.saturating_pow(u32::from(6 - exponent)))
+	};
+	micros.min(i64::MAX as u64) as i64
+}
+
+/// Reads all the frames of a pcap or pcapng capture, in the order they are stored.
+pub fn read_capture(data: &[u8]) -> Result<Vec<ReplayFrame>, ReplayError> {
+	if data.len() < 4 {
+		return Err(ReplayError::UnknownFormat);
+	}
+	let magic = [data[0], data[1], data[2], data[3]];
+	if u32::from_le_bytes(magic) == PCAPNG_BLOCK_TYPE_SECTION_HEADER {
+		return read_pcapng(data);
+	}
+	for big_endian in [false, true] {
+		let magic = if big_endian { u32::from_be_bytes(magic) } else { u32::from_le_bytes(magic) };
+		if magic == PCAP_MAGIC_MICROS || magic == PCAP_MAGIC_NANOS {
+			return read_pcap(Reader { data, big_endian }, magic == PCAP_MAGIC_NANOS);
+		}
+	}
+	Err(ReplayError::UnknownFormat)
+}
+
+fn read_pcap(reader: Reader, nanos: bool) -> Result<Vec<ReplayFrame>, ReplayError> {
+	let link_type = reader.u32(20)? as u16;
+	let mut frames = Vec::new();
+	let mut offset = 24;
+	while offset < reader.data.len() {
+		let seconds = u64::from(reader.u32(offset)?);
+		let fraction = u64::from(reader.u32(offset + 4)?);
+		let captured_len = reader.u32(offset + 8)? as usize;
+		let data = reader.bytes(offset + 16, captured_len)?;
+		let fraction = if nanos { fraction / 1000 } else { fraction };
+		frames.push(ReplayFrame {
+			timestamp: Some(to_micros(seconds, 0).saturating_add(fraction as i64)),
+			link_type,
+			data: data.to_vec(),
+		});
+		offset += 16 + captured_len;
+	}
+	Ok(frames)
+}
+
+/// The link type and the timestamp resolution of an interface of a pcapng section.
+#[derive(Debug, Clone, Copy)]
+struct PcapngInterface {
+	link_type: u16,
+	/// Exponent of the negative power of 10, or of 2 if the high bit is set, of a second the timestamps are in.
+	tsresol: u8,
+}
+
+impl PcapngInterface {
+	fn micros(&self, ticks: u64) -> i64 {
+		if self.tsresol & 0x80 != 0 {
+			let shift = u32::from(self.tsresol & 0x7F);
+			let micros = (u128::from(ticks) * 1_000_000) >> shift;
+			micros.min(i64::MAX as u128) as i64
+		} else {
+			to_micros(ticks, self.tsresol)
+		}
+	}
+}
+
+fn read_pcapng(data: &[u8]) -> Result<Vec<ReplayFrame>, ReplayError> {
+	let mut reader = Reader { data, big_endian: false };
+	let mut interfaces: Vec<PcapngInterface> = Vec::new();
+	let mut frames = Vec::new();
+	let mut offset = 0;
+	while offset < data.len() {
+		let block_type = reader.u32(offset)?;
+		if block_type == PCAPNG_BLOCK_TYPE_SECTION_HEADER {
+			// A new section may have another byte order, and has its own interfaces.
+			let magic = reader.bytes(offset + 8, 4)?;
+			let magic = [magic[0], magic[1], magic[2], magic[3]];
+			reader.big_endian = if u32::from_le_bytes(magic) == PCAPNG_BYTE_ORDER_MAGIC {
+				false
+			} else if u32::from_be_bytes(magic) == PCAPNG_BYTE_ORDER_MAGIC {
+				true
+			} else {
+				return Err(ReplayError::Malformed("bad byte-order magic"));
+			};
+			interfaces.clear();
+		}
+		let block_len = reader.u32(offset + 4)? as usize;
+		if block_len < 12 || !block_len.is_multiple_of(4) {
+			return Err(ReplayError::Malformed("bad block length"));
+		}
+		// The whole block must be there, down to its trailing length.
+		reader.bytes(offset, block_len)?;
+		let body = Reader {
+			data: reader.bytes(offset + 8, block_len - 12)?,
+			big_endian: reader.big_endian,
+		};
+		match block_type {
+			PCAPNG_BLOCK_TYPE_INTERFACE_DESCRIPTION => {
+				let mut interface = PcapngInterface {
+					link_type: body.u16(0)?,
+					tsresol: 6,
+				};
+				let mut option = 8;
+				while option + 4 <= body.data.len() {
+					let code = body.u16(option)?;
+					let len = body.u16(option + 2)? as usize;
+					if code == PCAPNG_OPTION_END {
+						break;
+					}
+					if code == PCAPNG_OPTION_IF_TSRESOL && len == 1 {
+						interface.tsresol = body.bytes(option + 4, 1)?[0];
+					}
+					option += 4 + ((len + 3) & !3);
+				}
+				interfaces.push(interface);
+			}
+			PCAPNG_BLOCK_TYPE_ENHANCED_PACKET => {
+				let interface = interfaces
+					.get(body.u32(0)? as usize)
+					.ok_or(ReplayError::Malformed("packet of an undescribed interface"))?;
+				let ticks = u64::from(body.u32(4)?) << 32 | u64::from(body.u32(8)?);
+				let captured_len = body.u32(12)? as usize;
+				frames.push(ReplayFrame {
+					timestamp: Some(interface.micros(ticks)),
+					link_type: interface.link_type,
+					data: body.bytes(20, captured_len)?.to_vec(),
+				});
+			}
+			PCAPNG_BLOCK_TYPE_SIMPLE_PACKET => {
+				let interface = interfaces.first().ok_or(ReplayError::Malformed("packet of an undescribed interface"))?;
+				let original_len = body.u32(0)? as usize;
+				let captured_len = original_len.min(body.data.len().saturating_sub(4));
+				frames.push(ReplayFrame {
+					timestamp: None,
+					link_type: interface.link_type,
+					data: body.bytes(4, captured_len)?.to_vec(),
+				});
+			}
+			// Statistics, name resolution and the rest don't matter for replaying.
+			_ => {}
+		}
+		offset += block_len;
+	}
+	Ok(frames)
+}
+
+/// Whether a frame of `link_type` can be put into a device of `medium`.
+fn link_type_matches(link_type: u16, medium: Medium) -> bool {
+	match link_type {
+		LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => medium == Medium::Ip,
+		LINKTYPE_ETHERNET => medium == Medium::Ethernet,
+		other => other == capture::link_type(medium),
+	}
+}
+
+/// Whether the frame is for the interface of the device: unicast to one of its addresses, or broadcast, or multicast.
+fn is_addressed_to(c_device: &CDevice, frame: &[u8]) -> bool {
+	match c_device.device.medium() {
+		Medium::Ethernet => match EthernetFrame::new_checked(frame) {
+			Ok(frame) => {
+				let dst = frame.dst_addr();
+				dst.is_broadcast() || dst.is_multicast() || c_device.iface.hardware_addr() == HardwareAddress::Ethernet(dst)
+			}
+			Err(_) => false,
+		},
+		Medium::Ip => match frame.first().map(|first| first >> 4) {
+			Some(4) => match Ipv4Packet::new_checked(frame) {
+				Ok(packet) => {
+					let dst = packet.dst_addr();
+					dst.is_broadcast() || dst.is_multicast() || c_device.iface.has_ip_addr(dst)
+				}
+				Err(_) => false,
+			},
+			Some(6) => match Ipv6Packet::new_checked(frame) {
+				Ok(packet) => {
+					let dst = packet.dst_addr();
+					dst.is_multicast() || c_device.iface.has_ip_addr(dst)
+				}
+				Err(_) => false,
+			},
+			_ => false,
+		},
+		#[allow(unreachable_patterns)]
+		_ => true,
+	}
+}
+
+/// What `replay` has done with the frames of a capture.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CReplayStats {
+	/// Frames in the capture.
+	pub frames: usize,
+	/// Frames put into the RX queue.
+	pub injected: usize,
+	/// Frames skipped, because their link type doesn't match the medium of the device or they are not addressed to the interface.
+	pub skipped: usize,
+	/// Frames refused by the RX queue.
+	pub refused: usize,
+}
+
+/// Feeds the frames into the device one by one, polling it after each. The clock of the device is advanced between the frames as they were between their timestamps, the first frame being put at the current moment of the device, and the device is also polled at the deadlines of the stack falling between them. If `only_addressed` is set, only the frames addressed to the interface are put.
+pub fn replay(c_device: &mut CDevice, frames: &[ReplayFrame], only_addressed: bool) -> CReplayStats {
+	let mut stats = CReplayStats {
+		frames: frames.len(),
+		..CReplayStats::default()
+	};
+	let medium = c_device.device.medium();
+	let start = c_device.timestamp;
+	let first = frames.iter().find_map(|frame| frame.timestamp);
+
+	for frame in frames {
+		if !link_type_matches(frame.link_type, medium) || (only_addressed && !is_addressed_to(c_device, &frame.data)) {
+			stats.skipped += 1;
+			continue;
+		}
+
+		let at = match (frame.timestamp, first) {
+			(Some(timestamp), Some(first)) => start + Duration::from_micros(timestamp.saturating_sub(first).max(0) as u64),
+			_ => c_device.timestamp,
+		};
+		// Captures are not always sorted, but the clock of the stack must not go back.
+		let at = if at < c_device.timestamp { c_device.timestamp } else { at };
+		advance_to(c_device, at);
+
+		// So a capture going on records the frame at the moment it is replayed at.
+		c_device.device.timestamp = at;
+		match c_device.device.put_rx(frame.data.clone().into()) {
+			Ok(()) => stats.injected += 1,
+			Err(_) => stats.refused += 1,
+		}
+		c_device.poll(at);
+	}
+	stats
+}
+
+/// Polls the device at each deadline of the stack before `at`.
+fn advance_to(c_device: &mut CDevice, at: Instant) {
+	while let Some(next) = c_device.poll_at() {
+		if next >= at || next <= c_device.timestamp {
+			break;
+		}
+		c_device.poll(next);
+	}
+}
+
+fn replay_data(name: &str, c_device: CDevicePtr, data: &[u8], only_addressed: bool, stats: *mut CReplayStats) -> ResultCode {
+	let c_device = unsafe { device_from_opaque_ptr(c_device) };
+	match read_capture(data) {
+		Ok(frames) => {
+			let res = replay(c_device, &frames, only_addressed);
+			if let Some(stats) = unsafe { stats.as_mut() } {
+				*stats = res;
+			}
+			ResultCode::OK
+		}
+		Err(err) => {
+			set_last_error(ResultCode::Malformed, format!("{}: cannot read the capture: {}", name, err));
+			ResultCode::Malformed
+		}
+	}
+}
+
+/// Replays the frames of the pcap or pcapng file at `path` (a NUL-terminated UTF-8 string) into the device, see `replay`. What has been done with them is written to `stats` (if not NULL). Returns `Illegal` if the file cannot be read, `Malformed` if it is not a valid capture.
+#[no_mangle]
+pub extern "C" fn replayCaptureFile(c_device: CDevicePtr, path: *const c_char, only_addressed: bool, stats: *mut CReplayStats) -> ResultCode {
+	guard("replayCaptureFile", || {
+		if path.is_null() {
+			set_last_error(ResultCode::Illegal, "replayCaptureFile: got NULL path".to_string());
+			return ResultCode::Illegal;
+		}
+		let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+			Ok(path) => path,
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("replayCaptureFile: the path is not UTF-8: {}", err));
+				return ResultCode::Illegal;
+			}
+		};
+		match fs::read(path) {
+			Ok(data) => replay_data("replayCaptureFile", c_device, &data, only_addressed, stats),
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("replayCaptureFile: cannot read {}: {}", path, err));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Same as `replayCaptureFile`, with the capture of `size` bytes in memory at `data`. Returns `Illegal` if `data` is NULL while `size` is not 0.
+#[no_mangle]
+pub extern "C" fn replayCaptureBuffer(
+	c_device: CDevicePtr,
+	data: *const u8,
+	size: usize,
+	only_addressed: bool,
+	stats: *mut CReplayStats,
+) -> ResultCode {
+	guard("replayCaptureBuffer", || {
+		let data = host_slice!("replayCaptureBuffer", data, size);
+		replay_data("replayCaptureBuffer", c_device, data, only_addressed, stats)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use smoltcp::iface::InterfaceBuilder;
+	use smoltcp::wire::{IpAddress, IpCidr};
+
+	use super::*;
+	use crate::capture::{Capture, CaptureDirection};
+	use crate::device::SansIO;
+
+	fn pcap(big_endian: bool, nanos: bool, records: &[(u32, u32, &[u8])]) -> Vec<u8> {
+		let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+		let mut data = Vec::new();
+		data.extend_from_slice(&u32_bytes(if nanos { PCAP_MAGIC_NANOS } else { PCAP_MAGIC_MICROS }));
+		data.extend_from_slice(&[0; 16]);
+		data.extend_from_slice(&u32_bytes(u32::from(LINKTYPE_RAW)));
+		for &(seconds, fraction, frame) in records {
+			data.extend_from_slice(&u32_bytes(seconds));
+			data.extend_from_slice(&u32_bytes(fraction));
+			data.extend_from_slice(&u32_bytes(frame.len() as u32));
+			data.extend_from_slice(&u32_bytes(frame.len() as u32));
+			data.extend_from_slice(frame);
+		}
+		data
+	}
+
+	/// A pcapng block of `block_type` with `body`, padded to 4 bytes.
+	fn block(big_endian: bool, block_type: u32, body: &[u8]) -> Vec<u8> {
+		let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+		let len = 12 + ((body.len() + 3) & !3) as u32;
+		let mut data = Vec::new();
+		data.extend_from_slice(&u32_bytes(block_type));
+		data.extend_from_slice(&u32_bytes(len));
+		data.extend_from_slice(body);
+		data.resize(len as usize - 4, 0);
+		data.extend_from_slice(&u32_bytes(len));
+		data
+	}
+
+	#[test]
+	fn pcap_of_either_byte_order_and_resolution_is_read() {
+		for (big_endian, nanos) in [(false, false), (true, false), (false, true), (true, true)] {
+			let fraction = if nanos { 250_000_000 } else { 250_000 };
+			let data = pcap(big_endian, nanos, &[(3, fraction, b"first"), (4, 0, b"")]);
+			let frames = read_capture(&data).unwrap();
+			assert_eq!(frames.len(), 2);
+			assert_eq!(frames[0].timestamp, Some(3_250_000));
+			assert_eq!(frames[0].link_type, LINKTYPE_RAW);
+			assert_eq!(frames[0].data, b"first");
+			assert_eq!(frames[1].timestamp, Some(4_000_000));
+			assert!(frames[1].data.is_empty());
+		}
+	}
+
+	#[test]
+	fn pcapng_written_by_a_capture_is_read_back() {
+		let mut capture = Capture::to_memory(Medium::Ethernet);
+		capture.record(Instant::from_micros(1_500_001), CaptureDirection::Inbound, &[1; 60]);
+		capture.record(Instant::from_micros(7_000_000_123i64), CaptureDirection::Outbound, &[2; 61]);
+
+		let frames = read_capture(capture.buffer().unwrap()).unwrap();
+		assert_eq!(frames.len(), 2);
+		assert_eq!(frames[0].timestamp, Some(1_500_001));
+		assert_eq!(frames[0].link_type, capture::LINKTYPE_ETHERNET);
+		assert_eq!(frames[0].data, vec![1; 60]);
+		assert_eq!(frames[1].timestamp, Some(7_000_000_123));
+		assert_eq!(frames[1].data, vec![2; 61]);
+	}
+
+	#[test]
+	fn replayed_frames_are_captured() {
+		let mut sans_io = SansIO::new(1500, Medium::Ip);
+		let iface = InterfaceBuilder::new()
+			.ip_addrs(vec![IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24)])
+			.finalize(&mut sans_io);
+		let mut device = CDevice::new(sans_io, iface);
+		device.poll(Instant::from_secs(10));
+		device.device.capture = Some(Capture::to_memory(Medium::Ip));
+
+		// Addressed to another host, so the stack drops them without answering.
+		let mut packets = Vec::new();
+		for id in 0..2u8 {
+			let mut packet = vec![0x45, 0, 0, 28, 0, id, 0, 0, 64, 17, 0, 0, 10, 0, 0, 2, 10, 0, 0, 9];
+			packet.extend_from_slice(&[0, 9, 0, 9, 0, 8, 0, 0]);
+			Ipv4Packet::new_unchecked(&mut packet[..]).fill_checksum();
+			packets.push(packet);
+		}
+		let frames: Vec<ReplayFrame> = packets
+			.iter()
+			.zip([3_000_000, 3_250_000])
+			.map(|(packet, timestamp)| ReplayFrame {
+				timestamp: Some(timestamp),
+				link_type: LINKTYPE_RAW,
+				data: packet.clone(),
+			})
+			.collect();
+		let stats = replay(&mut device, &frames, false);
+		assert_eq!(stats.injected, 2);
+
+		let captured = read_capture(device.device.capture.as_ref().unwrap().buffer().unwrap()).unwrap();
+		assert_eq!(captured.len(), 2);
+		for (frame, packet) in captured.iter().zip(&packets) {
+			assert_eq!(&frame.data, packet);
+		}
+		assert_eq!(captured[0].timestamp, Some(10_000_000));
+		assert_eq!(captured[1].timestamp, Some(10_250_000));
+	}
+
+	#[test]
+	fn big_endian_pcapng_with_a_timestamp_resolution_and_simple_packets_is_read() {
+		let mut section = PCAPNG_BYTE_ORDER_MAGIC.to_be_bytes().to_vec();
+		section.extend_from_slice(&[0, 1, 0, 0]);
+		section.extend_from_slice(&(-1i64).to_be_bytes());
+
+		let mut interface = LINKTYPE_IPV4.to_be_bytes().to_vec();
+		interface.extend_from_slice(&[0, 0, 0, 0, 0xff, 0xff]);
+		// Nanoseconds.
+		interface.extend_from_slice(&PCAPNG_OPTION_IF_TSRESOL.to_be_bytes());
+		interface.extend_from_slice(&1u16.to_be_bytes());
+		interface.extend_from_slice(&[9, 0, 0, 0]);
+		interface.extend_from_slice(&[0; 4]);
+
+		let ticks: u64 = 5_000_000_000_123_000;
+		let mut packet = 0u32.to_be_bytes().to_vec();
+		packet.extend_from_slice(&((ticks >> 32) as u32).to_be_bytes());
+		packet.extend_from_slice(&(ticks as u32).to_be_bytes());
+		packet.extend_from_slice(&3u32.to_be_bytes());
+		packet.extend_from_slice(&3u32.to_be_bytes());
+		packet.extend_from_slice(b"abc");
+
+		let mut simple = 5u32.to_be_bytes().to_vec();
+		simple.extend_from_slice(b"hello");
+
+		let mut data = block(true, PCAPNG_BLOCK_TYPE_SECTION_HEADER, &section);
+		data.extend(block(true, PCAPNG_BLOCK_TYPE_INTERFACE_DESCRIPTION, &interface));
+		// Of a type not replayed, skipped.
+		data.extend(block(true, 0x0000_0005, &[0; 8]));
+		data.extend(block(true, PCAPNG_BLOCK_TYPE_ENHANCED_PACKET, &packet));
+		data.extend(block(true, PCAPNG_BLOCK_TYPE_SIMPLE_PACKET, &simple));
+
+		let frames = read_capture(&data).unwrap();
+		assert_eq!(frames.len(), 2);
+		assert_eq!(frames[0].timestamp, Some(5_000_000_000_123));
+		assert_eq!(frames[0].link_type, LINKTYPE_IPV4);
+		assert_eq!(frames[0].data, b"abc");
+		assert_eq!(frames[1].timestamp, None);
+		assert_eq!(frames[1].data, b"hello");
+	}
+
+	#[test]
+	fn broken_captures_are_refused() {
+		assert!(matches!(read_capture(b""), Err(ReplayError::UnknownFormat)));
+		assert!(matches!(read_capture(b"GIF89a"), Err(ReplayError::UnknownFormat)));
+
+		let data = pcap(false, false, &[(1, 0, b"frame")]);
+		for len in [20, 30, data.len() - 1] {
+			assert!(matches!(read_capture(&data[..len]), Err(ReplayError::Malformed(_))));
+		}
+
+		let mut capture = Capture::to_memory(Medium::Ip);
+		capture.record(Instant::from_micros(0), CaptureDirection::Inbound, &[0x45; 20]);
+		let data = capture.buffer().unwrap();
+		for len in [8, 30, data.len() - 1] {
+			assert!(matches!(read_capture(&data[..len]), Err(ReplayError::Malformed(_))));
+		}
+		// A packet before its interface is described.
+		let data = [&data[..28], &data[48..]].concat();
+		assert!(matches!(read_capture(&data), Err(ReplayError::Malformed(_))));
+	}
+}
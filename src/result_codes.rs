@@ -1,3 +1,5 @@
+use core::fmt;
+
 /// The error type for the networking stack.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
@@ -89,3 +91,88 @@ pub enum ResultCode {
     // Used in FFI bindings to indicate that the provided buffer size is insufficient.
     BufferInsufficient = 0xFF,
 }
+
+impl ResultCode {
+    /// All the codes, to look a raw value up.
+    const ALL: [ResultCode; 28] = [
+        ResultCode::OK,
+        ResultCode::Exhausted,
+        ResultCode::Illegal,
+        ResultCode::Unaddressable,
+        ResultCode::Finished,
+        ResultCode::Truncated,
+        ResultCode::Checksum,
+        ResultCode::Unrecognized,
+        ResultCode::Fragmented,
+        ResultCode::Malformed,
+        ResultCode::Dropped,
+        ResultCode::ReassemblyTimeout,
+        ResultCode::PacketAssemblerNotInit,
+        ResultCode::PacketAssemblerBufferTooSmall,
+        ResultCode::PacketAssemblerIncomplete,
+        ResultCode::PacketAssemblerTooManyHoles,
+        ResultCode::PacketAssemblerOverlap,
+        ResultCode::PacketAssemblerSetFull,
+        ResultCode::PacketAssemblerSetKeyNotFound,
+        ResultCode::NotSupported,
+        ResultCode::InvalidState,
+        ResultCode::BufferFull,
+        ResultCode::NoFreeSlot,
+        ResultCode::InvalidName,
+        ResultCode::NameTooLong,
+        ResultCode::Pending,
+        ResultCode::Failed,
+        ResultCode::BufferInsufficient,
+    ];
+
+    /// Returns the code with the raw value `code`, if there is one.
+    pub fn from_u8(code: u8) -> Option<ResultCode> {
+        ResultCode::ALL.iter().copied().find(|&res| res as u8 == code)
+    }
+
+    /// The description of the code, NUL-terminated so it can be given out to C as is.
+    pub(crate) fn description_with_nul(self) -> &'static str {
+        match self {
+            ResultCode::OK => "everything is OK\0",
+            ResultCode::Exhausted => "a buffer is empty or full\0",
+            ResultCode::Illegal => "the operation is not permitted in the current state\0",
+            ResultCode::Unaddressable => "the endpoint or address could not be translated to a lower level address\0",
+            ResultCode::Finished => "the operation is finished\0",
+            ResultCode::Truncated => "a packet is truncated\0",
+            ResultCode::Checksum => "a packet has an incorrect checksum\0",
+            ResultCode::Unrecognized => "a packet could not be recognized\0",
+            ResultCode::Fragmented => "a packet is fragmented and reassembly is not supported\0",
+            ResultCode::Malformed => "a packet is self-contradictory\0",
+            ResultCode::Dropped => "a packet contradicts internal state and was dropped\0",
+            ResultCode::ReassemblyTimeout => "a fragment arrived too late\0",
+            ResultCode::PacketAssemblerNotInit => "the packet assembler is not initialized\0",
+            ResultCode::PacketAssemblerBufferTooSmall => "the buffer of the packet assembler is too small\0",
+            ResultCode::PacketAssemblerIncomplete => "the packet assembler did not receive all the fragments\0",
+            ResultCode::PacketAssemblerTooManyHoles => "there are too many holes in the packet assembler\0",
+            ResultCode::PacketAssemblerOverlap => "fragments overlap in the packet assembler\0",
+            ResultCode::PacketAssemblerSetFull => "the packet assembler set is full\0",
+            ResultCode::PacketAssemblerSetKeyNotFound => "the key was not found in the packet assembler set\0",
+            ResultCode::NotSupported => "a packet uses a feature not supported by smoltcp\0",
+            ResultCode::InvalidState => "the socket is in a state not allowing the operation\0",
+            ResultCode::BufferFull => "the buffer of the socket is full\0",
+            ResultCode::NoFreeSlot => "there is no free slot for a DNS query\0",
+            ResultCode::InvalidName => "the DNS name is invalid\0",
+            ResultCode::NameTooLong => "the DNS name is too long\0",
+            ResultCode::Pending => "the operation is not done yet\0",
+            ResultCode::Failed => "the operation has failed\0",
+            ResultCode::BufferInsufficient => "the provided buffer is too small\0",
+        }
+    }
+
+    /// A short human-readable description of the code.
+    pub fn description(self) -> &'static str {
+        let description = self.description_with_nul();
+        &description[..description.len() - 1]
+    }
+}
+
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
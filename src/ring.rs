@@ -0,0 +1,306 @@
+use core::ptr::{self, addr_of_mut};
+use core::sync::atomic::{AtomicU32, Ordering};
+use std::slice;
+use std::vec::Vec;
+
+use smoltcp::{Error, Result};
+
+/// Frames in a ring are stored as records: a native-endian `u32` length followed by the frame bytes, padded to a multiple of `RING_RECORD_ALIGN`.
+pub const RING_RECORD_ALIGN: u32 = 4;
+
+/// Written by the producer instead of a record length when the rest of the data area is too small for the next record; the record then starts at offset 0.
+pub const RING_WRAP_MARKER: u32 = 0xFFFF_FFFF;
+
+/// Required alignment of a ring region.
+pub const RING_REGION_ALIGN: usize = 64;
+
+/// The header of a single-producer/single-consumer ring living in shared memory, followed by `capacity` bytes of data area.
+///
+/// `head` (the offset the producer writes the next record at) is only written by the producer, `tail` (the offset of the oldest unconsumed record) only by the consumer. Both must be accessed atomically, published with release and read with acquire ordering. The ring is empty when `head == tail`, and a producer never lets `head` catch up with `tail`.
+#[repr(C)]
+pub struct CRingHeader {
+	pub head: u32,
+	_head_padding: [u8; 60],
+	pub tail: u32,
+	pub capacity: u32,
+	_tail_padding: [u8; 56],
+}
+
+/// A view of a ring in caller-supplied memory. Either side of the ring can be driven through it, but each side only by one thread at a time.
+#[derive(Debug, Clone, Copy)]
+pub struct SpscRing {
+	header: *mut CRingHeader,
+	data: *mut u8,
+	capacity: u32,
+}
+
+fn record_len(frame_len: u32) -> u32 {
+	4 + ((frame_len + RING_RECORD_ALIGN - 1) & !(RING_RECORD_ALIGN - 1))
+}
+
+impl SpscRing {
+	/// Initializes an empty ring occupying `size` bytes at `region`.
+	///
+	/// # Safety
+	/// `region` must be valid for `size` bytes, aligned to `RING_REGION_ALIGN` and outlive the ring.
+	pub unsafe fn init(region: *mut u8, size: usize) -> Result<SpscRing> {
+		let header_len = core::mem::size_of::<CRingHeader>();
+		if region.is_null() || region as usize & (RING_REGION_ALIGN - 1) != 0 || size < header_len + 2 * record_len(0) as usize {
+			return Err(Error::Illegal);
+		}
+		let capacity = ((size - header_len).min(u32::MAX as usize / 2) as u32) / RING_RECORD_ALIGN * RING_RECORD_ALIGN;
+
+		let header: *mut CRingHeader = region.cast();
+		ptr::write_bytes(header, 0, 1);
+		(*header).capacity = capacity;
+		let ring = SpscRing {
+			header,
+			data: region.add(header_len),
+			capacity,
+		};
+		ring.head().store(0, Ordering::Release);
+		ring.tail().store(0, Ordering::Release);
+		Ok(ring)
+	}
+
+	/// Opens a ring initialized with `init`, e.g. by another process.
+	///
+	/// # Safety
+	/// `region` must point to an initialized ring that outlives the returned view.
+	pub unsafe fn open(region: *mut u8) -> SpscRing {
+		let header: *mut CRingHeader = region.cast();
+		SpscRing {
+			header,
+			data: region.add(core::mem::size_of::<CRingHeader>()),
+			capacity: (*header).capacity,
+		}
+	}
+
+	fn head(&self) -> &AtomicU32 {
+		unsafe { &*(addr_of_mut!((*self.header).head) as *const AtomicU32) }
+	}
+
+	fn tail(&self) -> &AtomicU32 {
+		unsafe { &*(addr_of_mut!((*self.header).tail) as *const AtomicU32) }
+	}
+
+	unsafe fn write_u32(&self, offset: u32, value: u32) {
+		ptr::write_unaligned(self.data.add(offset as usize).cast(), value);
+	}
+
+	unsafe fn read_u32(&self, offset: u32) -> u32 {
+		ptr::read_unaligned(self.data.add(offset as usize).cast())
+	}
+
+	/// Whether `offset`, read from the shared memory, is a record offset within the data area, so the other side cannot make us access memory out of it.
+	fn is_valid_offset(&self, offset: u32) -> bool {
+		offset < self.capacity && offset.is_multiple_of(RING_RECORD_ALIGN)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.head().load(Ordering::Acquire) == self.tail().load(Ordering::Acquire)
+	}
+
+	/// Producer side: reserves room for a frame of `len` bytes, lets `f` write it right into the ring and publishes it. Fails with `Exhausted` if there is no room, and with `Illegal` if the offsets in the header are out of the data area.
+	pub fn push_with<R, F>(&self, len: usize, f: F) -> Result<R>
+	where
+		F: FnOnce(&mut [u8]) -> Result<R>,
+	{
+		if len > (self.capacity / 2) as usize {
+			return Err(Error::Exhausted);
+		}
+		let rec = record_len(len as u32);
+		let head = self.head().load(Ordering::Relaxed);
+		let tail = self.tail().load(Ordering::Acquire);
+		if !self.is_valid_offset(head) || !self.is_valid_offset(tail) {
+			return Err(Error::Illegal);
+		}
+
+		let (start, new_head) = if head >= tail {
+			let free_at_end = self.capacity - head;
+			if rec < free_at_end || (rec == free_at_end && tail != 0) {
+				(head, (head + rec) % self.capacity)
+			} else if rec < tail {
+				unsafe { self.write_u32(head, RING_WRAP_MARKER) };
+				(0, rec)
+			} else {
+				return Err(Error::Exhausted);
+			}
+		} else if rec < tail - head {
+			(head, head + rec)
+		} else {
+			return Err(Error::Exhausted);
+		};
+
+		unsafe { self.write_u32(start, len as u32) };
+		let dst = unsafe { slice::from_raw_parts_mut(self.data.add(start as usize + 4), len) };
+		let result = f(dst)?;
+		self.head().store(new_head, Ordering::Release);
+		Ok(result)
+	}
+
+	/// Producer side: copies a frame into the ring.
+	pub fn push(&self, frame: &[u8]) -> Result<()> {
+		self.push_with(frame.len(), |dst| {
+			dst.copy_from_slice(frame);
+			Ok(())
+		})
+	}
+
+	/// Consumer side: takes the oldest frame out of the ring. Returns `None` if the ring is empty, or if the producer has written offsets or a record length that would take the record out of the data area; the ring is left as it is then.
+	pub fn pop(&self) -> Option<Vec<u8>> {
+		let mut tail = self.tail().load(Ordering::Relaxed);
+		let head = self.head().load(Ordering::Acquire);
+		if tail == head || !self.is_valid_offset(tail) || !self.is_valid_offset(head) {
+			return None;
+		}
+
+		let mut len = unsafe { self.read_u32(tail) };
+		if len == RING_WRAP_MARKER {
+			tail = 0;
+			len = unsafe { self.read_u32(tail) };
+		}
+		if len > self.capacity / 2 || tail + record_len(len) > self.capacity {
+			return None;
+		}
+		let frame = unsafe { slice::from_raw_parts(self.data.add(tail as usize + 4), len as usize) }.to_vec();
+		self.tail().store((tail + record_len(len)) % self.capacity, Ordering::Release);
+		Some(frame)
+	}
+}
+
+/// The rings of a frame transport sharing one memory region: the host produces into `rx` and consumes from `tx`.
+#[derive(Debug, Clone, Copy)]
+pub struct RingPair {
+	pub rx: SpscRing,
+	pub tx: SpscRing,
+}
+
+impl RingPair {
+	/// Splits `size` bytes at `region` into two equal rings (RX first, TX second) and initializes them.
+	///
+	/// # Safety
+	/// `region` must be valid for `size` bytes, aligned to `RING_REGION_ALIGN` and outlive the rings.
+	pub unsafe fn init(region: *mut u8, size: usize) -> Result<RingPair> {
+		let half = size / 2 / RING_REGION_ALIGN * RING_REGION_ALIGN;
+		Ok(RingPair {
+			rx: SpscRing::init(region, half)?,
+			tx: SpscRing::init(region.add(half), half)?,
+		})
+	}
+
+	/// Opens the rings of a region initialized with `init`.
+	///
+	/// # Safety
+	/// `region` must point to an initialized ring pair of `size` bytes that outlives the returned views.
+	pub unsafe fn open(region: *mut u8, size: usize) -> RingPair {
+		let half = size / 2 / RING_REGION_ALIGN * RING_REGION_ALIGN;
+		RingPair {
+			rx: SpscRing::open(region),
+			tx: SpscRing::open(region.add(half)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A memfd mapped twice, as the two processes sharing a ring would map it.
+	struct SharedRegion {
+		fd: libc::c_int,
+		size: usize,
+		producer: *mut u8,
+		consumer: *mut u8,
+	}
+
+	impl SharedRegion {
+		fn new(size: usize) -> SharedRegion {
+			unsafe {
+				let fd = libc::memfd_create(b"sio-smoltcp-ring\0".as_ptr().cast(), 0);
+				assert!(fd >= 0);
+				assert_eq!(libc::ftruncate(fd, size as libc::off_t), 0);
+				let map = || {
+					let ptr = libc::mmap(ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd, 0);
+					assert_ne!(ptr, libc::MAP_FAILED);
+					ptr.cast::<u8>()
+				};
+				SharedRegion {
+					fd,
+					size,
+					producer: map(),
+					consumer: map(),
+				}
+			}
+		}
+	}
+
+	impl Drop for SharedRegion {
+		fn drop(&mut self) {
+			unsafe {
+				libc::munmap(self.producer.cast(), self.size);
+				libc::munmap(self.consumer.cast(), self.size);
+				libc::close(self.fd);
+			}
+		}
+	}
+
+	#[test]
+	fn frames_cross_the_mappings_in_order_and_wrap_around() {
+		let region = SharedRegion::new(4096);
+		let producer = unsafe { SpscRing::init(region.producer, 512) }.unwrap();
+		let consumer = unsafe { SpscRing::open(region.consumer) };
+		assert!(consumer.is_empty());
+
+		for round in 0..100u8 {
+			let frames: Vec<Vec<u8>> = (0..3).map(|i| vec![round.wrapping_add(i); 37 + usize::from(i) * 11]).collect();
+			for frame in &frames {
+				producer.push(frame).unwrap();
+			}
+			for frame in &frames {
+				assert_eq!(consumer.pop().as_ref(), Some(frame));
+			}
+			assert_eq!(consumer.pop(), None);
+		}
+	}
+
+	#[test]
+	fn a_full_ring_refuses_frames() {
+		let region = SharedRegion::new(4096);
+		// 256 bytes of data area, for frames of up to 128 bytes.
+		let producer = unsafe { SpscRing::init(region.producer, 128 + 256) }.unwrap();
+		let consumer = unsafe { SpscRing::open(region.consumer) };
+
+		assert_eq!(producer.push(&[0; 129]), Err(Error::Exhausted));
+		assert_eq!(producer.push(&[1; 100]), Ok(()));
+		assert_eq!(producer.push(&[2; 100]), Ok(()));
+		assert_eq!(producer.push(&[3; 100]), Err(Error::Exhausted));
+		assert_eq!(consumer.pop(), Some(vec![1; 100]));
+		assert_eq!(consumer.pop(), Some(vec![2; 100]));
+		assert_eq!(producer.push(&[3; 100]), Ok(()));
+		assert_eq!(consumer.pop(), Some(vec![3; 100]));
+	}
+
+	#[test]
+	fn records_out_of_the_data_area_are_refused() {
+		let region = SharedRegion::new(4096);
+		let producer = unsafe { SpscRing::init(region.producer, 512) }.unwrap();
+		let consumer = unsafe { SpscRing::open(region.consumer) };
+
+		producer.push(&[1; 8]).unwrap();
+		for len in [u32::MAX - 1, consumer.capacity, consumer.capacity / 2 + 1] {
+			unsafe { producer.write_u32(0, len) };
+			assert_eq!(consumer.pop(), None);
+		}
+		// Fits the limit of a frame, but not the data area after the record offset.
+		producer.tail().store(consumer.capacity - 8, Ordering::Release);
+		unsafe { producer.write_u32(consumer.capacity - 8, 64) };
+		assert_eq!(consumer.pop(), None);
+
+		producer.tail().store(consumer.capacity + 4, Ordering::Release);
+		assert_eq!(consumer.pop(), None);
+		assert_eq!(producer.push(&[1; 8]), Err(Error::Illegal));
+		producer.tail().store(2, Ordering::Release);
+		assert_eq!(consumer.pop(), None);
+	}
+}
@@ -0,0 +1,1272 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use smoltcp::phy::{ChecksumCapabilities, Medium};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+	ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, Icmpv4DstUnreachable,
+	Icmpv4Packet, Icmpv4Repr, Icmpv4TimeExceeded, Icmpv6DstUnreachable, Icmpv6Packet, Icmpv6Repr, Icmpv6TimeExceeded,
+	IpAddress, IpCidr, IpProtocol, Ipv4Address, Ipv4Packet, Ipv4Repr, Ipv6Address, Ipv6Packet, Ipv6Repr,
+	NdiscNeighborFlags, NdiscRepr,
+};
+
+use crate::address::{CAddress, CInterface, CMacAddress};
+use crate::abi::supported_medium;
+use crate::cdevice::{device_from_opaque_ptr, CDevicePtr};
+use crate::device::{SansIO, SansIOQueue};
+use crate::ffi::{guard, guard_or, pop_frame_to_host, set_last_error};
+use crate::link::{attach_device, detach_device, network_from_opaque_ptr, MAX_ROUNDS_PER_TICK};
+use crate::nat::Nat;
+use crate::result_codes::ResultCode;
+use crate::socket::icmp::{icmp_error_packet_type_from_mac_addr, ICMPErrorPacketType};
+
+/// Returned by `routerAddPort` when the port cannot be added.
+pub const ROUTER_NO_PORT: usize = !0;
+
+/// How long a learned neighbour is used without being heard from again.
+const NEIGHBOUR_LIFETIME: Duration = Duration::from_secs(60);
+
+/// How long a packet waits for the address of its next hop to be resolved before it is given up.
+const RESOLUTION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often an ARP request or a neighbour solicitation is repeated while packets wait for the answer.
+const RESOLUTION_RETRY: Duration = Duration::from_secs(1);
+
+/// Max count of packets waiting for the resolution of their next hops on a port, the oldest ones are dropped after it.
+const PENDING_LIMIT: usize = 64;
+
+/// The hop limit of the packets the router originates.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// The hop limit neighbour discovery messages are sent and accepted with.
+const NDISC_HOP_LIMIT: u8 = 255;
+
+/// How much of the payload of the offending packet an ICMP error quotes: as much as fits into the minimal datagram every host accepts, 576 bytes for IPv4 and 1280 bytes for IPv6. smoltcp quotes the same and rejects the errors whose quote is shorter than the packet it describes.
+const ICMPV4_ERROR_QUOTE: usize = 576 - 20 - 8 - 20;
+const ICMPV6_ERROR_QUOTE: usize = 1280 - 40 - 8 - 40;
+
+/// What a router has done so far.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CRouterStats {
+	/// IP packets that entered the router.
+	pub packets: u64,
+	pub forwarded: u64,
+	/// Packets addressed to the router itself, including neighbour discovery and echo requests.
+	pub local: u64,
+	/// Packets dropped because their TTL or hop limit ran out, with an ICMP Time Exceeded sent back.
+	pub time_exceeded: u64,
+	/// Packets dropped because there was no route, their next hop could not be resolved or they were too big, with an ICMP Destination Unreachable or Packet Too Big sent back.
+	pub unreachable: u64,
+	/// Packets dropped silently: malformed ones, ones that must not be forwarded and ones that did not fit into the queues.
+	pub dropped: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Neighbour {
+	hardware_addr: EthernetAddress,
+	expires_at: Instant,
+}
+
+/// A packet waiting for the hardware address of its next hop.
+#[derive(Debug)]
+struct PendingPacket {
+	next_hop: IpAddress,
+	packet: Vec<u8>,
+	/// The port the packet came in through, the one an error about it goes out of, or `None` if the router originated it.
+	from: Option<usize>,
+	since: Instant,
+}
+
+/// An interface of the router, with its own frame queues.
+#[derive(Debug)]
+pub struct RouterPort {
+	device: SansIO,
+	/// The largest IP packet the port can send.
+	ip_mtu: usize,
+	/// Only set for `Medium::Ethernet`.
+	hardware_addr: Option<EthernetAddress>,
+	addresses: Vec<IpCidr>,
+	neighbours: BTreeMap<IpAddress, Neighbour>,
+	pending: Vec<PendingPacket>,
+	/// When the addresses of next hops were last asked for.
+	solicited: BTreeMap<IpAddress, Instant>,
+	/// The device the port is connected to, if any. Otherwise the host moves the frames of the port.
+	peer: Option<CDevicePtr>,
+}
+
+impl RouterPort {
+	fn new(medium: Medium, hardware_addr: EthernetAddress, ip_mtu: usize) -> RouterPort {
+		RouterPort {
+			device: SansIO::new(ip_mtu, medium),
+			ip_mtu,
+			hardware_addr: if medium == Medium::Ethernet { Some(hardware_addr) } else { None },
+			addresses: Vec::new(),
+			neighbours: BTreeMap::new(),
+			pending: Vec::new(),
+			solicited: BTreeMap::new(),
+			peer: None,
+		}
+	}
+
+	fn has_address(&self, addr: IpAddress) -> bool {
+		self.addresses.iter().any(|cidr| cidr.address() == addr)
+	}
+
+	fn ipv4_address(&self, near: Option<Ipv4Address>) -> Option<Ipv4Address> {
+		let mut first = None;
+		for cidr in &self.addresses {
+			if let IpAddress::Ipv4(addr) = cidr.address() {
+				if near.is_some_and(|near| cidr.contains_addr(&IpAddress::Ipv4(near))) {
+					return Some(addr);
+				}
+				first = first.or(Some(addr));
+			}
+		}
+		first
+	}
+
+	fn ipv6_address(&self, link_local: bool) -> Option<Ipv6Address> {
+		let mut first = None;
+		for cidr in &self.addresses {
+			if let IpAddress::Ipv6(addr) = cidr.address() {
+				if addr.is_link_local() == link_local {
+					return Some(addr);
+				}
+				first = first.or(Some(addr));
+			}
+		}
+		first
+	}
+
+	/// The hardware address of `next_hop`, if it is known or is a broadcast or multicast one.
+	fn resolve(&self, next_hop: IpAddress, now: Instant) -> Option<EthernetAddress> {
+		match next_hop {
+			IpAddress::Ipv4(addr) if addr.is_broadcast() => return Some(EthernetAddress::BROADCAST),
+			IpAddress::Ipv4(addr) if addr.is_multicast() => {
+				let b = addr.as_bytes();
+				return Some(EthernetAddress([0x01, 0x00, 0x5e, b[1] & 0x7f, b[2], b[3]]));
+			}
+			IpAddress::Ipv6(addr) if addr.is_multicast() => {
+				let b = addr.as_bytes();
+				return Some(EthernetAddress([0x33, 0x33, b[12], b[13], b[14], b[15]]));
+			}
+			_ => {}
+		}
+		match self.neighbours.get(&next_hop) {
+			Some(neighbour) if neighbour.expires_at > now => Some(neighbour.hardware_addr),
+			_ => None,
+		}
+	}
+
+	fn learn(&mut self, addr: IpAddress, hardware_addr: EthernetAddress, now: Instant) {
+		if !hardware_addr.is_unicast() {
+			return;
+		}
+		self.neighbours.insert(
+			addr,
+			Neighbour {
+				hardware_addr,
+				expires_at: now + NEIGHBOUR_LIFETIME,
+			},
+		);
+	}
+
+	/// Puts an Ethernet frame carrying `payload` into the TX queue.
+	fn send_ethernet(&mut self, dst: EthernetAddress, ethertype: EthernetProtocol, payload: &[u8]) -> bool {
+		let repr = EthernetRepr {
+			src_addr: self.hardware_addr.unwrap_or(EthernetAddress([0; 6])),
+			dst_addr: dst,
+			ethertype,
+		};
+		let mut frame = vec![0; repr.buffer_len() + payload.len()];
+		repr.emit(&mut EthernetFrame::new_unchecked(&mut frame[..]));
+		frame[repr.buffer_len()..].copy_from_slice(payload);
+		self.device.tx.push(frame).is_ok()
+	}
+}
+
+/// A static route, used when it matches the destination with a longer prefix than the subnets of the ports do.
+#[derive(Debug, Clone, Copy)]
+struct Route {
+	cidr: IpCidr,
+	/// `None` for a destination reachable directly through the port.
+	via: Option<IpAddress>,
+	port: usize,
+}
+
+/// Errors the router sends back about the IPv6 packets it cannot forward.
+#[derive(Debug, Clone, Copy)]
+enum Icmpv6Error {
+	DstUnreachable(Icmpv6DstUnreachable),
+	PktTooBig(u32),
+	TimeExceeded(Icmpv6TimeExceeded),
+}
+
+/// Forwards IPv4 and IPv6 packets between its ports, the way a gateway does: resolving the next hops with ARP and neighbour discovery, decrementing TTLs and hop limits and sending ICMP errors about the packets it cannot forward. It also answers echo requests to its own addresses.
+#[derive(Debug)]
+pub struct Router {
+	ports: Vec<RouterPort>,
+	routes: Vec<Route>,
+	now: Instant,
+	checksum_caps: ChecksumCapabilities,
+	stats: CRouterStats,
+	nat: Option<Nat>,
+}
+
+impl Router {
+	pub fn new() -> Router {
+		Router {
+			ports: Vec::new(),
+			routes: Vec::new(),
+			now: Instant::from_millis(0),
+			checksum_caps: ChecksumCapabilities::default(),
+			stats: CRouterStats::default(),
+			nat: None,
+		}
+	}
+
+	pub fn stats(&self) -> CRouterStats {
+		self.stats
+	}
+
+	/// Adds a port sending IP packets of up to `ip_mtu` bytes. Returns its index.
+	pub fn add_port(&mut self, medium: Medium, hardware_addr: EthernetAddress, ip_mtu: usize) -> usize {
+		self.ports.push(RouterPort::new(medium, hardware_addr, ip_mtu));
+		self.ports.len() - 1
+	}
+
+	fn port_mut(&mut self, port: usize) -> Result<&mut RouterPort, String> {
+		let count = self.ports.len();
+		self.ports.get_mut(port).ok_or_else(|| format!("there is no port {}, the router has {}", port, count))
+	}
+
+	/// Gives the port an address. The subnet of the address is routed to the port.
+	pub fn add_address(&mut self, port: usize, cidr: IpCidr) -> Result<(), String> {
+		self.port_mut(port)?.addresses.push(cidr);
+		Ok(())
+	}
+
+	/// Routes `cidr` to the port, through the gateway `via` if given.
+	pub fn add_route(&mut self, cidr: IpCidr, via: Option<IpAddress>, port: usize) -> Result<(), String> {
+		self.port_mut(port)?;
+		if let Some(via) = via {
+			if via.version() != cidr.address().version() {
+				return Err(format!("the gateway {} is not of the version of the destination {}", via, cidr));
+			}
+		}
+		self.routes.push(Route { cidr, via, port });
+		Ok(())
+	}
+
+	/// Sets the NAT of the connections going out of one of the ports, replacing the one set before. `None` removes it.
+	pub fn set_nat(&mut self, nat: Option<Nat>) -> Result<(), String> {
+		if let Some(nat) = &nat {
+			let port = self.port_mut(nat.outside_port())?;
+			if !port.has_address(IpAddress::Ipv4(nat.public_addr())) {
+				return Err(format!("the public address {} is not an address of the port {}", nat.public_addr(), nat.outside_port()));
+			}
+		}
+		self.nat = nat;
+		Ok(())
+	}
+
+	pub fn nat(&self) -> Option<&Nat> {
+		self.nat.as_ref()
+	}
+
+	/// Connects a device of the medium of the port to it. The device must transmit into its TX queue and stay alive until it is disconnected.
+	///
+	/// # Safety
+	/// `device` must point to a live `CDevice`.
+	pub unsafe fn connect(&mut self, port: usize, device: CDevicePtr) -> Result<(), String> {
+		let router_port = self.port_mut(port)?;
+		if router_port.peer.is_some() {
+			return Err(format!("the port {} is already connected", port));
+		}
+		attach_device(device_from_opaque_ptr(device), Some(router_port.device.medium()))?;
+		router_port.peer = Some(device);
+		Ok(())
+	}
+
+	/// Disconnects the device connected to the port.
+	pub fn disconnect(&mut self, port: usize) -> Result<(), String> {
+		match self.port_mut(port)?.peer.take() {
+			Some(device) => {
+				detach_device(unsafe { device_from_opaque_ptr(device) });
+				Ok(())
+			}
+			None => Err(format!("the port {} is not connected", port)),
+		}
+	}
+
+	/// The port and the next hop to send a packet to `dst` through, by the longest prefix match among the subnets of the ports and the static routes.
+	fn lookup(&self, dst: IpAddress) -> Option<(usize, IpAddress)> {
+		let mut best: Option<(u8, usize, IpAddress)> = None;
+		for (index, port) in self.ports.iter().enumerate() {
+			for cidr in &port.addresses {
+				if cidr.contains_addr(&dst) && best.is_none_or(|(prefix_len, _, _)| cidr.prefix_len() > prefix_len) {
+					best = Some((cidr.prefix_len(), index, dst));
+				}
+			}
+		}
+		for route in &self.routes {
+			if route.cidr.contains_addr(&dst) && best.is_none_or(|(prefix_len, _, _)| route.cidr.prefix_len() > prefix_len) {
+				best = Some((route.cidr.prefix_len(), route.port, route.via.unwrap_or(dst)));
+			}
+		}
+		best.map(|(_, port, next_hop)| (port, next_hop))
+	}
+
+	fn is_local(&self, addr: IpAddress) -> bool {
+		self.ports.iter().any(|port| port.has_address(addr))
+	}
+
+	/// Processes a frame that came in through `port`.
+	fn receive(&mut self, port: usize, frame: &[u8]) {
+		match self.ports[port].device.medium() {
+			Medium::Ethernet => {
+				let ethernet = match EthernetFrame::new_checked(frame) {
+					Ok(ethernet) => ethernet,
+					Err(_) => {
+						self.stats.dropped += 1;
+						return;
+					}
+				};
+				let dst = ethernet.dst_addr();
+				if Some(dst) != self.ports[port].hardware_addr && !dst.is_broadcast() && !dst.is_multicast() {
+					return;
+				}
+				match ethernet.ethertype() {
+					EthernetProtocol::Arp => self.receive_arp(port, ethernet.payload()),
+					EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6 => self.receive_ip(port, ethernet.payload()),
+					_ => {}
+				}
+			}
+			_ => self.receive_ip(port, frame),
+		}
+	}
+
+	fn receive_arp(&mut self, port: usize, payload: &[u8]) {
+		let repr = match ArpPacket::new_checked(payload).and_then(|packet| ArpRepr::parse(&packet)) {
+			Ok(repr) => repr,
+			Err(_) => return,
+		};
+		if let ArpRepr::EthernetIpv4 {
+			operation,
+			source_hardware_addr,
+			source_protocol_addr,
+			target_protocol_addr,
+			..
+		} = repr
+		{
+			let now = self.now;
+			let router_port = &mut self.ports[port];
+			if !router_port.has_address(IpAddress::Ipv4(target_protocol_addr)) {
+				return;
+			}
+			router_port.learn(IpAddress::Ipv4(source_protocol_addr), source_hardware_addr, now);
+			if operation == ArpOperation::Request {
+				let reply = ArpRepr::EthernetIpv4 {
+					operation: ArpOperation::Reply,
+					source_hardware_addr: router_port.hardware_addr.unwrap_or(EthernetAddress([0; 6])),
+					source_protocol_addr: target_protocol_addr,
+					target_hardware_addr: source_hardware_addr,
+					target_protocol_addr: source_protocol_addr,
+				};
+				let mut buffer = vec![0; reply.buffer_len()];
+				reply.emit(&mut ArpPacket::new_unchecked(&mut buffer[..]));
+				if !router_port.send_ethernet(source_hardware_addr, EthernetProtocol::Arp, &buffer) {
+					self.stats.dropped += 1;
+				}
+			}
+			self.flush_pending(port);
+		}
+	}
+
+	fn receive_ip(&mut self, port: usize, packet: &[u8]) {
+		self.stats.packets += 1;
+		match packet.first().map(|b| b >> 4) {
+			Some(4) => self.receive_ipv4(port, packet),
+			Some(6) => self.receive_ipv6(port, packet),
+			_ => self.stats.dropped += 1,
+		}
+	}
+
+	fn receive_ipv4(&mut self, port: usize, packet: &[u8]) {
+		let ipv4 = match Ipv4Packet::new_checked(packet) {
+			Ok(ipv4) if ipv4.verify_checksum() => ipv4,
+			_ => {
+				self.stats.dropped += 1;
+				return;
+			}
+		};
+		let packet = &packet[..usize::from(ipv4.total_len())];
+
+		// The answers to the connections masqueraded by the NAT get in translated, the other packets coming in through the outside port only reach the router.
+		let mut translated = None;
+		if let Some(nat) = &mut self.nat {
+			if port == nat.outside_port() && ipv4.dst_addr() == nat.public_addr() {
+				let mut copy = packet.to_vec();
+				if nat.translate_inbound(&mut copy, self.now) {
+					translated = Some(copy);
+				}
+			}
+		}
+		let packet = translated.as_deref().unwrap_or(packet);
+		let ipv4 = Ipv4Packet::new_unchecked(packet);
+		let dst = ipv4.dst_addr();
+
+		if self.is_local(IpAddress::Ipv4(dst)) {
+			self.stats.local += 1;
+			self.answer_icmpv4_echo(packet);
+			return;
+		}
+		if !dst.is_unicast() {
+			self.stats.dropped += 1;
+			return;
+		}
+		if let Some(nat) = &mut self.nat {
+			if port == nat.outside_port() && translated.is_none() {
+				nat.refuse_unsolicited();
+				return;
+			}
+		}
+		if ipv4.hop_limit() <= 1 {
+			self.stats.time_exceeded += 1;
+			self.send_icmpv4_error(port, packet, ICMPErrorPacketType::TimeExceeded(Icmpv4TimeExceeded::TtlExpired));
+			return;
+		}
+		let (out, next_hop) = match self.lookup(IpAddress::Ipv4(dst)) {
+			Some(route) => route,
+			None => {
+				self.stats.unreachable += 1;
+				self.send_icmpv4_error(port, packet, ICMPErrorPacketType::DstUnreachable(Icmpv4DstUnreachable::NetUnreachable));
+				return;
+			}
+		};
+		if packet.len() > self.ports[out].ip_mtu {
+			// Fragmenting is not supported, so the packets that may be fragmented are just dropped.
+			if ipv4.dont_frag() {
+				self.stats.unreachable += 1;
+				self.send_icmpv4_error(port, packet, ICMPErrorPacketType::DstUnreachable(Icmpv4DstUnreachable::FragRequired));
+			} else {
+				self.stats.dropped += 1;
+			}
+			return;
+		}
+
+		let mut forwarded = packet.to_vec();
+		let mut ipv4 = Ipv4Packet::new_unchecked(&mut forwarded[..]);
+		ipv4.set_hop_limit(ipv4.hop_limit() - 1);
+		ipv4.fill_checksum();
+		let from_local = self.is_local(IpAddress::Ipv4(ipv4.src_addr()));
+		if let Some(nat) = &mut self.nat {
+			if out == nat.outside_port() && port != out && !from_local && !nat.translate_outbound(&mut forwarded, self.now) {
+				self.stats.dropped += 1;
+				return;
+			}
+		}
+		self.stats.forwarded += 1;
+		self.send_ip(out, next_hop, forwarded, Some(port));
+	}
+
+	fn answer_icmpv4_echo(&mut self, packet: &[u8]) {
+		let ipv4 = Ipv4Packet::new_unchecked(packet);
+		if ipv4.next_header() != IpProtocol::Icmp {
+			return;
+		}
+		let repr = match Icmpv4Packet::new_checked(ipv4.payload()).and_then(|icmp| Icmpv4Repr::parse(&icmp, &self.checksum_caps)) {
+			Ok(repr) => repr,
+			Err(_) => return,
+		};
+		if let Icmpv4Repr::EchoRequest { ident, seq_no, data } = repr {
+			self.send_icmpv4(ipv4.dst_addr(), ipv4.src_addr(), Icmpv4Repr::EchoReply { ident, seq_no, data });
+		}
+	}
+
+	/// Sends an ICMP error about `original`, which came in through `port`, to its source.
+	fn send_icmpv4_error(&mut self, port: usize, original: &[u8], tp: ICMPErrorPacketType) {
+		let ipv4 = Ipv4Packet::new_unchecked(original);
+		let dst = ipv4.src_addr();
+		// No errors about errors, broadcasts or fragments but the first one.
+		if !dst.is_unicast() || ipv4.frag_offset() != 0 {
+			return;
+		}
+		if ipv4.next_header() == IpProtocol::Icmp {
+			match Icmpv4Packet::new_checked(ipv4.payload()) {
+				Ok(icmp) if matches!(Icmpv4Repr::parse(&icmp, &self.checksum_caps), Ok(Icmpv4Repr::EchoRequest { .. })) => {}
+				_ => return,
+			}
+		}
+		let header = match Ipv4Repr::parse(&ipv4, &self.checksum_caps) {
+			Ok(header) => header,
+			Err(_) => return,
+		};
+		let src = match self.ports[port].ipv4_address(Some(dst)).or_else(|| self.ports.iter().find_map(|port| port.ipv4_address(None))) {
+			Some(src) => src,
+			None => return,
+		};
+		let quote = &ipv4.payload()[..ipv4.payload().len().min(ICMPV4_ERROR_QUOTE)];
+		let repr = icmp_error_packet_type_from_mac_addr(tp, header, quote);
+		self.send_icmpv4(src, dst, repr);
+	}
+
+	/// Sends an ICMP message originated by the router.
+	fn send_icmpv4(&mut self, src: Ipv4Address, dst: Ipv4Address, repr: Icmpv4Repr) {
+		let ip_repr = Ipv4Repr {
+			src_addr: src,
+			dst_addr: dst,
+			next_header: IpProtocol::Icmp,
+			payload_len: repr.buffer_len(),
+			hop_limit: DEFAULT_HOP_LIMIT,
+		};
+		let mut packet = vec![0; ip_repr.buffer_len() + repr.buffer_len()];
+		ip_repr.emit(&mut Ipv4Packet::new_unchecked(&mut packet[..]), &self.checksum_caps);
+		repr.emit(&mut Icmpv4Packet::new_unchecked(&mut packet[ip_repr.buffer_len()..]), &self.checksum_caps);
+		match self.lookup(IpAddress::Ipv4(dst)) {
+			Some((out, next_hop)) => self.send_ip(out, next_hop, packet, None),
+			None => self.stats.dropped += 1,
+		}
+	}
+
+	fn receive_ipv6(&mut self, port: usize, packet: &[u8]) {
+		let ipv6 = match Ipv6Packet::new_checked(packet) {
+			Ok(ipv6) => ipv6,
+			Err(_) => {
+				self.stats.dropped += 1;
+				return;
+			}
+		};
+		let packet = &packet[..ipv6.total_len()];
+		let src = ipv6.src_addr();
+		let dst = ipv6.dst_addr();
+
+		if self.is_local(IpAddress::Ipv6(dst)) || dst.is_multicast() {
+			self.stats.local += 1;
+			self.receive_icmpv6_local(port, packet);
+			return;
+		}
+		if !dst.is_unicast() || dst.is_link_local() || src.is_link_local() || src.is_unspecified() {
+			self.stats.dropped += 1;
+			return;
+		}
+		if ipv6.hop_limit() <= 1 {
+			self.stats.time_exceeded += 1;
+			self.send_icmpv6_error(port, packet, Icmpv6Error::TimeExceeded(Icmpv6TimeExceeded::HopLimitExceeded));
+			return;
+		}
+		let (out, next_hop) = match self.lookup(IpAddress::Ipv6(dst)) {
+			Some(route) => route,
+			None => {
+				self.stats.unreachable += 1;
+				self.send_icmpv6_error(port, packet, Icmpv6Error::DstUnreachable(Icmpv6DstUnreachable::NoRoute));
+				return;
+			}
+		};
+		if packet.len() > self.ports[out].ip_mtu {
+			self.stats.unreachable += 1;
+			self.send_icmpv6_error(port, packet, Icmpv6Error::PktTooBig(self.ports[out].ip_mtu as u32));
+			return;
+		}
+
+		let mut forwarded = packet.to_vec();
+		let mut ipv6 = Ipv6Packet::new_unchecked(&mut forwarded[..]);
+		ipv6.set_hop_limit(ipv6.hop_limit() - 1);
+		self.stats.forwarded += 1;
+		self.send_ip(out, next_hop, forwarded, Some(port));
+	}
+
+	/// Answers the neighbour discovery messages and the echo requests addressed to the router.
+	fn receive_icmpv6_local(&mut self, port: usize, packet: &[u8]) {
+		let ipv6 = Ipv6Packet::new_unchecked(packet);
+		if ipv6.next_header() != IpProtocol::Icmpv6 {
+			return;
+		}
+		let src = ipv6.src_addr();
+		let dst = ipv6.dst_addr();
+		let icmp = match Icmpv6Packet::new_checked(ipv6.payload()) {
+			Ok(icmp) => icmp,
+			Err(_) => return,
+		};
+		let repr = match Icmpv6Repr::parse(&IpAddress::Ipv6(src), &IpAddress::Ipv6(dst), &icmp, &self.checksum_caps) {
+			Ok(repr) => repr,
+			Err(_) => return,
+		};
+		let now = self.now;
+		match repr {
+			Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit { target_addr, lladdr }) if ipv6.hop_limit() == NDISC_HOP_LIMIT => {
+				let router_port = &mut self.ports[port];
+				let hardware_addr = match router_port.hardware_addr {
+					Some(hardware_addr) if router_port.has_address(IpAddress::Ipv6(target_addr)) => hardware_addr,
+					_ => return,
+				};
+				let (reply_dst, flags) = if src.is_unspecified() {
+					(Ipv6Address::LINK_LOCAL_ALL_NODES, NdiscNeighborFlags::ROUTER | NdiscNeighborFlags::OVERRIDE)
+				} else {
+					if let Some(lladdr) = lladdr.filter(|lladdr| lladdr.as_bytes().len() == 6) {
+						router_port.learn(IpAddress::Ipv6(src), EthernetAddress::from_bytes(lladdr.as_bytes()), now);
+					}
+					(src, NdiscNeighborFlags::ROUTER | NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE)
+				};
+				let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+					flags,
+					target_addr,
+					lladdr: Some(hardware_addr.into()),
+				});
+				self.send_icmpv6(Some(port), target_addr, reply_dst, advert, NDISC_HOP_LIMIT);
+				self.flush_pending(port);
+			}
+			Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+				target_addr,
+				lladdr: Some(lladdr),
+				..
+			}) if ipv6.hop_limit() == NDISC_HOP_LIMIT && lladdr.as_bytes().len() == 6 => {
+				self.ports[port].learn(IpAddress::Ipv6(target_addr), EthernetAddress::from_bytes(lladdr.as_bytes()), now);
+				self.flush_pending(port);
+			}
+			Icmpv6Repr::EchoRequest { ident, seq_no, data } if self.is_local(IpAddress::Ipv6(dst)) => {
+				let reply = Icmpv6Repr::EchoReply { ident, seq_no, data };
+				let out = if dst.is_link_local() { Some(port) } else { None };
+				self.send_icmpv6(out, dst, src, reply, DEFAULT_HOP_LIMIT);
+			}
+			_ => {}
+		}
+	}
+
+	/// Sends an ICMPv6 error about `original`, which came in through `port`, to its source.
+	fn send_icmpv6_error(&mut self, port: usize, original: &[u8], error: Icmpv6Error) {
+		let ipv6 = Ipv6Packet::new_unchecked(original);
+		let dst = ipv6.src_addr();
+		if !dst.is_unicast() {
+			return;
+		}
+		// No errors about errors, which have the types below 128.
+		if ipv6.next_header() == IpProtocol::Icmpv6 && ipv6.payload().first().is_none_or(|&tp| tp < 128) {
+			return;
+		}
+		let header = match Ipv6Repr::parse(&ipv6) {
+			Ok(header) => header,
+			Err(_) => return,
+		};
+		let src = match self.ports[port].ipv6_address(dst.is_link_local()) {
+			Some(src) => src,
+			None => return,
+		};
+		let data = &ipv6.payload()[..ipv6.payload().len().min(ICMPV6_ERROR_QUOTE)];
+		let repr = match error {
+			Icmpv6Error::DstUnreachable(reason) => Icmpv6Repr::DstUnreachable { reason, header, data },
+			Icmpv6Error::PktTooBig(mtu) => Icmpv6Repr::PktTooBig { mtu, header, data },
+			Icmpv6Error::TimeExceeded(reason) => Icmpv6Repr::TimeExceeded { reason, header, data },
+		};
+		let out = if dst.is_link_local() { Some(port) } else { None };
+		self.send_icmpv6(out, src, dst, repr, DEFAULT_HOP_LIMIT);
+	}
+
+	/// Sends an ICMPv6 message originated by the router, through `port` if given, by the routes otherwise.
+	fn send_icmpv6(&mut self, port: Option<usize>, src: Ipv6Address, dst: Ipv6Address, repr: Icmpv6Repr, hop_limit: u8) {
+		let ip_repr = Ipv6Repr {
+			src_addr: src,
+			dst_addr: dst,
+			next_header: IpProtocol::Icmpv6,
+			payload_len: repr.buffer_len(),
+			hop_limit,
+		};
+		let mut packet = vec![0; ip_repr.buffer_len() + repr.buffer_len()];
+		ip_repr.emit(&mut Ipv6Packet::new_unchecked(&mut packet[..]));
+		repr.emit(
+			&IpAddress::Ipv6(src),
+			&IpAddress::Ipv6(dst),
+			&mut Icmpv6Packet::new_unchecked(&mut packet[ip_repr.buffer_len()..]),
+			&self.checksum_caps,
+		);
+		let route = match port {
+			Some(port) => Some((port, IpAddress::Ipv6(dst))),
+			None => self.lookup(IpAddress::Ipv6(dst)),
+		};
+		match route {
+			Some((out, next_hop)) => self.send_ip(out, next_hop, packet, None),
+			None => self.stats.dropped += 1,
+		}
+	}
+
+	/// Sends an IP packet out of `port` to `next_hop`, holding it until the hardware address of the next hop is resolved if needed. `from` is the port the packet came in through, if it is forwarded.
+	fn send_ip(&mut self, port: usize, next_hop: IpAddress, packet: Vec<u8>, from: Option<usize>) {
+		let now = self.now;
+		let router_port = &mut self.ports[port];
+		if router_port.device.medium() != Medium::Ethernet {
+			if router_port.device.tx.push(packet).is_err() {
+				self.stats.dropped += 1;
+			}
+			return;
+		}
+		let ethertype = match next_hop {
+			IpAddress::Ipv4(_) => EthernetProtocol::Ipv4,
+			IpAddress::Ipv6(_) => EthernetProtocol::Ipv6,
+		};
+		match router_port.resolve(next_hop, now) {
+			Some(hardware_addr) => {
+				if !router_port.send_ethernet(hardware_addr, ethertype, &packet) {
+					self.stats.dropped += 1;
+				}
+			}
+			None => {
+				if router_port.pending.len() >= PENDING_LIMIT {
+					router_port.pending.remove(0);
+					self.stats.dropped += 1;
+				}
+				router_port.pending.push(PendingPacket {
+					next_hop,
+					packet,
+					from,
+					since: now,
+				});
+				let due = router_port.solicited.get(&next_hop).is_none_or(|&at| at + RESOLUTION_RETRY <= now);
+				if due {
+					self.solicit(port, next_hop);
+				}
+			}
+		}
+	}
+
+	/// Asks for the hardware address of `next_hop` on `port`.
+	fn solicit(&mut self, port: usize, next_hop: IpAddress) {
+		let now = self.now;
+		let router_port = &mut self.ports[port];
+		let hardware_addr = match router_port.hardware_addr {
+			Some(hardware_addr) => hardware_addr,
+			None => return,
+		};
+		router_port.solicited.insert(next_hop, now);
+		match next_hop {
+			IpAddress::Ipv4(target) => {
+				let src = match router_port.ipv4_address(Some(target)) {
+					Some(src) => src,
+					None => return,
+				};
+				let request = ArpRepr::EthernetIpv4 {
+					operation: ArpOperation::Request,
+					source_hardware_addr: hardware_addr,
+					source_protocol_addr: src,
+					target_hardware_addr: EthernetAddress([0; 6]),
+					target_protocol_addr: target,
+				};
+				let mut buffer = vec![0; request.buffer_len()];
+				request.emit(&mut ArpPacket::new_unchecked(&mut buffer[..]));
+				if !router_port.send_ethernet(EthernetAddress::BROADCAST, EthernetProtocol::Arp, &buffer) {
+					self.stats.dropped += 1;
+				}
+			}
+			IpAddress::Ipv6(target) => {
+				let src = match router_port.ipv6_address(true) {
+					Some(src) => src,
+					None => return,
+				};
+				let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+					target_addr: target,
+					lladdr: Some(hardware_addr.into()),
+				});
+				self.send_icmpv6(Some(port), src, target.solicited_node(), solicit, NDISC_HOP_LIMIT);
+			}
+		}
+	}
+
+	/// Sends the packets waiting on `port` whose next hops got resolved.
+	fn flush_pending(&mut self, port: usize) {
+		let now = self.now;
+		let router_port = &mut self.ports[port];
+		let pending = std::mem::take(&mut router_port.pending);
+		for waiting in pending {
+			match router_port.resolve(waiting.next_hop, now) {
+				Some(hardware_addr) => {
+					let ethertype = match waiting.next_hop {
+						IpAddress::Ipv4(_) => EthernetProtocol::Ipv4,
+						IpAddress::Ipv6(_) => EthernetProtocol::Ipv6,
+					};
+					if !router_port.send_ethernet(hardware_addr, ethertype, &waiting.packet) {
+						self.stats.dropped += 1;
+					}
+				}
+				None => router_port.pending.push(waiting),
+			}
+		}
+	}
+
+	/// Gives up the packets that have waited too long for their next hops, repeating the solicitations for the others.
+	fn expire_pending(&mut self) {
+		let now = self.now;
+		for port in 0..self.ports.len() {
+			let (expired, waiting): (Vec<PendingPacket>, Vec<PendingPacket>) =
+				std::mem::take(&mut self.ports[port].pending).into_iter().partition(|waiting| waiting.since + RESOLUTION_TIMEOUT <= now);
+			self.ports[port].pending = waiting;
+			for packet in expired {
+				self.ports[port].solicited.remove(&packet.next_hop);
+				let from = match packet.from {
+					Some(from) => from,
+					None => {
+						self.stats.dropped += 1;
+						continue;
+					}
+				};
+				self.stats.unreachable += 1;
+				match packet.next_hop {
+					IpAddress::Ipv4(_) => self.send_icmpv4_error(
+						from,
+						&packet.packet,
+						ICMPErrorPacketType::DstUnreachable(Icmpv4DstUnreachable::HostUnreachable),
+					),
+					IpAddress::Ipv6(_) => {
+						self.send_icmpv6_error(from, &packet.packet, Icmpv6Error::DstUnreachable(Icmpv6DstUnreachable::AddrUnreachable))
+					}
+				}
+			}
+			let next_hops: BTreeSet<IpAddress> = self.ports[port].pending.iter().map(|waiting| waiting.next_hop).collect();
+			for next_hop in next_hops {
+				let due = self.ports[port].solicited.get(&next_hop).is_none_or(|&at| at + RESOLUTION_RETRY <= now);
+				if due {
+					self.solicit(port, next_hop);
+				}
+			}
+			let neighbours = &mut self.ports[port].neighbours;
+			neighbours.retain(|_, neighbour| neighbour.expires_at > now);
+		}
+	}
+
+	/// Puts a frame into the RX queue of the port, to be routed at the next tick.
+	pub fn put_rx(&mut self, port: usize, frame: &[u8]) -> Result<(), String> {
+		self.port_mut(port)?.device.rx.push(frame.to_vec().into()).map_err(|err| format!("the RX queue refused the frame: {}", err))
+	}
+
+	/// Takes out the oldest frame sent out of the port, if the port is not connected to a device.
+	pub fn pop_tx(&mut self, port: usize) -> Result<Option<Vec<u8>>, String> {
+		Ok(self.port_mut(port)?.device.tx.pop())
+	}
+
+	/// The frames sent out of the port, for ports not connected to a device.
+	pub fn tx_queue(&mut self, port: usize) -> Result<&mut SansIOQueue, String> {
+		Ok(&mut self.port_mut(port)?.device.tx)
+	}
+
+	/// Polls the connected devices at `timestamp` and routes the frames they and the host have put into the ports, over and over until no frame is left in flight. Returns the count of frames routed.
+	pub fn tick(&mut self, timestamp: Instant) -> u64 {
+		self.now = timestamp;
+		self.expire_pending();
+		if let Some(nat) = &mut self.nat {
+			nat.expire(timestamp);
+		}
+		let mut routed = 0;
+		for _ in 0..MAX_ROUNDS_PER_TICK {
+			let mut frames = Vec::new();
+			for (index, port) in self.ports.iter_mut().enumerate() {
+				if let Some(peer) = port.peer {
+					let c_device = unsafe { device_from_opaque_ptr(peer) };
+					c_device.poll(timestamp);
+					for frame in c_device.device.tx.drain() {
+						if port.device.rx.push(frame.into()).is_err() {
+							self.stats.dropped += 1;
+						}
+					}
+				}
+				frames.extend(port.device.rx.drain().map(|frame| (index, frame)));
+			}
+			let idle = frames.is_empty();
+			for (index, frame) in frames {
+				routed += 1;
+				self.receive(index, frame.as_slice());
+			}
+			// Even in an idle round, as expiring the pending packets can have queued ICMP errors.
+			self.deliver_to_peers();
+			if idle {
+				break;
+			}
+		}
+		routed
+	}
+
+	/// Puts the frames the ports have transmitted into the RX queues of the devices connected to them.
+	fn deliver_to_peers(&mut self) {
+		for port in &mut self.ports {
+			if let Some(peer) = port.peer {
+				let c_device = unsafe { device_from_opaque_ptr(peer) };
+				for frame in port.device.tx.drain() {
+					if c_device.device.put_rx(frame.into()).is_err() {
+						self.stats.dropped += 1;
+					}
+				}
+			}
+		}
+	}
+}
+
+impl Default for Router {
+	fn default() -> Self {
+		Router::new()
+	}
+}
+
+impl Drop for Router {
+	fn drop(&mut self) {
+		for port in &self.ports {
+			if let Some(peer) = port.peer {
+				detach_device(unsafe { device_from_opaque_ptr(peer) });
+			}
+		}
+	}
+}
+
+pub type CRouterPtr = *mut Router;
+
+/// Turns an error of a router method into `Illegal`.
+fn router_result(fn_name: &str, res: Result<(), String>) -> ResultCode {
+	match res {
+		Ok(()) => ResultCode::OK,
+		Err(err) => {
+			set_last_error(ResultCode::Illegal, format!("{}: {}", fn_name, err));
+			ResultCode::Illegal
+		}
+	}
+}
+
+/// Creates a router with no ports yet.
+#[no_mangle]
+pub extern "C" fn newRouter() -> CRouterPtr {
+	guard("newRouter", || Box::into_raw(Box::new(Router::new())))
+}
+
+/// Frees the router, disconnecting the devices from it.
+#[no_mangle]
+pub extern "C" fn deleteRouter(c_router: CRouterPtr) {
+	guard("deleteRouter", || unsafe { drop(Box::from_raw(network_from_opaque_ptr(c_router))) })
+}
+
+/// Adds a port of `medium`, a `CMedium`, sending IP packets of up to `mtu` bytes, with the hardware address `mac` if the medium is `Ethernet`. Returns the index of the port, or `ROUTER_NO_PORT` if the medium is not `Ethernet` or `Ip`.
+#[no_mangle]
+pub extern "C" fn routerAddPort(c_router: CRouterPtr, medium: u8, mac: CMacAddress, mtu: usize) -> usize {
+	guard_or("routerAddPort", || ROUTER_NO_PORT, || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		let medium = match supported_medium(medium) {
+			Some(medium) => medium,
+			None => {
+				set_last_error(ResultCode::Illegal, format!("routerAddPort: the medium {} cannot be routed", medium));
+				return ROUTER_NO_PORT;
+			}
+		};
+		router.add_port(medium, mac.into(), mtu)
+	})
+}
+
+/// Gives the port an address, the router answers ARP, neighbour solicitations and echo requests for it. The subnet of the address is routed to the port. Returns `Illegal` if there is no such port.
+#[no_mangle]
+pub extern "C" fn routerPortAddAddress(c_router: CRouterPtr, port: usize, address: CInterface) -> ResultCode {
+	guard("routerPortAddAddress", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		router_result("routerPortAddAddress", router.add_address(port, address.into()))
+	})
+}
+
+/// Routes the `destination` subnet to the port, through `gateway` unless it is unspecified. Returns `Illegal` if there is no such port or the gateway is of another IP version than the destination.
+#[no_mangle]
+pub extern "C" fn routerAddRoute(c_router: CRouterPtr, destination: CInterface, gateway: CAddress, port: usize) -> ResultCode {
+	guard("routerAddRoute", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		let gateway = IpAddress::from(gateway);
+		let via = if gateway.is_unspecified() { None } else { Some(gateway) };
+		router_result("routerAddRoute", router.add_route(destination.into(), via, port))
+	})
+}
+
+/// Connects the device to the port, so the frames transmitted by each go to the other at each tick. The device must have the medium of the port and transmit into its TX queue, as for `linkAttach`. It cannot be freed or given another TX sink until disconnected. Returns `Illegal` otherwise, or if the port is connected already.
+#[no_mangle]
+pub extern "C" fn routerConnect(c_router: CRouterPtr, port: usize, c_device: CDevicePtr) -> ResultCode {
+	guard("routerConnect", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		unsafe { device_from_opaque_ptr(c_device) };
+		router_result("routerConnect", unsafe { router.connect(port, c_device) })
+	})
+}
+
+/// Disconnects the device connected to the port. Returns `Illegal` if none is.
+#[no_mangle]
+pub extern "C" fn routerDisconnect(c_router: CRouterPtr, port: usize) -> ResultCode {
+	guard("routerDisconnect", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		router_result("routerDisconnect", router.disconnect(port))
+	})
+}
+
+/// Puts a frame into the RX queue of the port, to be routed at the next tick. Returns `Illegal` if there is no such port.
+#[no_mangle]
+pub extern "C" fn routerPutRxPacket(c_router: CRouterPtr, port: usize, src: *const u8, size: u32) -> ResultCode {
+	guard("routerPutRxPacket", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		let src = host_slice!("routerPutRxPacket", src, size);
+		router_result("routerPutRxPacket", router.put_rx(port, src))
+	})
+}
+
+/// Copies the oldest frame sent out of the port into `dst` and removes it, for ports not connected to a device. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is kept and `BufferInsufficient` is returned; if there is no frame, `Exhausted` is returned; if there is no such port, `Illegal` is returned.
+#[no_mangle]
+pub extern "C" fn routerGetTxPacket(
+	c_router: CRouterPtr,
+	port: usize,
+	dst: *mut u8,
+	size: u32,
+	frame_size: *mut usize,
+) -> ResultCode {
+	guard("routerGetTxPacket", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		match router.tx_queue(port) {
+			Ok(queue) => unsafe { pop_frame_to_host("routerGetTxPacket", queue, dst, size, frame_size) },
+			Err(err) => router_result("routerGetTxPacket", Err(err)),
+		}
+	})
+}
+
+/// Polls the devices connected to the router at the moment `millis` of the caller's clock and routes the frames they and the host put into the ports, see `Router::tick`. Returns the count of frames routed.
+#[no_mangle]
+pub extern "C" fn routerTick(c_router: CRouterPtr, millis: i64) -> u64 {
+	guard("routerTick", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		router.tick(Instant::from_millis(millis))
+	})
+}
+
+/// Writes what the router has done so far into `stats`.
+#[no_mangle]
+pub extern "C" fn routerGetStats(c_router: CRouterPtr, stats: *mut CRouterStats) -> ResultCode {
+	guard("routerGetStats", || {
+		let router = unsafe { network_from_opaque_ptr(c_router) };
+		match unsafe { stats.as_mut() } {
+			Some(stats) => {
+				*stats = router.stats();
+				ResultCode::OK
+			}
+			None => {
+				set_last_error(ResultCode::Illegal, "routerGetStats: got NULL stats pointer".to_string());
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use smoltcp::iface::InterfaceBuilder;
+
+	use super::*;
+	use crate::cdevice::{CDevice, CMedium};
+	use crate::nat::CNatConfig;
+
+	const ROUTER_MAC: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 1]);
+	const HOST_MAC: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 2]);
+	const INSIDE_HOST: Ipv4Address = Ipv4Address([10, 0, 0, 2]);
+
+	fn ipv4_packet(src: Ipv4Address, dst: Ipv4Address, hop_limit: u8) -> Vec<u8> {
+		let repr = Ipv4Repr {
+			src_addr: src,
+			dst_addr: dst,
+			next_header: IpProtocol::Udp,
+			payload_len: 12,
+			hop_limit,
+		};
+		let mut packet = vec![0x5a; repr.buffer_len() + 12];
+		repr.emit(&mut Ipv4Packet::new_unchecked(&mut packet[..]), &ChecksumCapabilities::default());
+		packet
+	}
+
+	/// A router with an IP port 0 on 10.0.0.0/24 and a port 1 on 10.0.1.0/24, of `medium`.
+	fn router(medium: Medium) -> Router {
+		let mut router = Router::new();
+		router.add_port(Medium::Ip, EthernetAddress([0; 6]), 1500);
+		router.add_port(medium, ROUTER_MAC, 1500);
+		router.add_address(0, IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24)).unwrap();
+		router.add_address(1, IpCidr::new(IpAddress::v4(10, 0, 1, 1), 24)).unwrap();
+		router
+	}
+
+	fn drain(router: &mut Router, port: usize) -> Vec<Vec<u8>> {
+		std::iter::from_fn(|| router.pop_tx(port).unwrap()).collect()
+	}
+
+	/// The type and code of the ICMP message carried by an IPv4 packet.
+	fn icmp_type_and_code(packet: &[u8]) -> (u8, u8) {
+		let ipv4 = Ipv4Packet::new_checked(packet).unwrap();
+		assert_eq!(ipv4.next_header(), IpProtocol::Icmp);
+		let payload = ipv4.payload();
+		(payload[0], payload[1])
+	}
+
+	fn arp(frame: &[u8]) -> Option<ArpRepr> {
+		let ethernet = EthernetFrame::new_checked(frame).ok()?;
+		if ethernet.ethertype() != EthernetProtocol::Arp {
+			return None;
+		}
+		ArpRepr::parse(&ArpPacket::new_checked(ethernet.payload()).ok()?).ok()
+	}
+
+	#[test]
+	fn packets_are_forwarded_with_their_ttl_decremented() {
+		let mut router = router(Medium::Ip);
+		let packet = ipv4_packet(INSIDE_HOST, Ipv4Address::new(10, 0, 1, 5), 64);
+		router.put_rx(0, &packet).unwrap();
+		assert_eq!(router.tick(Instant::from_millis(0)), 1);
+
+		let forwarded = drain(&mut router, 1);
+		assert_eq!(forwarded.len(), 1);
+		let ipv4 = Ipv4Packet::new_checked(&forwarded[0][..]).unwrap();
+		assert_eq!(ipv4.hop_limit(), 63);
+		assert!(ipv4.verify_checksum());
+		assert_eq!(&forwarded[0][20..], &packet[20..]);
+		assert!(drain(&mut router, 0).is_empty());
+		assert_eq!(router.stats().forwarded, 1);
+	}
+
+	#[test]
+	fn expired_ttls_and_unknown_networks_are_answered_with_icmp_errors() {
+		let mut router = router(Medium::Ip);
+		router.put_rx(0, &ipv4_packet(INSIDE_HOST, Ipv4Address::new(10, 0, 1, 5), 1)).unwrap();
+		router.put_rx(0, &ipv4_packet(INSIDE_HOST, Ipv4Address::new(192, 0, 2, 1), 64)).unwrap();
+		router.tick(Instant::from_millis(0));
+
+		assert!(drain(&mut router, 1).is_empty());
+		let errors = drain(&mut router, 0);
+		assert_eq!(errors.len(), 2);
+		for error in &errors {
+			let ipv4 = Ipv4Packet::new_checked(&error[..]).unwrap();
+			assert_eq!((ipv4.src_addr(), ipv4.dst_addr()), (Ipv4Address::new(10, 0, 0, 1), INSIDE_HOST));
+		}
+		// Time exceeded in transit, then network unreachable.
+		assert_eq!(icmp_type_and_code(&errors[0]), (11, 0));
+		assert_eq!(icmp_type_and_code(&errors[1]), (3, 0));
+		let stats = router.stats();
+		assert_eq!((stats.time_exceeded, stats.unreachable, stats.forwarded), (1, 1, 0));
+	}
+
+	#[test]
+	fn next_hops_are_resolved_once_each_and_given_up_after_a_while() {
+		let mut router = router(Medium::Ethernet);
+		let resolved = Ipv4Address::new(10, 0, 1, 5);
+		let silent = Ipv4Address::new(10, 0, 1, 6);
+		for dst in [resolved, silent, resolved] {
+			router.put_rx(0, &ipv4_packet(INSIDE_HOST, dst, 64)).unwrap();
+		}
+		router.tick(Instant::from_millis(0));
+		let targets = |frames: Vec<Vec<u8>>| -> Vec<Ipv4Address> {
+			frames
+				.iter()
+				.map(|frame| match arp(frame) {
+					Some(ArpRepr::EthernetIpv4 {
+						operation: ArpOperation::Request,
+						target_protocol_addr,
+						..
+					}) => target_protocol_addr,
+					other => panic!("expected an ARP request, got {:?}", other),
+				})
+				.collect()
+		};
+		assert_eq!(targets(drain(&mut router, 1)), vec![resolved, silent]);
+
+		// The requests are repeated once per next hop.
+		router.tick(Instant::from_millis(1000));
+		assert_eq!(targets(drain(&mut router, 1)), vec![resolved, silent]);
+
+		let reply = ArpRepr::EthernetIpv4 {
+			operation: ArpOperation::Reply,
+			source_hardware_addr: HOST_MAC,
+			source_protocol_addr: resolved,
+			target_hardware_addr: ROUTER_MAC,
+			target_protocol_addr: Ipv4Address::new(10, 0, 1, 1),
+		};
+		let ethernet = EthernetRepr {
+			src_addr: HOST_MAC,
+			dst_addr: ROUTER_MAC,
+			ethertype: EthernetProtocol::Arp,
+		};
+		let mut frame = vec![0; ethernet.buffer_len() + reply.buffer_len()];
+		ethernet.emit(&mut EthernetFrame::new_unchecked(&mut frame[..]));
+		reply.emit(&mut ArpPacket::new_unchecked(&mut frame[ethernet.buffer_len()..]));
+		router.put_rx(1, &frame).unwrap();
+		router.tick(Instant::from_millis(1500));
+
+		let sent = drain(&mut router, 1);
+		assert_eq!(sent.len(), 2);
+		for frame in &sent {
+			let ethernet = EthernetFrame::new_checked(&frame[..]).unwrap();
+			assert_eq!((ethernet.dst_addr(), ethernet.ethertype()), (HOST_MAC, EthernetProtocol::Ipv4));
+			assert_eq!(Ipv4Packet::new_checked(ethernet.payload()).unwrap().dst_addr(), resolved);
+		}
+
+		router.tick(Instant::from_millis(3000));
+		let errors = drain(&mut router, 0);
+		assert_eq!(errors.len(), 1);
+		// Host unreachable.
+		assert_eq!(icmp_type_and_code(&errors[0]), (3, 1));
+		assert_eq!(router.stats().unreachable, 1);
+	}
+
+	#[test]
+	fn only_the_answers_to_masqueraded_connections_get_in_through_the_nat() {
+		let mut router = router(Medium::Ip);
+		let public = Ipv4Address::new(10, 0, 1, 1);
+		let remote = Ipv4Address::new(10, 0, 1, 5);
+		router.set_nat(Some(Nat::new(1, public, CNatConfig::default()))).unwrap();
+
+		// Unsolicited, straight to the inside address.
+		router.put_rx(1, &ipv4_packet(remote, INSIDE_HOST, 64)).unwrap();
+		router.tick(Instant::from_millis(0));
+		assert!(drain(&mut router, 0).is_empty());
+		assert_eq!(router.nat().unwrap().stats().unsolicited, 1);
+
+		router.put_rx(0, &ipv4_packet(INSIDE_HOST, remote, 64)).unwrap();
+		router.tick(Instant::from_millis(10));
+		let request = drain(&mut router, 1);
+		assert_eq!(request.len(), 1);
+		assert_eq!(Ipv4Packet::new_checked(&request[0][..]).unwrap().src_addr(), public);
+
+		// The answer, with the ports of the request swapped, which are the same.
+		router.put_rx(1, &ipv4_packet(remote, public, 64)).unwrap();
+		router.tick(Instant::from_millis(20));
+		let answer = drain(&mut router, 0);
+		assert_eq!(answer.len(), 1);
+		assert_eq!(Ipv4Packet::new_checked(&answer[0][..]).unwrap().dst_addr(), INSIDE_HOST);
+		assert_eq!(router.nat().unwrap().stats().unsolicited, 1);
+	}
+
+	#[test]
+	fn ports_of_unroutable_or_unknown_mediums_are_refused() {
+		let c_router = newRouter();
+		let add_port = |medium: u8| routerAddPort(c_router, medium, CMacAddress { mac: ROUTER_MAC.0 }, 1500);
+		assert_eq!(add_port(CMedium::Ip as u8), 0);
+		assert_eq!(add_port(CMedium::Invalid as u8), ROUTER_NO_PORT);
+		assert_eq!(add_port(CMedium::Ieee802154 as u8), ROUTER_NO_PORT);
+		assert_eq!(add_port(7), ROUTER_NO_PORT);
+		assert_eq!(add_port(CMedium::Ethernet as u8), 1);
+		deleteRouter(c_router);
+	}
+
+	#[test]
+	fn icmp_errors_of_expired_resolutions_reach_connected_devices() {
+		let mut sans_io = SansIO::new(1500, Medium::Ip);
+		let iface = InterfaceBuilder::new()
+			.ip_addrs(vec![IpCidr::new(IpAddress::Ipv4(INSIDE_HOST), 24)])
+			.finalize(&mut sans_io);
+		let c_device: CDevicePtr = Box::into_raw(Box::new(CDevice::new(sans_io, iface)));
+		let device = unsafe { device_from_opaque_ptr(c_device) };
+
+		let mut router = router(Medium::Ethernet);
+		unsafe { router.connect(0, c_device) }.unwrap();
+		// As if the stack of the device had sent it, to a next hop nobody answers for.
+		device.device.tx.push(ipv4_packet(INSIDE_HOST, Ipv4Address::new(10, 0, 1, 6), 64)).unwrap();
+		router.tick(Instant::from_millis(0));
+		assert!(device.device.rx.is_empty());
+
+		// No frame comes in during this tick, the error only comes from the expiry.
+		router.tick(Instant::from_millis(3500));
+		let errors: Vec<Vec<u8>> = device.device.rx.drain().map(|frame| frame.as_slice().to_vec()).collect();
+		assert_eq!(errors.len(), 1);
+		assert_eq!(icmp_type_and_code(&errors[0]), (3, 1));
+		assert_eq!(Ipv4Packet::new_checked(&errors[0][..]).unwrap().dst_addr(), INSIDE_HOST);
+
+		router.disconnect(0).unwrap();
+		drop(unsafe { Box::from_raw(c_device) });
+	}
+}
@@ -1,19 +1,27 @@
-use std::slice;
-
-use smoltcp::iface::SocketHandle;
 //use smoltcp::iface::interface::InterfaceInner;
 
 use smoltcp::socket::dns;
 
-use super::socket::{delete_socket, wrap_socket_handle};
+use super::socket::{remove_socket, wrap_socket_handle, CSocketKind, OpaqueSocketHandle};
 use crate::address::CAddress;
 use crate::cdevice::device_from_opaque_ptr;
 use crate::cdevice::CDevicePtr;
+use crate::ffi::{guard, set_last_error};
 use smoltcp::wire::{DnsQueryType, IpAddress, Ipv4Address};
 
 use crate::result_codes::ResultCode;
 
-pub type DNSSocketHandle = SocketHandle;
+/// What the pointers to DNS sockets point to, opaque to C. It is a `CSocketHandle` of a DNS socket.
+pub struct DNSSocketHandle {
+	_private: [u8; 0],
+}
+
+impl OpaqueSocketHandle for DNSSocketHandle {}
+
+/// What the pointers returned by `dnsSocketQuery` point to, opaque to C. Behind it is a boxed `dns::QueryHandle`.
+pub struct DNSQueryHandle {
+	_private: [u8; 0],
+}
 
 error_code_enum! {
 	/// Error returned by [`Socket::start_query`]
@@ -36,48 +44,82 @@ error_code_enum! {
 
 #[no_mangle]
 pub extern "C" fn newDnsSocket(c_device: CDevicePtr, address: CAddress) -> *mut DNSSocketHandle {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let socket = dns::Socket::new(&[IpAddress::from(Ipv4Address::from(address))], vec![]);
-	let handle = c_device.sockets.add(socket);
+	guard("newDnsSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let socket = dns::Socket::new(&[IpAddress::from(Ipv4Address::from(address))], vec![]);
+		let handle = c_device.sockets.add(socket);
 
-	wrap_socket_handle(handle)
+		wrap_socket_handle(c_device, CSocketKind::Dns, handle)
+	})
 }
 
+/// Removes the socket from its device and frees the handle.
 #[no_mangle]
-pub extern "C" fn deleteDnsSocket(c_handle: *mut DNSSocketHandle) {
-	delete_socket(c_handle)
+pub extern "C" fn dnsSocketDelete(c_handle: *mut DNSSocketHandle) {
+	guard("dnsSocketDelete", || {
+		let (c_device, _) = checked_socket!(c_handle, CSocketKind::Dns);
+		remove_socket(c_device, c_handle, CSocketKind::Dns);
+	})
 }
 
+/// Same as `dnsSocketDelete`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
-pub extern "C" fn newDnsQuery(
-	c_device: CDevicePtr,
+pub extern "C" fn deleteDnsSocket(c_device: CDevicePtr, c_handle: *mut DNSSocketHandle) {
+	guard("deleteDnsSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Dns);
+		dnsSocketDelete(c_handle)
+	})
+}
+
+#[no_mangle]
+pub extern "C" fn dnsSocketQuery(
 	c_handle: *mut DNSSocketHandle,
 	name: *const u8,
 	name_size: u32,
-) -> *const dns::QueryHandle {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let cx = c_device.iface.context();
-
-	let name = unsafe { slice::from_raw_parts(name, name_size as usize) };
-	match String::from_utf8(name.to_vec()) {
-		Ok(name) => {
-			let socket = c_device.sockets.get_mut::<dns::Socket>(handle);
-
-			match socket.start_query(cx, &name, DnsQueryType::A) {
-				Ok(query) => {
-					let query_box = Box::new(query);
-					return Box::into_raw(query_box).cast();
-				}
-				Err(_err) => {
-					return 0 as *const dns::QueryHandle;
+) -> *const DNSQueryHandle {
+	guard("dnsSocketQuery", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Dns);
+		let cx = c_device.iface.context();
+
+		let name = host_slice!("dnsSocketQuery", name, name_size);
+		match String::from_utf8(name.to_vec()) {
+			Ok(name) => {
+				let socket = c_device.sockets.get_mut::<dns::Socket>(handle);
+
+				match socket.start_query(cx, &name, DnsQueryType::A) {
+					Ok(query) => {
+						let query_box = Box::new(query);
+						return Box::into_raw(query_box) as *const DNSQueryHandle;
+					}
+					Err(err) => {
+						let code: DNSStartQueryError = err.into();
+						set_last_error(code.into(), format!("dnsSocketQuery: cannot start the query: {:?}", code));
+						return 0 as *const DNSQueryHandle;
+					}
 				}
 			}
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("dnsSocketQuery: the name is not UTF-8: {}", err));
+				return 0 as *const DNSQueryHandle;
+			}
 		}
-		Err(_err) => {
-			return 0 as *const dns::QueryHandle;
-		}
-	}
+	})
+}
+
+/// Same as `dnsSocketQuery`, with the device passed along. The device must be the one of the socket.
+#[no_mangle]
+pub extern "C" fn newDnsQuery(
+	c_device: CDevicePtr,
+	c_handle: *mut DNSSocketHandle,
+	name: *const u8,
+	name_size: u32,
+) -> *const DNSQueryHandle {
+	guard("newDnsQuery", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Dns);
+		dnsSocketQuery(c_handle, name, name_size)
+	})
 }
 /*
 #[no_mangle]
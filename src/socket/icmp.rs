@@ -1,6 +1,3 @@
-use std::slice;
-
-use smoltcp::iface::SocketHandle;
 //use smoltcp::iface::interface::InterfaceInner;
 use smoltcp::phy::ChecksumCapabilities;
 
@@ -8,13 +5,19 @@ use smoltcp::socket::icmp;
 
 use smoltcp::wire::{Icmpv4DstUnreachable, Icmpv4Packet, Icmpv4Repr, Icmpv4TimeExceeded, Ipv4Repr};
 
-use super::socket::{delete_socket, wrap_socket_handle};
+use super::socket::{remove_socket, wrap_socket_handle, CSocketKind, OpaqueSocketHandle};
 use crate::address::{CAddress, CIPEndpoint};
 use crate::cdevice::device_from_opaque_ptr;
 use crate::cdevice::CDevicePtr;
+use crate::ffi::{guard, guard_or, host_slice, host_slice_mut, set_last_error};
 use crate::result_codes::ResultCode;
 
-pub type ICMPSocketHandle = SocketHandle;
+/// What the pointers to ICMP sockets point to, opaque to C. It is a `CSocketHandle` of an ICMP socket.
+pub struct ICMPSocketHandle {
+	_private: [u8; 0],
+}
+
+impl OpaqueSocketHandle for ICMPSocketHandle {}
 
 error_code_enum! {
 	/// Error returned by [`Socket::bind`]
@@ -41,17 +44,34 @@ error_code_enum! {
 
 #[no_mangle]
 pub extern "C" fn newIcmpSocket(c_device: CDevicePtr) -> *mut ICMPSocketHandle {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let rx = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
-	let tx = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
-	let socket = icmp::Socket::new(rx, tx);
+	guard("newIcmpSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		let rx = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
+		let tx = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
+		let socket = icmp::Socket::new(rx, tx);
+
+		let handle = c_device.sockets.add(socket);
+		wrap_socket_handle(c_device, CSocketKind::Icmp, handle)
+	})
+}
 
-	wrap_socket_handle(c_device.sockets.add(socket))
+/// Removes the socket from its device and frees the handle.
+#[no_mangle]
+pub extern "C" fn icmpSocketDelete(c_handle: *mut ICMPSocketHandle) {
+	guard("icmpSocketDelete", || {
+		let (c_device, _) = checked_socket!(c_handle, CSocketKind::Icmp);
+		remove_socket(c_device, c_handle, CSocketKind::Icmp);
+	})
 }
 
+/// Same as `icmpSocketDelete`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
-pub extern "C" fn deleteIcmpSocket(c_handle: *mut ICMPSocketHandle) {
-	delete_socket(c_handle)
+pub extern "C" fn deleteIcmpSocket(c_device: CDevicePtr, c_handle: *mut ICMPSocketHandle) {
+	guard("deleteIcmpSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Icmp);
+		icmpSocketDelete(c_handle)
+	})
 }
 
 #[repr(u8)]
@@ -64,10 +84,8 @@ fn icmp_echo_packet_type_from_mac_addr<'a>(
 	tp: ICMPEchoPacketType,
 	ident: u16,
 	seq_no: u16,
-	payload: *const u8,
-	payload_size: u32,
+	payload_slc: &'a [u8],
 ) -> Icmpv4Repr<'a> {
-	let payload_slc = unsafe { slice::from_raw_parts(payload, payload_size as usize) };
 	match tp {
 		ICMPEchoPacketType::EchoRequest => Icmpv4Repr::EchoRequest {
 			ident: ident,
@@ -82,13 +100,11 @@ fn icmp_echo_packet_type_from_mac_addr<'a>(
 	}
 }
 
-fn finalize_icmp_packet<'a>(icmp_repr: Icmpv4Repr<'a>, dst: *mut u8, dst_size: u32) -> u32 {
-	let dst_size = dst_size as usize;
+fn finalize_icmp_packet<'a>(icmp_repr: Icmpv4Repr<'a>, mut dst_slc: &mut [u8]) -> u32 {
 	let needed_len = icmp_repr.buffer_len();
-	if dst_size < needed_len {
+	if dst_slc.len() < needed_len {
 		return needed_len.try_into().unwrap();
 	} else {
-		let mut dst_slc = unsafe { slice::from_raw_parts_mut(dst, dst_size) };
 		icmp_repr.emit(
 			&mut Icmpv4Packet::new_unchecked(&mut dst_slc),
 			&ChecksumCapabilities::default(),
@@ -97,6 +113,7 @@ fn finalize_icmp_packet<'a>(icmp_repr: Icmpv4Repr<'a>, dst: *mut u8, dst_size: u
 	}
 }
 
+/// Writes an echo packet into `dst`. Returns 0 if it fits, or the size it needs if `dst_size` is too small. Returns `u32::MAX` if a buffer is NULL while its size is not 0.
 #[no_mangle]
 pub extern "C" fn buildIcmpV4EchoPacket(
 	tp: ICMPEchoPacketType,
@@ -107,11 +124,16 @@ pub extern "C" fn buildIcmpV4EchoPacket(
 	dst: *mut u8,
 	dst_size: u32,
 ) -> u32 {
-	finalize_icmp_packet(
-		icmp_echo_packet_type_from_mac_addr(tp, ident, seq_no, payload, payload_size),
-		dst,
-		dst_size,
-	)
+	guard_or("buildIcmpV4EchoPacket", || u32::MAX, || {
+		let (payload, dst) = match unsafe { (host_slice(payload, payload_size as usize), host_slice_mut(dst, dst_size as usize)) } {
+			(Some(payload), Some(dst)) => (payload, dst),
+			_ => {
+				set_last_error(ResultCode::Illegal, "buildIcmpV4EchoPacket: got a NULL buffer".to_string());
+				return u32::MAX;
+			}
+		};
+		finalize_icmp_packet(icmp_echo_packet_type_from_mac_addr(tp, ident, seq_no, payload), dst)
+	})
 }
 
 /*enum_with_unknown! {
@@ -130,13 +152,11 @@ pub enum ICMPErrorPacketType {
 	TimeExceeded(Icmpv4TimeExceeded),
 }
 
-fn icmp_error_packet_type_from_mac_addr<'a>(
+pub(crate) fn icmp_error_packet_type_from_mac_addr<'a>(
 	tp: ICMPErrorPacketType,
 	header: Ipv4Repr,
-	payload: *const u8,
-	payload_size: u32,
+	payload_slc: &'a [u8],
 ) -> Icmpv4Repr<'a> {
-	let payload_slc = unsafe { slice::from_raw_parts(payload, payload_size as usize) };
 	match tp {
 		ICMPErrorPacketType::DstUnreachable(reason) => Icmpv4Repr::DstUnreachable {
 			reason: reason,
@@ -152,61 +172,110 @@ fn icmp_error_packet_type_from_mac_addr<'a>(
 }
 
 #[no_mangle]
-pub extern "C" fn icmpBindAny(
-	c_device: CDevicePtr,
-	c_handle: *mut ICMPSocketHandle,
-) -> ICMPBindError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let _cx = c_device.iface.context();
+pub extern "C" fn icmpSocketBindAny(c_handle: *mut ICMPSocketHandle) -> ICMPBindError {
+	guard("icmpSocketBindAny", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Icmp);
+		let _cx = c_device.iface.context();
 
-	let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+		let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
 
-	if !socket.is_open() {
-		socket.bind(icmp::Endpoint::Unspecified).into()
-	} else {
-		ICMPBindError::OK
-	}
+		if !socket.is_open() {
+			socket.bind(icmp::Endpoint::Unspecified).into()
+		} else {
+			ICMPBindError::OK
+		}
+	})
+}
+
+/// Same as `icmpSocketBindAny`, with the device passed along. The device must be the one of the socket.
+#[no_mangle]
+pub extern "C" fn icmpBindAny(c_device: CDevicePtr, c_handle: *mut ICMPSocketHandle) -> ICMPBindError {
+	guard("icmpBindAny", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Icmp);
+		icmpSocketBindAny(c_handle)
+	})
+}
+
+#[no_mangle]
+pub extern "C" fn icmpSocketBindIdent(c_handle: *mut ICMPSocketHandle, ident: u16) -> ICMPBindError {
+	guard("icmpSocketBindIdent", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Icmp);
+		let _cx = c_device.iface.context();
+
+		let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+
+		if !socket.is_open() {
+			socket.bind(icmp::Endpoint::Ident(ident)).into()
+		} else {
+			ICMPBindError::OK
+		}
+	})
 }
 
+/// Same as `icmpSocketBindIdent`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn icmpBindIdent(
 	c_device: CDevicePtr,
 	c_handle: *mut ICMPSocketHandle,
 	ident: u16,
 ) -> ICMPBindError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let _cx = c_device.iface.context();
+	guard("icmpBindIdent", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Icmp);
+		icmpSocketBindIdent(c_handle, ident)
+	})
+}
+
+#[no_mangle]
+pub extern "C" fn icmpSocketBindUDP(c_handle: *mut ICMPSocketHandle, endpoint: CIPEndpoint) -> ICMPBindError {
+	guard("icmpSocketBindUDP", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Icmp);
+		let _cx = c_device.iface.context();
 
-	let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+		let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
 
-	if !socket.is_open() {
-		socket.bind(icmp::Endpoint::Ident(ident)).into()
-	} else {
-		ICMPBindError::OK
-	}
+		if !socket.is_open() {
+			socket.bind(icmp::Endpoint::Udp(endpoint.into())).into()
+		} else {
+			ICMPBindError::OK
+		}
+	})
 }
 
+/// Same as `icmpSocketBindUDP`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn icmpBindUDP(
 	c_device: CDevicePtr,
 	c_handle: *mut ICMPSocketHandle,
 	endpoint: CIPEndpoint,
 ) -> ICMPBindError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let _cx = c_device.iface.context();
+	guard("icmpBindUDP", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Icmp);
+		icmpSocketBindUDP(c_handle, endpoint)
+	})
+}
 
-	let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+#[no_mangle]
+pub extern "C" fn icmpSocketSend(
+	c_handle: *mut ICMPSocketHandle,
+	address: CAddress,
+	data: *const u8,
+	size: u32,
+) -> ICMPSendError {
+	guard("icmpSocketSend", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Icmp);
+		//let cx = c_device.iface.context();
+		let slc = host_slice!("icmpSocketSend", data, size);
 
-	if !socket.is_open() {
-		socket.bind(icmp::Endpoint::Udp(endpoint.into())).into()
-	} else {
-		ICMPBindError::OK
-	}
+		let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+
+		socket.send_slice(slc, address.into()).into()
+	})
 }
 
+/// Same as `icmpSocketSend`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn icmpSend(
 	c_device: CDevicePtr,
@@ -215,41 +284,80 @@ pub extern "C" fn icmpSend(
 	data: *const u8,
 	size: u32,
 ) -> ICMPSendError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	//let cx = c_device.iface.context();
-	let slc = unsafe { slice::from_raw_parts(data, size as usize) };
+	guard("icmpSend", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Icmp);
+		icmpSocketSend(c_handle, address, data, size)
+	})
+}
+
+/// Copies the oldest datagram into `dst` and its source into `address`. The size of the datagram is written to `datagram_size` (if not NULL). If `size` is too small, `BufferInsufficient` is returned and the datagram is kept for the next call. Returns `Illegal` if `address` is NULL.
+#[no_mangle]
+pub extern "C" fn icmpSocketReceive(
+	c_handle: *mut ICMPSocketHandle,
+	address: *mut CAddress,
+	dst: *mut u8,
+	size: u32,
+	datagram_size: *mut u32,
+) -> ICMPRecvError {
+	guard("icmpSocketReceive", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Icmp);
+		//let cx = c_device.iface.context();
+		let address = match unsafe { address.as_mut() } {
+			Some(address) => address,
+			None => {
+				set_last_error(ResultCode::Illegal, "icmpSocketReceive: got NULL `address`".to_string());
+				return ICMPRecvError::Illegal;
+			}
+		};
+
+		if !c_device.icmp_pending.contains_key(&handle) {
+			// ICMP sockets cannot be peeked, so the datagram is moved out of the socket and kept until the host has got a buffer large enough for it.
+			let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+			match socket.recv() {
+				Ok((data, source_addr)) => {
+					c_device.icmp_pending.insert(handle, (data.to_vec(), source_addr));
+				}
+				Err(err) => return err.into(),
+			}
+		}
 
-	let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
+		let (data, _source_addr) = &c_device.icmp_pending[&handle];
+		let len = data.len();
+		if let Some(datagram_size) = unsafe { datagram_size.as_mut() } {
+			*datagram_size = len as u32;
+		}
+		if len > size as usize {
+			set_last_error(
+				ResultCode::BufferInsufficient,
+				format!("icmpSocketReceive: the datagram is {} bytes, but the buffer is only {}", len, size),
+			);
+			return ICMPRecvError::BufferInsufficient;
+		}
 
-	socket.send_slice(slc, address.into()).into()
+		let slc = host_slice_mut!("icmpSocketReceive", dst, len);
+		let (data, source_addr) = c_device.icmp_pending.remove(&handle).unwrap();
+		*address = source_addr.into();
+		slc.copy_from_slice(&data);
+		ICMPRecvError::OK
+	})
 }
 
+/// Same as `icmpSocketReceive`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn icmpReceive(
 	c_device: CDevicePtr,
 	c_handle: *mut ICMPSocketHandle,
-	address: &mut CAddress,
+	address: *mut CAddress,
 	dst: *mut u8,
 	size: u32,
+	datagram_size: *mut u32,
 ) -> ICMPRecvError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	//let cx = c_device.iface.context();
-
-	let socket = c_device.sockets.get_mut::<icmp::Socket>(handle);
-
-	match socket.recv() {
-		Ok((data, source_addr)) => {
-			let slc = unsafe { slice::from_raw_parts_mut(dst, size as usize) };
-			*address = source_addr.into();
-			let len = data.len();
-			let (left, _right) = slc.split_at_mut(len);
-			left.copy_from_slice(data);
-			ICMPRecvError::OK
-		}
-		Err(err) => err.into(),
-	}
+	guard("icmpReceive", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Icmp);
+		icmpSocketReceive(c_handle, address, dst, size, datagram_size)
+	})
 }
 
 /*
@@ -297,7 +405,7 @@ pub extern "C" fn icmpReceive(
 #[no_mangle]
 pub extern "C" fn ping(c_device: CDevicePtr, c_handle: *mut ICMPSocketHandle){
 	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let c_socket = unsafe { *c_handle.as_ref().unwrap() };
+	let c_socket = unsafe { handle_from_ptr(c_handle) };
 	let socket = c_device.sockets.get_mut::<icmp::Socket>(c_socket.handle);
 
 	let icmp_payload = socket.send(icmp_repr.buffer_len(), remote_addr).unwrap();
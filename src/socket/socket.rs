@@ -1,15 +1,209 @@
+use std::sync::Mutex;
+
 use smoltcp::iface::SocketHandle;
 
+use crate::cdevice::{device_from_opaque_ptr, CDevice, CDevicePtr};
+use crate::ffi::{guard, set_last_error};
+use crate::result_codes::ResultCode;
+
+/// Kind of the socket a handle refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CSocketKind {
+	Tcp = 1,
+	Udp = 2,
+	Icmp = 3,
+	Dns = 4,
+}
+
+/// What the pointers returned by the `new*Socket` functions point to, opaque to C. They are never dereferenced: a handle is the index of a slot of the socket table together with the generation of the slot, so a handle of a socket of another kind, of another device or of a deleted socket is refused with `Illegal`, and the slot of a deleted socket is reused by the next one.
+pub struct CSocketHandle {
+	_private: [u8; 0],
+}
+
+/// Implemented by the types C sees the socket handles of each kind as. They all are a `CSocketHandle`.
+pub trait OpaqueSocketHandle {}
+
+impl OpaqueSocketHandle for CSocketHandle {}
+
+/// The socket a live handle refers to.
+#[derive(Debug, Clone, Copy)]
+struct SocketEntry {
+	kind: CSocketKind,
+	/// The device owning the socket. It stays valid as long as the handle is live, since `freeDevice` is refused while the device has live handles.
+	device: CDevicePtr,
+	device_id: u64,
+	handle: SocketHandle,
+}
+
+/// The low half of the bits of a handle is the index of its slot plus 1, so that no handle is NULL. The high half is the generation of the slot.
+const INDEX_BITS: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: usize = usize::MAX >> INDEX_BITS;
+
+struct SocketSlot {
+	/// Bumped each time the socket of the slot is deleted, so that the handles of the former sockets of the slot are refused.
+	generation: usize,
+	entry: Option<SocketEntry>,
+}
+
+/// The sockets the host has handles of, of all the devices.
+struct SocketTable {
+	slots: Vec<SocketSlot>,
+	/// The indices of the slots without a socket.
+	free: Vec<usize>,
+}
+
+// The devices behind the entries are only dereferenced by the functions the host calls with their handles, from the thread it uses the device on.
+unsafe impl Send for SocketTable {}
+
+static SOCKETS: Mutex<SocketTable> = Mutex::new(SocketTable { slots: Vec::new(), free: Vec::new() });
+
+impl SocketTable {
+	fn with<R>(f: impl FnOnce(&mut SocketTable) -> R) -> R {
+		match SOCKETS.lock() {
+			Ok(mut table) => f(&mut table),
+			Err(poisoned) => f(&mut poisoned.into_inner()),
+		}
+	}
+
+	/// Puts the entry in a free slot and returns the handle of it.
+	fn insert(&mut self, entry: SocketEntry) -> usize {
+		let index = match self.free.pop() {
+			Some(index) => index,
+			None => {
+				assert!(self.slots.len() < INDEX_MASK, "too many socket handles");
+				self.slots.push(SocketSlot { generation: 0, entry: None });
+				self.slots.len() - 1
+			}
+		};
+		let slot = &mut self.slots[index];
+		slot.entry = Some(entry);
+		(slot.generation << INDEX_BITS) | (index + 1)
+	}
+
+	/// The slot the handle refers to, if it is the current generation of the slot.
+	fn slot(&mut self, c_handle: usize) -> Option<&mut SocketSlot> {
+		let index = (c_handle & INDEX_MASK).checked_sub(1)?;
+		let slot = self.slots.get_mut(index)?;
+		(slot.generation == c_handle >> INDEX_BITS).then_some(slot)
+	}
+
+	fn get(&mut self, c_handle: usize) -> Option<SocketEntry> {
+		self.slot(c_handle)?.entry
+	}
+
+	/// Empties the slot of a live handle, making the handle stale.
+	fn remove(&mut self, c_handle: usize) -> Option<SocketEntry> {
+		let slot = self.slot(c_handle)?;
+		let entry = slot.entry.take()?;
+		slot.generation = (slot.generation + 1) & GENERATION_MASK;
+		self.free.push((c_handle & INDEX_MASK) - 1);
+		Some(entry)
+	}
+}
+
 /// Deletes a socket using its handle. While you currently can use ths function to delete sockets, using specialized functions is more future-proof since I can imagine that we can have to store some auxillary info besides the handle.
 #[no_mangle]
-pub extern "C" fn delete_socket(c_handle: *mut SocketHandle) {
-	unsafe {
-		Box::from_raw(c_handle);
+pub extern "C" fn delete_socket(c_device: CDevicePtr, c_handle: *mut CSocketHandle) {
+	guard("delete_socket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		if let Some(entry) = socket_entry(c_handle) {
+			remove_socket(c_device, c_handle, entry.kind);
+		}
+	})
+}
+
+/// Registers a socket just added to the device and makes a handle for it, so C++ can use it.
+pub fn wrap_socket_handle<H: OpaqueSocketHandle>(c_device: &mut CDevice, kind: CSocketKind, handle: SocketHandle) -> *mut H {
+	let entry = SocketEntry { kind, device: (c_device as *mut CDevice).cast(), device_id: c_device.id, handle };
+	let c_handle = SocketTable::with(|table| table.insert(entry));
+	c_device.handles.insert(handle);
+	c_handle as *mut H
+}
+
+/// The entry of a live handle. Otherwise records why as the last error and returns `None`.
+fn socket_entry<H: OpaqueSocketHandle>(c_handle: *const H) -> Option<SocketEntry> {
+	if c_handle.is_null() {
+		set_last_error(ResultCode::Illegal, "got NULL socket handle pointer".to_string());
+		return None;
+	}
+	let entry = SocketTable::with(|table| table.get(c_handle as usize));
+	if entry.is_none() {
+		set_last_error(ResultCode::Illegal, "the socket of the handle was deleted".to_string());
+	}
+	entry
+}
+
+/// Gets the `SocketHandle` behind a pointer returned by one of the `new*Socket` functions, if it is a live handle of a socket of `kind` on the device. Otherwise records why as the last error and returns `None`.
+pub fn checked_handle<H: OpaqueSocketHandle>(c_device: &CDevice, c_handle: *const H, kind: CSocketKind) -> Option<SocketHandle> {
+	let entry = socket_entry(c_handle)?;
+	if entry.kind != kind {
+		set_last_error(
+			ResultCode::Illegal,
+			format!("the handle is of a {:?} socket, but a {:?} one is expected", entry.kind, kind),
+		);
+		return None;
+	}
+	if entry.device_id != c_device.id {
+		set_last_error(ResultCode::Illegal, "the socket handle belongs to another device".to_string());
+		return None;
+	}
+	Some(entry.handle)
+}
+
+/// Gets the device owning the socket and the `SocketHandle` behind a pointer returned by one of the `new*Socket` functions, if it is a live handle of a socket of `kind`. Otherwise records why as the last error and returns `None`.
+///
+/// # Safety
+/// The device of the handle must not be borrowed elsewhere.
+pub unsafe fn checked_socket<'b, 'a, H: OpaqueSocketHandle>(
+	c_handle: *const H,
+	kind: CSocketKind,
+) -> Option<(&'b mut CDevice<'a>, SocketHandle)> {
+	let c_device = device_from_opaque_ptr(socket_entry(c_handle)?.device);
+	let handle = checked_handle(c_device, c_handle, kind)?;
+	Some((c_device, handle))
+}
+
+/// Forgets the socket behind a live handle, leaving the socket itself in the device. The slot of the handle is freed for the next socket, and using the handle again or deleting it twice is refused. Returns `None` (recording why) if the handle is not valid.
+pub fn unregister_socket<H: OpaqueSocketHandle>(
+	c_device: &mut CDevice,
+	c_handle: *mut H,
+	kind: CSocketKind,
+) -> Option<SocketHandle> {
+	let handle = checked_handle(c_device, c_handle, kind)?;
+	SocketTable::with(|table| table.remove(c_handle as usize));
+	c_device.handles.remove(&handle);
+	Some(handle)
+}
+
+/// Removes the socket behind a live handle from the device, dropping its buffers, and makes the handle stale.
+pub fn remove_socket<H: OpaqueSocketHandle>(c_device: &mut CDevice, c_handle: *mut H, kind: CSocketKind) {
+	if let Some(handle) = unregister_socket(c_device, c_handle, kind) {
+		c_device.sockets.remove(handle);
+		c_device.icmp_pending.remove(&handle);
 	}
 }
 
-/// Wraps a socket handle into a box and returns a pointer, so C++ can use it.
-pub fn wrap_socket_handle(handle: SocketHandle) -> *mut SocketHandle {
-	let c_handle_box = Box::new(handle);
-	return Box::into_raw(c_handle_box).cast();
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_handles_of_deleted_sockets_stay_stale_when_their_slots_are_reused() {
+		let mut table = SocketTable { slots: Vec::new(), free: Vec::new() };
+		let entry =
+			SocketEntry { kind: CSocketKind::Udp, device: std::ptr::null_mut(), device_id: 1, handle: SocketHandle::default() };
+
+		let first = table.insert(entry);
+		assert!(table.remove(first).is_some());
+		let second = table.insert(SocketEntry { kind: CSocketKind::Tcp, ..entry });
+
+		assert_eq!(table.slots.len(), 1);
+		assert_ne!(first, second);
+		assert!(table.get(first).is_none());
+		assert!(table.remove(first).is_none());
+		assert_eq!(table.get(second).map(|entry| entry.kind), Some(CSocketKind::Tcp));
+		assert!(table.get(0).is_none());
+	}
 }
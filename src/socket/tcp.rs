@@ -1,30 +1,57 @@
-use std::slice;
-
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp;
 use smoltcp::wire::IpEndpoint;
 
-use super::socket::{delete_socket, wrap_socket_handle};
+use super::socket::{remove_socket, unregister_socket, wrap_socket_handle, CSocketKind, OpaqueSocketHandle};
 use crate::address::CIPEndpoint;
 use crate::cdevice::{device_from_opaque_ptr, CDevicePtr};
+use crate::ffi::guard;
 use crate::result_codes::ResultCode;
 
-pub type TCPSocketHandle = SocketHandle;
+/// What the pointers to TCP sockets point to, opaque to C. It is a `CSocketHandle` of a TCP socket.
+pub struct TCPSocketHandle {
+	_private: [u8; 0],
+}
+
+impl OpaqueSocketHandle for TCPSocketHandle {}
 
 #[no_mangle]
 pub extern "C" fn newTcpSocket(c_device: CDevicePtr) -> *mut TCPSocketHandle {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
+	guard("newTcpSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+
+		let socket = tcp::Socket::new(
+			tcp::SocketBuffer::new(vec![0; 1024]),
+			tcp::SocketBuffer::new(vec![0; 1024]),
+		);
+		let handle = c_device.sockets.add(socket);
+		return wrap_socket_handle(c_device, CSocketKind::Tcp, handle);
+	})
+}
 
-	let socket = tcp::Socket::new(
-		tcp::SocketBuffer::new(vec![0; 1024]),
-		tcp::SocketBuffer::new(vec![0; 1024]),
-	);
-	return wrap_socket_handle(c_device.sockets.add(socket));
+/// Frees the handle and removes the socket from its device. If `graceful` is set, the connection is closed first (sending FIN once the data enqueued so far is sent), and the socket is removed after the connection is over, during one of the later polls. Otherwise the socket is removed right away, without notifying the remote.
+#[no_mangle]
+pub extern "C" fn tcpSocketDelete(c_handle: *mut TCPSocketHandle, graceful: bool) {
+	guard("tcpSocketDelete", || {
+		let (c_device, _) = checked_socket!(c_handle, CSocketKind::Tcp);
+		if graceful {
+			if let Some(handle) = unregister_socket(c_device, c_handle, CSocketKind::Tcp) {
+				c_device.sockets.get_mut::<tcp::Socket>(handle).close();
+				c_device.closing.push(handle);
+			}
+		} else {
+			remove_socket(c_device, c_handle, CSocketKind::Tcp);
+		}
+	})
 }
 
+/// Same as `tcpSocketDelete`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
-pub extern "C" fn deleteTcpSocket(c_handle: *mut TCPSocketHandle) {
-	delete_socket(c_handle)
+pub extern "C" fn deleteTcpSocket(c_device: CDevicePtr, c_handle: *mut TCPSocketHandle, graceful: bool) {
+	guard("deleteTcpSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Tcp);
+		tcpSocketDelete(c_handle, graceful)
+	})
 }
 
 error_code_enum! {
@@ -54,6 +81,24 @@ error_code_enum! {
 	}
 }
 
+#[no_mangle]
+pub extern "C" fn tcpSocketConnect(
+	c_handle: *mut TCPSocketHandle,
+	c_endpoint: CIPEndpoint,
+	local_port: u16,
+) -> TCPConnectError {
+	guard("tcpSocketConnect", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Tcp);
+		let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+
+		let cx = c_device.iface.context();
+		socket
+			.connect(cx, IpEndpoint::from(c_endpoint), local_port)
+			.into()
+	})
+}
+
+/// Same as `tcpSocketConnect`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn tcpConnect(
 	c_device: CDevicePtr,
@@ -61,71 +106,139 @@ pub extern "C" fn tcpConnect(
 	c_endpoint: CIPEndpoint,
 	local_port: u16,
 ) -> TCPConnectError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle: TCPSocketHandle = unsafe { *c_handle.as_ref().unwrap() };
-	let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+	guard("tcpConnect", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Tcp);
+		tcpSocketConnect(c_handle, c_endpoint, local_port)
+	})
+}
 
-	let cx = c_device.iface.context();
-	socket
-		.connect(cx, IpEndpoint::from(c_endpoint), local_port)
-		.into()
+/// Enqueues up to `size` bytes of `data` for sending. The count of bytes actually enqueued, which is less than `size` if the send buffer is full, is written to `sent` (if not NULL).
+#[no_mangle]
+pub extern "C" fn tcpSocketSend(
+	c_handle: *mut TCPSocketHandle,
+	data: *const u8,
+	size: u32,
+	sent: *mut u32,
+) -> TCPSendError {
+	guard("tcpSocketSend", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Tcp);
+		let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+
+		let slc = host_slice!("tcpSocketSend", data, size);
+		let res = socket.send_slice(slc.as_ref());
+
+		let len = *res.as_ref().unwrap_or(&0);
+		if let Some(sent) = unsafe { sent.as_mut() } {
+			*sent = len as u32;
+		}
+		res.map(|_| ()).into()
+	})
 }
 
+/// Same as `tcpSocketSend`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn tcpSend(
 	c_device: CDevicePtr,
 	c_handle: *mut TCPSocketHandle,
 	data: *const u8,
 	size: u32,
-) {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+	sent: *mut u32,
+) -> TCPSendError {
+	guard("tcpSend", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Tcp);
+		tcpSocketSend(c_handle, data, size, sent)
+	})
+}
 
-	let slc = unsafe { slice::from_raw_parts(data, size as usize) };
-	socket.send_slice(slc.as_ref()).expect("cannot send");
+/// Copies up to `size` bytes of received data into `data`. Since TCP is a stream, the data not fitting the buffer just stays in the socket for the next call. The count of bytes copied is written to `received` (if not NULL).
+#[no_mangle]
+pub extern "C" fn tcpSocketReceive(
+	c_handle: *mut TCPSocketHandle,
+	data: *mut u8,
+	size: u32,
+	received: *mut u32,
+) -> TCPRecvError {
+	guard("tcpSocketReceive", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Tcp);
+		let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+
+		let slc = host_slice_mut!("tcpSocketReceive", data, size);
+
+		let res = socket.recv(|data| {
+			let len = data.len().min(slc.len());
+			slc[..len].copy_from_slice(&data[..len]);
+			(len, len)
+		});
+
+		let len = *res.as_ref().unwrap_or(&0);
+		if let Some(received) = unsafe { received.as_mut() } {
+			*received = len as u32;
+		}
+		res.map(|_| ()).into()
+
+		//	let socket = c_handle.as_ref().unwrap();
+		//let (payload, _) = socket.recv().unwrap();
+	})
 }
 
+/// Same as `tcpSocketReceive`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn tcpReceive(
 	c_device: CDevicePtr,
 	c_handle: *mut TCPSocketHandle,
 	data: *mut u8,
 	size: u32,
-) {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+	received: *mut u32,
+) -> TCPRecvError {
+	guard("tcpReceive", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Tcp);
+		tcpSocketReceive(c_handle, data, size, received)
+	})
+}
 
-	let slc = unsafe { slice::from_raw_parts_mut(data, size as usize) };
+#[no_mangle]
+pub extern "C" fn tcpSocketListen(c_handle: *mut TCPSocketHandle, port: u16) -> TCPListenError {
+	guard("tcpSocketListen", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Tcp);
+		let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
 
-	socket
-		.recv(|data| {
-			let len = data.len();
-			let (left, _right) = slc.split_at_mut(len);
-			left.copy_from_slice(data);
-			(left.len(), ())
-		})
-		.unwrap();
+		socket.listen(port).into()
+	})
+}
 
-	//	let socket = c_handle.as_ref().unwrap();
-	//let (payload, _) = socket.recv().unwrap();
+/// Same as `tcpSocketListen`, with the device passed along. The device must be the one of the socket.
+#[no_mangle]
+pub extern "C" fn tcpListen(
+	c_device: CDevicePtr,
+	c_handle: *mut TCPSocketHandle,
+	port: u16,
+) -> TCPListenError {
+	guard("tcpListen", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Tcp);
+		tcpSocketListen(c_handle, port)
+	})
 }
 
 #[no_mangle]
-pub extern "C" fn tcpListen(c_device: CDevicePtr, c_handle: *mut TCPSocketHandle, port: u16) {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
+pub extern "C" fn tcpSocketIsActive(c_handle: *mut TCPSocketHandle) -> bool {
+	guard("tcpSocketIsActive", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Tcp);
+		let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
 
-	socket.listen(port).unwrap();
+		return socket.is_active();
+	})
 }
 
+/// Same as `tcpSocketIsActive`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn tcpIsActive(c_device: CDevicePtr, c_handle: *mut TCPSocketHandle) -> bool {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let socket = c_device.sockets.get_mut::<tcp::Socket>(handle);
-
-	return socket.is_active();
+	guard("tcpIsActive", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Tcp);
+		tcpSocketIsActive(c_handle)
+	})
 }
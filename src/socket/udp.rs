@@ -1,32 +1,52 @@
-use std::slice;
-
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::udp;
 use smoltcp::wire::IpEndpoint;
 
 use crate::address::CIPEndpoint;
 use crate::cdevice::device_from_opaque_ptr;
 use crate::cdevice::CDevicePtr;
+use crate::ffi::{guard, set_last_error};
 
-use super::socket::{delete_socket, wrap_socket_handle};
+use super::socket::{remove_socket, wrap_socket_handle, CSocketKind, OpaqueSocketHandle};
 use crate::result_codes::ResultCode;
 
-pub type UDPSocketHandle = SocketHandle;
+/// What the pointers to UDP sockets point to, opaque to C. It is a `CSocketHandle` of a UDP socket.
+pub struct UDPSocketHandle {
+	_private: [u8; 0],
+}
+
+impl OpaqueSocketHandle for UDPSocketHandle {}
 
 #[no_mangle]
 pub extern "C" fn newUdpSocket(c_device: CDevicePtr) -> *mut UDPSocketHandle {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
+	guard("newUdpSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
 
-	let rx = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 65535]);
-	let tx = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 65535]);
-	let socket = udp::Socket::new(rx, tx);
+		let rx = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 65535]);
+		let tx = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 65535]);
+		let socket = udp::Socket::new(rx, tx);
+
+		let handle = c_device.sockets.add(socket);
+		return wrap_socket_handle(c_device, CSocketKind::Udp, handle);
+	})
+}
 
-	return wrap_socket_handle(c_device.sockets.add(socket));
+/// Removes the socket from its device and frees the handle.
+#[no_mangle]
+pub extern "C" fn udpSocketDelete(c_handle: *mut UDPSocketHandle) {
+	guard("udpSocketDelete", || {
+		let (c_device, _) = checked_socket!(c_handle, CSocketKind::Udp);
+		remove_socket(c_device, c_handle, CSocketKind::Udp);
+	})
 }
 
+/// Same as `udpSocketDelete`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
-pub extern "C" fn deleteUdpSocket(c_handle: *mut UDPSocketHandle) {
-	delete_socket(c_handle)
+pub extern "C" fn deleteUdpSocket(c_device: CDevicePtr, c_handle: *mut UDPSocketHandle) {
+	guard("deleteUdpSocket", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Udp);
+		udpSocketDelete(c_handle)
+	})
 }
 
 error_code_enum! {
@@ -52,90 +72,164 @@ error_code_enum! {
 	}
 }
 
+#[no_mangle]
+pub extern "C" fn udpSocketBind(c_handle: *mut UDPSocketHandle, port: u16) -> UDPBindError {
+	guard("udpSocketBind", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Udp);
+		let _cx = c_device.iface.context();
+
+		let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
+
+		if !socket.is_open() {
+			socket.bind(port).into()
+		} else {
+			UDPBindError::OK
+		}
+	})
+}
+
+/// Same as `udpSocketBind`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn udpBind(
 	c_device: CDevicePtr,
 	c_handle: *mut UDPSocketHandle,
 	port: u16,
 ) -> UDPBindError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	let _cx = c_device.iface.context();
+	guard("udpBind", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Udp);
+		udpSocketBind(c_handle, port)
+	})
+}
 
-	let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
+#[no_mangle]
+pub extern "C" fn udpSocketGetLastReceivedPacketSize(c_handle: *mut UDPSocketHandle) -> u32 {
+	guard("udpSocketGetLastReceivedPacketSize", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Udp);
+		//let cx = c_device.iface.context();
+
+		let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
+
+		match socket.peek() {
+			Ok((data, _source_endpoint)) => {
+				return data.len() as u32;
+			}
+			Err(_) => {
+				return 0;
+			}
+		};
+	})
+}
 
-	if !socket.is_open() {
-		socket.bind(port).into()
-	} else {
-		UDPBindError::OK
-	}
+/// Same as `udpSocketGetLastReceivedPacketSize`, with the device passed along. The device must be the one of the socket.
+#[no_mangle]
+pub extern "C" fn udpGetLastReceivedPacketSize(c_device: CDevicePtr, c_handle: *mut UDPSocketHandle) -> u32 {
+	guard("udpGetLastReceivedPacketSize", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Udp);
+		udpSocketGetLastReceivedPacketSize(c_handle)
+	})
 }
 
+/// Copies the oldest datagram into `dst` and its source into `endpoint`. The size of the datagram is written to `datagram_size` (if not NULL). If `size` is too small, the datagram is left in the socket and `BufferInsufficient` is returned. Returns `Illegal` if `endpoint` is NULL.
 #[no_mangle]
-pub extern "C" fn udpGetLastReceivedPacketSize(
-	c_device: CDevicePtr,
+pub extern "C" fn udpSocketReceive(
 	c_handle: *mut UDPSocketHandle,
-) -> u32 {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	//let cx = c_device.iface.context();
+	endpoint: *mut CIPEndpoint,
+	dst: *mut u8,
+	size: u32,
+	datagram_size: *mut u32,
+) -> UDPRecvError {
+	guard("udpSocketReceive", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Udp);
+		//let cx = c_device.iface.context();
+		let endpoint = match unsafe { endpoint.as_mut() } {
+			Some(endpoint) => endpoint,
+			None => {
+				set_last_error(ResultCode::Illegal, "udpSocketReceive: got NULL `endpoint`".to_string());
+				return UDPRecvError::Illegal;
+			}
+		};
 
-	let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
+		let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
 
-	match socket.peek() {
-		Ok((data, _source_endpoint)) => {
-			return data.len() as u32;
+		let len = match socket.peek() {
+			Ok((data, _source_endpoint)) => data.len(),
+			Err(err) => return err.into(),
+		};
+		if let Some(datagram_size) = unsafe { datagram_size.as_mut() } {
+			*datagram_size = len as u32;
 		}
-		Err(_) => {
-			return 0;
+		if len > size as usize {
+			set_last_error(
+				ResultCode::BufferInsufficient,
+				format!("udpSocketReceive: the datagram is {} bytes, but the buffer is only {}", len, size),
+			);
+			return UDPRecvError::BufferInsufficient;
 		}
-	};
+		let slc = host_slice_mut!("udpSocketReceive", dst, len);
+
+		match socket.recv() {
+			Ok((data, source_endpoint)) => {
+				*endpoint = source_endpoint.into();
+				slc.copy_from_slice(data);
+				UDPRecvError::OK
+			}
+			Err(err) => err.into(),
+		}
+	})
 }
 
+/// Same as `udpSocketReceive`, with the device passed along. The device must be the one of the socket.
 #[no_mangle]
 pub extern "C" fn udpReceive(
 	c_device: CDevicePtr,
 	c_handle: *mut UDPSocketHandle,
-	endpoint: &mut CIPEndpoint,
+	endpoint: *mut CIPEndpoint,
 	dst: *mut u8,
 	size: u32,
+	datagram_size: *mut u32,
 ) -> UDPRecvError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	//let cx = c_device.iface.context();
-
-	let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
-
-	match socket.recv() {
-		Ok((data, source_endpoint)) => {
-			let slc = unsafe { slice::from_raw_parts_mut(dst, size as usize) };
-			*endpoint = source_endpoint.into();
-			let len = data.len();
-			let (left, _right) = slc.split_at_mut(len);
-			left.copy_from_slice(data);
-			UDPRecvError::OK
-		}
-		Err(err) => err.into(),
-	}
+	guard("udpReceive", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Udp);
+		udpSocketReceive(c_handle, endpoint, dst, size, datagram_size)
+	})
 }
 
 #[no_mangle]
-pub extern "C" fn udpSend(
-	c_device: CDevicePtr,
+pub extern "C" fn udpSocketSend(
 	c_handle: *mut UDPSocketHandle,
 	endpoint: CIPEndpoint,
 	data: *const u8,
 	size: u32,
 ) -> UDPSendError {
-	let c_device = unsafe { device_from_opaque_ptr(c_device) };
-	let handle = unsafe { *c_handle.as_ref().unwrap() };
-	//let cx = c_device.iface.context();
-	let slc = unsafe { slice::from_raw_parts(data, size as usize) };
+	guard("udpSocketSend", || {
+		let (c_device, handle) = checked_socket!(c_handle, CSocketKind::Udp);
+		//let cx = c_device.iface.context();
+		let slc = host_slice!("udpSocketSend", data, size);
 
-	let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
+		let socket = c_device.sockets.get_mut::<udp::Socket>(handle);
 
-	let endpoint: IpEndpoint = endpoint.into();
-	socket.send_slice(slc, endpoint).into()
+		let endpoint: IpEndpoint = endpoint.into();
+		socket.send_slice(slc, endpoint).into()
+	})
+}
+
+/// Same as `udpSocketSend`, with the device passed along. The device must be the one of the socket.
+#[no_mangle]
+pub extern "C" fn udpSend(
+	c_device: CDevicePtr,
+	c_handle: *mut UDPSocketHandle,
+	endpoint: CIPEndpoint,
+	data: *const u8,
+	size: u32,
+) -> UDPSendError {
+	guard("udpSend", || {
+		let c_device = unsafe { device_from_opaque_ptr(c_device) };
+		checked_handle!(c_device, c_handle, CSocketKind::Udp);
+		udpSocketSend(c_handle, endpoint, data, size)
+	})
 }
 
 //	let socket = *c_handle.as_ref().unwrap();
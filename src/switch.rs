@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+
+use smoltcp::phy::Medium;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, EthernetFrame};
+
+use crate::cdevice::{device_from_opaque_ptr, CDevicePtr};
+use crate::device::{QueueOverflowPolicy, SansIOQueue};
+use crate::ffi::{guard, pop_frame_to_host, set_last_error};
+use crate::link::{attach_device, detach_device, network_from_opaque_ptr, MAX_ROUNDS_PER_TICK};
+use crate::result_codes::ResultCode;
+
+/// Used when no ageing time is given, the default of IEEE 802.1D.
+const DEFAULT_AGEING_TIME: Duration = Duration::from_secs(300);
+
+/// Max count of frames waiting to be taken by the host, the oldest ones are dropped after it.
+const HOST_QUEUE_LIMIT: usize = 1024;
+
+/// Where a frame enters or leaves the switch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SwitchPort {
+	Device(CDevicePtr),
+	/// The frames injected by the host and the ones it takes out, see `switchInjectFrame` and `switchPopHostFrame`.
+	Host,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MacEntry {
+	port: SwitchPort,
+	last_seen: Instant,
+}
+
+/// What a switch has done so far.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct CSwitchStats {
+	/// Frames that entered the switch.
+	pub frames: u64,
+	/// Frames sent out of the only port their destination was learned on.
+	pub forwarded: u64,
+	/// Frames sent out of all the ports but the one they came from, because their destination is broadcast, multicast or unknown.
+	pub flooded: u64,
+	/// Frames not sent anywhere, because their destination was learned on the port they came from.
+	pub filtered: u64,
+	/// Frames too short to be Ethernet frames.
+	pub malformed: u64,
+	/// Copies of frames refused by the RX queues of the devices.
+	pub dropped: u64,
+}
+
+/// A learning Ethernet switch between devices of `Medium::Ethernet` within the process, with a port for the host.
+#[derive(Debug)]
+pub struct Switch {
+	devices: Vec<CDevicePtr>,
+	table: BTreeMap<EthernetAddress, MacEntry>,
+	ageing_time: Duration,
+	/// The moment of the last tick, the one the frames injected by the host are learned at.
+	now: Instant,
+	/// The frames sent out of the host port.
+	host: SansIOQueue,
+	stats: CSwitchStats,
+}
+
+impl Switch {
+	pub fn new(ageing_time: Duration) -> Switch {
+		let mut host = SansIOQueue::default();
+		host.set_limit(HOST_QUEUE_LIMIT, QueueOverflowPolicy::DropOldest);
+		Switch {
+			devices: Vec::new(),
+			table: BTreeMap::new(),
+			ageing_time,
+			now: Instant::from_millis(0),
+			host,
+			stats: CSwitchStats::default(),
+		}
+	}
+
+	pub fn stats(&self) -> CSwitchStats {
+		self.stats
+	}
+
+	/// Count of addresses learned and not aged out yet.
+	pub fn table_len(&self) -> usize {
+		self.table.len()
+	}
+
+	/// Attaches a device, which must be of `Medium::Ethernet`, transmit into its TX queue and stay alive until it is detached.
+	///
+	/// # Safety
+	/// `device` must point to a live `CDevice`.
+	pub unsafe fn attach(&mut self, device: CDevicePtr) -> Result<(), String> {
+		if self.devices.contains(&device) {
+			return Err("the device is already attached".to_string());
+		}
+		attach_device(device_from_opaque_ptr(device), Some(Medium::Ethernet))?;
+		self.devices.push(device);
+		Ok(())
+	}
+
+	/// Detaches a device, forgetting the addresses learned on its port. Returns whether it was attached.
+	///
+	/// # Safety
+	/// `device` must point to a live `CDevice`.
+	pub unsafe fn detach(&mut self, device: CDevicePtr) -> bool {
+		match self.devices.iter().position(|&attached| attached == device) {
+			Some(index) => {
+				self.devices.remove(index);
+				self.table.retain(|_, entry| entry.port != SwitchPort::Device(device));
+				detach_device(device_from_opaque_ptr(device));
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Forgets the addresses not seen for the ageing time by `now`.
+	fn age(&mut self, now: Instant) {
+		let ageing_time = self.ageing_time;
+		self.table.retain(|_, entry| now < entry.last_seen + ageing_time);
+	}
+
+	/// Learns the source of a frame coming from `from` and sends it out of the port of its destination, or floods it.
+	fn switch(&mut self, from: SwitchPort, frame: &[u8]) {
+		self.stats.frames += 1;
+		let (src, dst) = match EthernetFrame::new_checked(frame) {
+			Ok(ethernet) => (ethernet.src_addr(), ethernet.dst_addr()),
+			Err(_) => {
+				self.stats.malformed += 1;
+				return;
+			}
+		};
+
+		if src.is_unicast() {
+			self.table.insert(
+				src,
+				MacEntry {
+					port: from,
+					last_seen: self.now,
+				},
+			);
+		}
+
+		let known = if dst.is_unicast() {
+			self.table.get(&dst).map(|entry| entry.port)
+		} else {
+			None
+		};
+		match known {
+			Some(port) if port == from => self.stats.filtered += 1,
+			Some(port) => {
+				self.stats.forwarded += 1;
+				self.send(port, frame);
+			}
+			None => {
+				self.stats.flooded += 1;
+				let mut ports: Vec<SwitchPort> = self.devices.iter().map(|&device| SwitchPort::Device(device)).collect();
+				ports.push(SwitchPort::Host);
+				for port in ports {
+					if port != from {
+						self.send(port, frame);
+					}
+				}
+			}
+		}
+	}
+
+	fn send(&mut self, port: SwitchPort, frame: &[u8]) {
+		let res = match port {
+			SwitchPort::Device(device) => unsafe { device_from_opaque_ptr(device) }.device.put_rx(frame.to_vec().into()),
+			SwitchPort::Host => self.host.push(frame.to_vec()),
+		};
+		if res.is_err() {
+			self.stats.dropped += 1;
+		}
+	}
+
+	/// Puts a frame from the host into the switch, as if it came from the host port.
+	pub fn inject(&mut self, frame: &[u8]) {
+		self.switch(SwitchPort::Host, frame);
+	}
+
+	/// Takes out the oldest frame sent out of the host port.
+	pub fn pop_host(&mut self) -> Option<Vec<u8>> {
+		self.host.pop()
+	}
+
+	/// Ages the table out, then polls every device at `timestamp` and switches the frames they have transmitted, over and over until no frame is left in flight. Returns the count of frames switched.
+	pub fn tick(&mut self, timestamp: Instant) -> u64 {
+		self.now = timestamp;
+		self.age(timestamp);
+		let frames_before = self.stats.frames;
+		for _ in 0..MAX_ROUNDS_PER_TICK {
+			let mut frames = Vec::new();
+			for &device in &self.devices {
+				let c_device = unsafe { device_from_opaque_ptr(device) };
+				c_device.poll(timestamp);
+				frames.extend(c_device.device.tx.drain().map(|frame| (device, frame)));
+			}
+			if frames.is_empty() {
+				break;
+			}
+			for (device, frame) in frames {
+				self.switch(SwitchPort::Device(device), &frame);
+			}
+		}
+		self.stats.frames - frames_before
+	}
+}
+
+impl Drop for Switch {
+	fn drop(&mut self) {
+		for &device in &self.devices {
+			detach_device(unsafe { device_from_opaque_ptr(device) });
+		}
+	}
+}
+
+pub type CSwitchPtr = *mut Switch;
+
+/// Creates a switch with no devices attached yet. Learned addresses are forgotten after not being seen for `ageing_ms` milliseconds, 0 means the default of 300 seconds.
+#[no_mangle]
+pub extern "C" fn newSwitch(ageing_ms: u32) -> CSwitchPtr {
+	guard("newSwitch", || {
+		let ageing_time = if ageing_ms == 0 {
+			DEFAULT_AGEING_TIME
+		} else {
+			Duration::from_millis(u64::from(ageing_ms))
+		};
+		Box::into_raw(Box::new(Switch::new(ageing_time)))
+	})
+}
+
+/// Frees the switch, detaching the devices from it.
+#[no_mangle]
+pub extern "C" fn deleteSwitch(c_switch: CSwitchPtr) {
+	guard("deleteSwitch", || unsafe { drop(Box::from_raw(network_from_opaque_ptr(c_switch))) })
+}
+
+/// Attaches the device to a port of the switch. The device must be of `Medium::Ethernet` and transmit into its TX queue, as for `linkAttach`. It cannot be freed or given another TX sink until detached. Returns `Illegal` otherwise.
+#[no_mangle]
+pub extern "C" fn switchAttach(c_switch: CSwitchPtr, c_device: CDevicePtr) -> ResultCode {
+	guard("switchAttach", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		unsafe { device_from_opaque_ptr(c_device) };
+		match unsafe { switch.attach(c_device) } {
+			Ok(()) => ResultCode::OK,
+			Err(err) => {
+				set_last_error(ResultCode::Illegal, format!("switchAttach: {}", err));
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Detaches the device from the switch. Returns `Illegal` if it is not attached.
+#[no_mangle]
+pub extern "C" fn switchDetach(c_switch: CSwitchPtr, c_device: CDevicePtr) -> ResultCode {
+	guard("switchDetach", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		unsafe { device_from_opaque_ptr(c_device) };
+		if unsafe { switch.detach(c_device) } {
+			ResultCode::OK
+		} else {
+			set_last_error(ResultCode::Illegal, "switchDetach: the device is not attached to the switch".to_string());
+			ResultCode::Illegal
+		}
+	})
+}
+
+/// Polls the devices of the switch at the moment `millis` of the caller's clock and switches the frames they transmit, see `Switch::tick`. Returns the count of frames switched.
+#[no_mangle]
+pub extern "C" fn switchTick(c_switch: CSwitchPtr, millis: i64) -> u64 {
+	guard("switchTick", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		switch.tick(Instant::from_millis(millis))
+	})
+}
+
+/// Puts a frame into the switch through the host port. It reaches the devices at their next poll. Returns `Illegal` if `src` is NULL.
+#[no_mangle]
+pub extern "C" fn switchInjectFrame(c_switch: CSwitchPtr, src: *const u8, size: u32) -> ResultCode {
+	guard("switchInjectFrame", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		let src = host_slice!("switchInjectFrame", src, size);
+		switch.inject(src);
+		ResultCode::OK
+	})
+}
+
+/// Copies the oldest frame sent out of the host port into `dst` and removes it. The size of the frame is written to `frame_size` (if not NULL). If `size` is too small, the frame is kept and `BufferInsufficient` is returned; if there is no frame, `Exhausted` is returned.
+#[no_mangle]
+pub extern "C" fn switchPopHostFrame(c_switch: CSwitchPtr, dst: *mut u8, size: u32, frame_size: *mut usize) -> ResultCode {
+	guard("switchPopHostFrame", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		unsafe { pop_frame_to_host("switchPopHostFrame", &mut switch.host, dst, size, frame_size) }
+	})
+}
+
+/// Writes what the switch has done so far into `stats`.
+#[no_mangle]
+pub extern "C" fn switchGetStats(c_switch: CSwitchPtr, stats: *mut CSwitchStats) -> ResultCode {
+	guard("switchGetStats", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		match unsafe { stats.as_mut() } {
+			Some(stats) => {
+				*stats = switch.stats();
+				ResultCode::OK
+			}
+			None => {
+				set_last_error(ResultCode::Illegal, "switchGetStats: got NULL stats pointer".to_string());
+				ResultCode::Illegal
+			}
+		}
+	})
+}
+
+/// Returns the count of addresses in the table of the switch, aged out at each tick.
+#[no_mangle]
+pub extern "C" fn switchGetMacTableSize(c_switch: CSwitchPtr) -> usize {
+	guard("switchGetMacTableSize", || {
+		let switch = unsafe { network_from_opaque_ptr(c_switch) };
+		switch.table_len()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use smoltcp::wire::{EthernetProtocol, EthernetRepr};
+
+	const HOST_MAC: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 1]);
+
+	fn frame(src: EthernetAddress, dst: EthernetAddress) -> Vec<u8> {
+		let repr = EthernetRepr {
+			src_addr: src,
+			dst_addr: dst,
+			ethertype: EthernetProtocol::Ipv4,
+		};
+		let mut frame = vec![0; repr.buffer_len() + 46];
+		repr.emit(&mut EthernetFrame::new_unchecked(&mut frame[..]));
+		frame
+	}
+
+	#[test]
+	fn addresses_are_learned_filtered_and_aged_out() {
+		let mut switch = Switch::new(Duration::from_secs(10));
+		switch.inject(&frame(HOST_MAC, EthernetAddress::BROADCAST));
+		assert_eq!(switch.table_len(), 1);
+		// Not sent back out of the port it came from.
+		assert_eq!(switch.pop_host(), None);
+
+		switch.inject(&frame(EthernetAddress([0x02, 0, 0, 0, 0, 2]), HOST_MAC));
+		assert_eq!(switch.table_len(), 2);
+		switch.inject(&[0; 10]);
+
+		let stats = switch.stats();
+		assert_eq!((stats.frames, stats.flooded, stats.filtered, stats.malformed), (3, 1, 1, 1));
+
+		switch.tick(Instant::from_millis(9999));
+		assert_eq!(switch.table_len(), 2);
+		switch.tick(Instant::from_millis(10000));
+		assert_eq!(switch.table_len(), 0);
+	}
+}